@@ -0,0 +1,87 @@
+//! Small-object allocation should cost the same per object no matter how many objects are
+//! already live. Each run uses a fresh thread, and with it a fresh heap. The bench fails if
+//! allocating from the spans with a million objects live costs several times what it does with
+//! ten thousand, which is far more slack than timing noise needs and far less than a search
+//! through the live objects would take.
+//!
+//!     cargo bench --bench alloc
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use defunct::testing::alloc_buffer;
+use defunct::Vm;
+use defunct::compiler::compile;
+use defunct::global::Global;
+
+fn per_object(elapsed: Duration, objects: usize) -> f64 {
+    elapsed.as_nanos() as f64 / objects as f64
+}
+
+// The most the cost per object may grow between the smallest and largest runs of a size.
+const MAX_GROWTH: f64 = 3.0;
+
+// Allocates straight from the spans, so every object stays live. The best of a few runs is
+// taken, since anything else running can only slow one down.
+fn span_alloc(size: usize, objects: usize) -> f64 {
+    let run = move || std::thread::spawn(move || {
+        let start = Instant::now();
+        for _ in 0..objects {
+            alloc_buffer(black_box(size));
+        }
+        per_object(start.elapsed(), objects)
+    }).join().unwrap();
+    (0..3).map(|_| run()).fold(f64::INFINITY, f64::min)
+}
+
+const CHURN: &str = "
+(do
+  (set churn
+    (fn [n]
+      (if (< n 1)
+        (vector-length keep)
+        (let [m {:n n}]
+          (vector-push! keep m)
+          (churn (+ n -1))))))
+  (set round
+    (fn [n]
+      (if (< n 1)
+        0
+        (do
+          (set keep [])
+          (churn 2000)
+          (round (+ n -1)))))))
+";
+
+// Allocates maps through the VM, with collections running as they normally would.
+fn vm_alloc(rounds: usize) -> f64 {
+    std::thread::spawn(move || {
+        let mut global = Global::new();
+        let mut eval = |src: &str| {
+            let bytecode = compile(src, &mut global.st).unwrap().pop().unwrap();
+            Vm::new(&mut global, bytecode, &[], false).run()
+        };
+        eval(CHURN);
+        let start = Instant::now();
+        eval(&format!("(round {})", rounds));
+        per_object(start.elapsed(), rounds * 2000)
+    }).join().unwrap()
+}
+
+fn main() {
+    println!("span allocation");
+    for size in [16, 64, 256] {
+        let costs: Vec<f64> = [10_000, 100_000, 1_000_000].into_iter().map(|objects| {
+            let cost = span_alloc(size, objects);
+            println!("  {:>4} B x {:>9}: {:>7.1} ns/object", size, objects, cost);
+            cost
+        }).collect();
+        let growth = costs[costs.len() - 1] / costs[0];
+        println!("  {:>4} B growth: {:.2}x", size, growth);
+        assert!(growth < MAX_GROWTH, "allocating {} B objects slows down as more are live", size);
+    }
+    println!("vm allocation");
+    for rounds in [1, 10, 100] {
+        println!("  {:>9} maps: {:>7.1} ns/object", rounds * 2000, vm_alloc(rounds));
+    }
+}
@@ -1,28 +1,51 @@
-use super::{PAGE_SIZE, ARENA_SIZE};
+use super::PAGE_SIZE;
+use super::Span;
+use super::os;
 
-const ARENA_BITS_SIZE: usize = ARENA_COUNT / 8;
-const ARENA_COUNT: usize = ARENA_SIZE / PAGE_SIZE;
-type ArenaBits = [u8; ARENA_BITS_SIZE];
+use std::collections::{BTreeMap, BTreeSet};
 
-// 64MiB arena of 8KiB pages with a bitmap tracking page allocation.
+type ArenaBits = Box<[u64]>;
+
+// Arena of 8KiB pages, 64MiB by default, with a bitmap tracking page allocation.
+// Each page also records the span it was handed to, so that pointers can be mapped back to their slot.
+// Free pages are kept as maximal runs, indexed both by position and by length, so a request is
+// served from the smallest run that fits and freed runs merge with their neighbours.
 pub struct Arena {
     base: *mut u8,
+    // Size in pages, always a multiple of 64.
+    pages: usize,
     count: usize,
     bits: ArenaBits,
+    spans: Box<[*mut Span]>,
+    // start page -> length
+    free_runs: BTreeMap<usize, usize>,
+    // (length, start page)
+    free_by_length: BTreeSet<(usize, usize)>,
+    // Free pages that may still be backed by memory, because they were used since the
+    // last time free pages were returned to the OS.
+    dirty: ArenaBits,
 }
 
 impl Arena {
-    pub fn new() -> Arena {
-        use std::alloc::*;
-        unsafe {
-            let layout = Layout::from_size_align(
-                ARENA_SIZE, PAGE_SIZE
-            ).expect("Arena allocation was misaligned.");
-            let base = alloc(layout);
-            Arena {
-                base, bits: [0u8; ARENA_BITS_SIZE], count: 0
-            }
-        }
+    // The arena is only reserved; its pages take up memory once they are first used.
+    pub fn new(size: usize) -> Arena {
+        assert!(size > 0 && size.is_multiple_of(64 * PAGE_SIZE), "Arena size must be a multiple of 64 pages.");
+        let pages = size / PAGE_SIZE;
+        let base = os::reserve(size, PAGE_SIZE);
+        let mut arena = Arena {
+            base, pages, count: 0,
+            bits: vec![0; pages / 64].into_boxed_slice(),
+            spans: vec![std::ptr::null_mut(); pages].into_boxed_slice(),
+            free_runs: BTreeMap::new(),
+            free_by_length: BTreeSet::new(),
+            dirty: vec![0; pages / 64].into_boxed_slice(),
+        };
+        arena.insert_run(0, pages);
+        arena
+    }
+
+    pub fn size(&self) -> usize {
+        self.pages * PAGE_SIZE
     }
 
     pub fn base(&mut self) -> *mut u8 {
@@ -37,74 +60,203 @@ impl Arena {
         self.count
     }
 
+    pub fn largest_free_run(&self) -> usize {
+        self.free_by_length.last().map_or(0, |&(length, _)| length)
+    }
+
+    // How many separate runs the free pages are split into.
+    pub fn fragments(&self) -> usize {
+        self.free_runs.len()
+    }
+
+    fn insert_run(&mut self, start: usize, length: usize) {
+        self.free_runs.insert(start, length);
+        self.free_by_length.insert((length, start));
+    }
+
+    fn remove_run(&mut self, start: usize, length: usize) {
+        self.free_runs.remove(&start);
+        self.free_by_length.remove(&(length, start));
+    }
+
+    fn set_bits(bits: &mut ArenaBits, start: usize, npages: usize, value: bool) {
+        let mut page = start;
+        let end = start + npages;
+        while page < end {
+            let bit = page % 64;
+            let n = (64 - bit).min(end - page);
+            let mask = if n == 64 { !0 } else { ((1u64 << n) - 1) << bit };
+            if value {
+                bits[page / 64] |= mask;
+            } else {
+                bits[page / 64] &= !mask;
+            }
+            page += n;
+        }
+    }
+
+    // Best fit: takes the front of the smallest free run that is long enough.
     pub fn try_alloc(&mut self, npages: usize) -> Option<*mut u8> {
-        if npages == 0 || npages > ARENA_COUNT {
+        if npages == 0 || npages > self.pages {
             panic!("Tried to allocate a span with a bad number of pages.");
         }
 
-        if npages + self.count > ARENA_COUNT {
-            return None;
+        let &(length, start) = self.free_by_length.range((npages, 0)..).next()?;
+        self.remove_run(start, length);
+        if length > npages {
+            self.insert_run(start + npages, length - npages);
+        }
+        Arena::set_bits(&mut self.bits, start, npages, true);
+        Arena::set_bits(&mut self.dirty, start, npages, false);
+        self.count += npages;
+        Some(unsafe { self.base.add(start * PAGE_SIZE) })
+    }
+
+    pub fn dealloc(&mut self, start: *mut u8, npages: usize) {
+        let offset = start as isize - self.base as isize;
+        assert!(offset >= 0 && offset < self.size() as isize);
+        assert!(npages * PAGE_SIZE + (offset as usize) <= self.size());
+
+        let page = offset as usize / PAGE_SIZE;
+        Arena::set_bits(&mut self.bits, page, npages, false);
+        Arena::set_bits(&mut self.dirty, page, npages, true);
+        self.count -= npages;
+
+        // Coalesce with the free runs on either side.
+        let (mut start, mut length) = (page, npages);
+        if let Some((&before, &before_length)) = self.free_runs.range(..page).next_back()
+            && before + before_length == page {
+            self.remove_run(before, before_length);
+            start = before;
+            length += before_length;
+        }
+        if let Some(&after_length) = self.free_runs.get(&(page + npages)) {
+            self.remove_run(page + npages, after_length);
+            length += after_length;
         }
+        self.insert_run(start, length);
+    }
 
-        let total_pages = ARENA_COUNT;
-        let bits = &mut self.bits;
-
-        let mut start = 0;
-        'outer: while start < total_pages {
-            let mut count = 0;
-            for i in start..total_pages {
-                if (bits[i / 8] & (1 << (i % 8))) != 0 {
-                    start = i + 1;
-                    continue 'outer;
-                }
-                count += 1;
-
-                if count >= npages {
-                    // mark bits
-                    for i in start..(start + count) {
-                        bits[i / 8] |= 1 << (i % 8);
-                    }
-                    self.count += npages;
-                    let addr = self.base as usize + start * PAGE_SIZE;
-                    return Some(addr as *mut u8)
-                }
+    // Hands dirty free pages back to the OS, at most `limit` of them, starting from the front.
+    // Returns how many pages were released.
+    pub fn release_free_pages(&mut self, limit: usize) -> usize {
+        let mut released = 0;
+        let mut page = 0;
+        while page < self.pages && released < limit {
+            if self.dirty[page / 64] == 0 {
+                page = (page / 64 + 1) * 64;
+                continue;
+            }
+            if self.dirty[page / 64] & (1 << (page % 64)) == 0 {
+                page += 1;
+                continue;
             }
-            break;
+            let start = page;
+            while page < self.pages && page - start < limit - released && self.dirty[page / 64] & (1 << (page % 64)) != 0 {
+                page += 1;
+            }
+            os::decommit(unsafe { self.base.add(start * PAGE_SIZE) }, (page - start) * PAGE_SIZE);
+            Arena::set_bits(&mut self.dirty, start, page - start, false);
+            released += page - start;
         }
+        released
+    }
 
-        None
+    pub fn contains(&self, ptr: *const u8) -> bool {
+        let addr = ptr.addr();
+        addr >= self.base.addr() && addr < self.base.addr() + self.size()
     }
 
-    pub fn dealloc(&mut self, start: *mut u8, npages: usize) {
-        let offset = start as isize - self.base as isize;
-        assert!(offset >= 0 && offset < ARENA_SIZE as isize);
-        assert!(npages * PAGE_SIZE + (offset as usize) <= ARENA_SIZE);
+    pub fn span_at(&self, ptr: *const u8) -> Option<*mut Span> {
+        if !self.contains(ptr) {
+            return None;
+        }
+        let span = self.spans[(ptr.addr() - self.base.addr()) / PAGE_SIZE];
+        if span.is_null() { None } else { Some(span) }
+    }
 
-        let page = offset as usize / PAGE_SIZE;
-        for i in page..page + npages {
-            self.bits[i / 8] &= !(1 << i % 8);
+    // Points every page of the span's run at it, or clears them if span is null.
+    pub fn set_span(&mut self, start: *mut u8, npages: usize, span: *mut Span) {
+        let page = (start.addr() - self.base.addr()) / PAGE_SIZE;
+        self.spans[page..page + npages].fill(span);
+    }
+
+    // Checks that every page of the span's run is allocated and maps back to it.
+    pub fn verify_span(&self, span: &Span) -> Result<(), String> {
+        let page = (span.base.addr() - self.base.addr()) / PAGE_SIZE;
+        for i in page..page + span.pages as usize {
+            if self.bits[i / 64] & (1 << (i % 64)) == 0 {
+                return Err(format!("page {} of the span at {:x} is free in its arena",
+                    i - page, span.base.addr()));
+            }
+            if !std::ptr::eq(self.spans[i], span) {
+                return Err(format!("page {} of the span at {:x} maps to another span",
+                    i - page, span.base.addr()));
+            }
         }
-        self.count -= npages;
+        Ok(())
+    }
+
+    // Checks the page bitmap against count and the free runs, and that exactly `span_pages`
+    // pages belong to spans.
+    pub fn verify(&self, span_pages: usize) -> Result<(), String> {
+        let allocated: u32 = self.bits.iter().map(|word| word.count_ones()).sum();
+        if allocated as usize != self.count {
+            return Err(format!("arena at {:x} counts {} pages but has {} allocated",
+                self.base.addr(), self.count, allocated));
+        }
+        if span_pages != self.count {
+            return Err(format!("arena at {:x} has {} pages allocated but its spans hold {}",
+                self.base.addr(), self.count, span_pages));
+        }
+        let mapped = self.spans.iter().filter(|span| !span.is_null()).count();
+        if mapped != self.count {
+            return Err(format!("arena at {:x} maps {} pages to spans but has {} allocated",
+                self.base.addr(), mapped, self.count));
+        }
+        if self.free_runs.len() != self.free_by_length.len() {
+            return Err(format!("arena at {:x} indexes {} free runs by position but {} by length",
+                self.base.addr(), self.free_runs.len(), self.free_by_length.len()));
+        }
+        let mut free = 0;
+        let mut previous_end = None;
+        for (&start, &length) in self.free_runs.iter() {
+            let is_free = |page: usize| self.bits[page / 64] & (1 << (page % 64)) == 0;
+            if !self.free_by_length.contains(&(length, start)) || !(start..start + length).all(is_free) {
+                return Err(format!("arena at {:x} has a bad free run of {} pages at page {}",
+                    self.base.addr(), length, start));
+            }
+            if previous_end == Some(start) {
+                return Err(format!("arena at {:x} did not coalesce the free run at page {}",
+                    self.base.addr(), start));
+            }
+            previous_end = Some(start + length);
+            free += length;
+        }
+        for i in 0..self.pages / 64 {
+            if self.dirty[i] & self.bits[i] != 0 {
+                return Err(format!("arena at {:x} has allocated pages marked dirty", self.base.addr()));
+            }
+        }
+        if free + self.count != self.pages {
+            return Err(format!("arena at {:x} has {} free pages in runs but {} unallocated",
+                self.base.addr(), free, self.pages - self.count));
+        }
+        Ok(())
     }
 
     pub fn print_alloc_bits(&self, max: usize) {
-        let n = if max == 0 { ARENA_BITS_SIZE } else { max.div_ceil(8) };
+        let n = if max == 0 { self.pages } else { max.min(self.pages) };
         for i in 0..n {
-            print!("{:08b}", self.bits[i].reverse_bits());
+            print!("{}", self.bits[i / 64] >> (i % 64) & 1);
         }
-        println!("");
+        println!();
     }
 }
 
 
 impl Drop for Arena {
     fn drop(&mut self) {
-        use std::alloc::*;
-        let layout = Layout::from_size_align(
-                ARENA_SIZE, PAGE_SIZE
-        ).expect("Arena allocation was misaligned.");
-        unsafe {
-            std::alloc::dealloc(self.base, layout);
-        }
+        os::release(self.base, self.size(), PAGE_SIZE);
     }
 }
\ No newline at end of file
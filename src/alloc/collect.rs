@@ -0,0 +1,217 @@
+//! Collection happens in two generations.
+//!
+//! A minor collection copies every live nursery object into the spans, leaving a forwarding
+//! address behind, and then hands the whole nursery back to the bump allocator.
+//!
+//! A major collection marks the spans incrementally. Marking starts from the roots and is
+//! advanced in slices bounded by the pause budget, interleaved with the mutator. The write
+//! barrier shades every value stored while marking, so no white object is ever hidden inside
+//! a black one. Once the gray stack runs dry the nursery is emptied by an ordinary minor
+//! collection, and the very next safepoint rescans the roots and carries on marking. If that
+//! turns up more than a slice has room for, marking resumes as usual and tries to finish again
+//! later, keeping what it marked. The spans are
+//! then swept a slice at a time, though allocation sweeps its own size class first so that it
+//! never takes a fresh span while an old one could have room. Allocations too big for any
+//! size class are mapped on their own and unmapped by the same sweep once they go unmarked.
+//! Last of all, the pages the sweep freed are returned to the OS, again a slice at a time.
+//!
+//! The budget counts work rather than time, so a collection goes the same way on any machine.
+//!
+//! Objects with finalizers that were not marked are revived for one more cycle and queued.
+//! Their finalizers fall due once the sweep has finished, and run when the mutator asks for
+//! them at a safepoint.
+
+use std::time::Instant;
+
+use crate::values::{Header, Val};
+use super::heap::{Finalizer, Phase};
+
+/// Visits every root slot with the given visitor. May be called more than once per collection.
+pub type Roots<'a> = &'a mut dyn FnMut(&mut dyn FnMut(&mut Val));
+
+/// Does whatever collection work is due. Must only be called when every live value
+/// is reachable from `roots`, from the heap itself, or from a root or handle held by the host.
+pub fn safepoint(roots: Roots) {
+    let mut with_host = |visit: &mut dyn FnMut(&mut Val)| {
+        roots(visit);
+        visit_host_roots(visit);
+    };
+    let roots: Roots = &mut with_host;
+    let (minor, slice) = super::HEAP.with(|heap| {
+        let heap = heap.borrow();
+        (heap.minor_requested, heap.slice_due() || heap.full_requested && heap.phase != Phase::Idle)
+    });
+    if minor {
+        minor_collect(roots);
+    }
+    // A collection already under way when a full one is asked for is finished first.
+    if slice {
+        collect_slice(roots);
+    }
+    if super::HEAP.with(|heap| heap.borrow().wants_major()) {
+        super::HEAP.with(|heap| heap.borrow_mut().start_major());
+        shade_roots(roots);
+        collect_slice(roots);
+    }
+}
+
+// Advances the collection in progress by at most the pause budget, or finishes it outright
+// if a full collection was asked for.
+fn collect_slice(roots: Roots) {
+    let started = Instant::now();
+    let budget = super::HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.start_slice();
+        match heap.full_requested {
+            true => None,
+            false => heap.pause_budget,
+        }
+    });
+    if super::HEAP.with(|heap| heap.borrow().phase == Phase::Mark) {
+        mark_slice(roots, budget);
+    }
+    super::HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        if heap.phase == Phase::Sweep {
+            heap.sweep_spans(budget);
+        }
+        heap.finish_slice(started.elapsed());
+    });
+}
+
+// Young objects are never marked, so the nursery is emptied before marking can finish; its
+// survivors are allocated black and shade their children as they are promoted. Within a budget
+// that is left to the minor collection the next safepoint runs first, so that the slice right
+// after it only has what one step allocated to promote. Anything the roots picked up since
+// marking began is caught by the rescan, and if that and the weak maps leave more to mark than
+// the slice has room for, a later slice tries again. Weak objects are cleared only once
+// everything reachable has been marked, and unreachable objects with finalizers have been
+// revived. Reviving them is the one step left unbudgeted, since weak references must not be
+// cleared until it is done, and it only does work for objects that just died.
+fn mark_slice(roots: Roots, budget: Option<usize>) {
+    let finishing = super::HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        if !heap.drain(budget) {
+            return false;
+        }
+        if budget.is_some() && !heap.terminating {
+            heap.terminating = true;
+            heap.minor_requested = true;
+            return false;
+        }
+        heap.terminating = false;
+        true
+    });
+    if !finishing {
+        return;
+    }
+    minor_collect(roots);
+    shade_roots(roots);
+    super::HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        if heap.drain(budget) && heap.mark_ephemerons(budget) {
+            heap.queue_finalizers();
+            heap.clear_weak();
+            heap.start_sweep();
+        }
+    });
+}
+
+// The host's roots are taken out of the heap while they are visited, since visiting
+// them may promote or shade objects.
+fn visit_host_roots(visit: &mut dyn FnMut(&mut Val)) {
+    let mut host = super::HEAP.with(|heap| std::mem::take(&mut heap.borrow_mut().host_roots));
+    host.visit(visit);
+    super::HEAP.with(|heap| heap.borrow_mut().host_roots = host);
+}
+
+fn shade_roots(roots: Roots) {
+    roots(&mut |slot| super::HEAP.with(|heap| heap.borrow_mut().shade(*slot)));
+}
+
+/// Promotes all nursery objects reachable from the roots or from the remembered set.
+fn minor_collect(roots: Roots) {
+    let mut promoted = Vec::new();
+    {
+        roots(&mut |slot| evacuate(slot, &mut promoted, true));
+
+        let remembered = super::HEAP.with(|heap|
+            heap.borrow_mut().take_remembered()
+        );
+        for container in remembered {
+            let strong = !container.is_weak();
+            container.trace(&mut |slot| evacuate(slot, &mut promoted, strong));
+        }
+
+        // Only major collections decide whether a finalizable object is dead, so it is always
+        // promoted; marking it here would keep it alive.
+        let (mut finalizable, mut queue) = super::HEAP.with(|heap| {
+            let mut heap = heap.borrow_mut();
+            (std::mem::take(&mut heap.finalizable), std::mem::take(&mut heap.finalize_queue))
+        });
+        for (obj, finalizer) in finalizable.iter_mut() {
+            evacuate(obj, &mut promoted, false);
+            if let Finalizer::Script(f) = finalizer {
+                evacuate(f, &mut promoted, true);
+            }
+        }
+        for (obj, finalizer) in queue.iter_mut() {
+            evacuate(obj, &mut promoted, true);
+            if let Finalizer::Script(f) = finalizer {
+                evacuate(f, &mut promoted, true);
+            }
+        }
+        super::HEAP.with(|heap| {
+            let mut heap = heap.borrow_mut();
+            heap.finalizable = finalizable;
+            heap.finalize_queue = queue;
+        });
+    }
+
+    // Cheney-style scan; promoted objects may themselves point into the nursery.
+    // Weak objects keep their young referents, but must not mark them. A copy promoted while
+    // marking is black and never scanned by the marker, so its buffers are marked here.
+    while let Some(obj) = promoted.pop() {
+        let strong = !obj.is_weak();
+        obj.trace(&mut |slot| evacuate(slot, &mut promoted, strong));
+        super::HEAP.with(|heap| {
+            let mut heap = heap.borrow_mut();
+            if heap.phase == Phase::Mark {
+                obj.buffers(&mut |ptr| heap.mark_buffer(ptr));
+            }
+        });
+    }
+
+    super::HEAP.with(|heap| heap.borrow_mut().finish_minor());
+}
+
+// Rewrites `slot` to the promoted copy of its object, copying it out of the nursery first
+// if this is the first time it has been reached.
+fn evacuate(slot: &mut Val, promoted: &mut Vec<Val>, strong: bool) {
+    if !slot.is_ptr() {
+        return;
+    }
+    let (tag, ptr) = (slot.tag(), slot.ptr());
+    super::HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        if heap.nursery.contains(ptr) {
+            let to = match heap.nursery.forwarding(ptr) {
+                Some(to) => to,
+                None => {
+                    let size = unsafe { (*(ptr as *const Header)).size as usize };
+                    let to = heap.promote(size);
+                    unsafe { std::ptr::copy_nonoverlapping(ptr, to, size) };
+                    heap.nursery.forward(ptr, to);
+                    promoted.push(Val::from_ptr(tag, to));
+                    to
+                }
+            };
+            *slot = Val::from_ptr(tag, to);
+        }
+        // Promoted objects are black while marking, so whatever they point to must be shaded.
+        if strong && heap.phase == Phase::Mark {
+            heap.shade(*slot);
+        }
+    });
+}
+
@@ -0,0 +1,67 @@
+use super::{PAGE_SIZE, DEFAULT_ARENA_SIZE, DEFAULT_TRIGGER_RATIO, DEFAULT_EMPTY_ARENA_LIMIT};
+
+/// Runtime settings for the heap, passed to `Global::with_config`.
+#[derive(Clone, Debug)]
+pub struct HeapConfig {
+    /// Bytes reserved by each arena. Must be a multiple of 64 pages, i.e. 512KiB.
+    pub arena_size: usize,
+    /// The most memory the heap may hand out to objects, in bytes. Allocating past it
+    /// raises `OutOfMemory`. Objects promoted out of the nursery are never refused, so the heap
+    /// can overshoot by at most the nursery's size. `None` lets the heap grow as long as the OS allows.
+    pub max_heap: Option<usize>,
+    /// Arenas mapped up front.
+    pub initial_arenas: usize,
+    /// A major collection starts once the heap holds this many times what survived the last one.
+    pub trigger_ratio: f64,
+    /// Completely empty arenas kept mapped after a collection. The rest are unmapped.
+    pub empty_arena_limit: usize,
+}
+
+impl Default for HeapConfig {
+    fn default() -> HeapConfig {
+        HeapConfig {
+            arena_size: DEFAULT_ARENA_SIZE,
+            max_heap: None,
+            initial_arenas: 1,
+            trigger_ratio: DEFAULT_TRIGGER_RATIO,
+            empty_arena_limit: DEFAULT_EMPTY_ARENA_LIMIT,
+        }
+    }
+}
+
+impl HeapConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.arena_size == 0 || !self.arena_size.is_multiple_of(64 * PAGE_SIZE) {
+            return Err(format!("arena size {} is not a multiple of {} bytes", self.arena_size, 64 * PAGE_SIZE));
+        }
+        // The largest size class takes four pages.
+        if self.arena_size < super::MAX_SMALL_OBJ_SIZE {
+            return Err(format!("arena size {} cannot hold the largest objects", self.arena_size));
+        }
+        if self.trigger_ratio.is_nan() || self.trigger_ratio <= 1.0 {
+            return Err(format!("trigger ratio {} must be greater than 1", self.trigger_ratio));
+        }
+        Ok(())
+    }
+}
+
+/// Raised by an allocation that would take the heap past `HeapConfig::max_heap`.
+///
+/// Allocation unwinds with this as the panic payload; `Vm::try_run` catches it and hands it back.
+/// The heap itself stays consistent, so the `Global` can keep being used.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OutOfMemory {
+    /// Bytes the failed allocation needed from the OS.
+    pub requested: usize,
+    /// Bytes already in use.
+    pub in_use: usize,
+    pub limit: usize,
+}
+
+impl std::fmt::Display for OutOfMemory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "out of memory: needed {} more bytes with {} of {} in use", self.requested, self.in_use, self.limit)
+    }
+}
+
+impl std::error::Error for OutOfMemory {}
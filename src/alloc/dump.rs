@@ -0,0 +1,173 @@
+//! Heap dumps, for finding out what holds on to what.
+//!
+//! A dump lists every object reachable from the roots, the host's roots and handles, the pinned
+//! objects and the finalizer queue, along with its type, its size including the buffers it
+//! owns, and the objects it refers to. Interned symbols are pinned, so global variables are reached through them.
+//! Objects are numbered in the order a breadth-first walk reaches them, which keeps two dumps
+//! of the same program comparable even though addresses change from run to run.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::values::{Tag, Val};
+use super::collect::Roots;
+use super::heap::{Finalizer, HeapInner};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DumpFormat {
+    /// One line per object, meant to be read and diffed.
+    Text,
+    /// A Graphviz digraph.
+    Dot,
+}
+
+impl DumpFormat {
+    /// Graphviz for paths ending in `.dot`, text for anything else.
+    pub fn for_path(path: &Path) -> DumpFormat {
+        match path.extension() {
+            Some(ext) if ext == "dot" => DumpFormat::Dot,
+            _ => DumpFormat::Text,
+        }
+    }
+}
+
+struct Node {
+    val: Val,
+    size: usize,
+    edges: Vec<usize>,
+}
+
+#[derive(Default)]
+struct Graph {
+    ids: HashMap<usize, usize>,
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+}
+
+impl Graph {
+    // Numbers a newly found object. Immediates and other values without a header are skipped.
+    fn id(&mut self, val: Val) -> Option<usize> {
+        val.header()?;
+        let next = self.nodes.len();
+        let id = *self.ids.entry(val.bits()).or_insert(next);
+        if id == next {
+            self.nodes.push(Node { val, size: 0, edges: vec![] });
+        }
+        Some(id)
+    }
+
+    fn bytes(&self) -> usize {
+        self.nodes.iter().map(|node| node.size).sum()
+    }
+}
+
+impl HeapInner {
+    pub fn dump(&mut self, roots: Roots, format: DumpFormat, out: &mut dyn Write) -> io::Result<()> {
+        let graph = self.object_graph(roots);
+        match format {
+            DumpFormat::Text => write_text(&graph, out),
+            DumpFormat::Dot => write_dot(&graph, out),
+        }
+    }
+
+    fn object_graph(&mut self, roots: Roots) -> Graph {
+        let mut found = vec![];
+        roots(&mut |slot| found.push(*slot));
+        found.extend(self.host_roots.values());
+        found.extend(self.pinned.iter().copied());
+        for (obj, finalizer) in self.finalize_queue.iter() {
+            found.push(*obj);
+            if let Finalizer::Script(f) = finalizer {
+                found.push(*f);
+            }
+        }
+        for (_, finalizer) in self.finalizable.iter() {
+            if let Finalizer::Script(f) = finalizer {
+                found.push(*f);
+            }
+        }
+
+        let mut graph = Graph::default();
+        for val in found {
+            if let Some(id) = graph.id(val) && !graph.roots.contains(&id) {
+                graph.roots.push(id);
+            }
+        }
+        let mut queue: VecDeque<usize> = (0..graph.nodes.len()).collect();
+        while let Some(id) = queue.pop_front() {
+            let val = graph.nodes[id].val;
+            let mut children = vec![];
+            val.trace(&mut |slot| children.push(*slot));
+            let mut edges = vec![];
+            for child in children {
+                let before = graph.nodes.len();
+                if let Some(child) = graph.id(child) {
+                    if child == before {
+                        queue.push_back(child);
+                    }
+                    edges.push(child);
+                }
+            }
+            let mut size = val.header().unwrap().size as usize;
+            val.buffers(&mut |ptr| {
+                size += match self.find_span(ptr) {
+                    Some(span) => span.obj_size as usize,
+                    None => self.large.find(ptr).map_or(0, |large| large.size),
+                };
+            });
+            let node = &mut graph.nodes[id];
+            node.edges = edges;
+            node.size = size;
+        }
+        graph
+    }
+}
+
+// Symbols are worth naming, since they are how the program refers to everything else.
+fn describe(val: Val) -> String {
+    let name = val.header().unwrap().desc.name;
+    if matches!(val.tag(), Tag::Symbol) {
+        format!("{} {:?}", name, val)
+    } else {
+        name.to_string()
+    }
+}
+
+fn write_text(graph: &Graph, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "# {} objects, {} bytes", graph.nodes.len(), graph.bytes())?;
+    write!(out, "roots ->")?;
+    for id in graph.roots.iter() {
+        write!(out, " @{}", id)?;
+    }
+    writeln!(out)?;
+    for (id, node) in graph.nodes.iter().enumerate() {
+        let weak = if node.val.is_weak() { "~" } else { "" };
+        write!(out, "@{} {} {} ->", id, describe(node.val), node.size)?;
+        for edge in node.edges.iter() {
+            write!(out, " {}@{}", weak, edge)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_dot(graph: &Graph, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "digraph heap {{")?;
+    writeln!(out, "  // {} objects, {} bytes", graph.nodes.len(), graph.bytes())?;
+    writeln!(out, "  node [shape=box, fontname=monospace];")?;
+    writeln!(out, "  roots [shape=point];")?;
+    for id in graph.roots.iter() {
+        writeln!(out, "  roots -> n{};", id)?;
+    }
+    for (id, node) in graph.nodes.iter().enumerate() {
+        let label = describe(node.val).replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(out, "  n{} [label=\"{}\\n{} bytes\"];", id, label, node.size)?;
+        // References held by weak objects do not keep anything alive.
+        let style = if node.val.is_weak() { " [style=dashed]" } else { "" };
+        for edge in node.edges.iter() {
+            writeln!(out, "  n{} -> n{}{};", id, edge, style)?;
+        }
+    }
+    writeln!(out, "}}")
+}
@@ -1,99 +1,728 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::ptr::NonNull;
+use std::time::Duration;
 use allocator_api2::alloc as alloc;
 
-use super::{NUM_SIZE_CLASSES, MAX_SMALL_OBJ_SIZE};
+use crate::values::{Cases, Header, Object, Val, REMEMBERED};
+use super::{NUM_SIZE_CLASSES, MAX_SMALL_OBJ_SIZE, MAX_YOUNG_OBJ_SIZE};
+use super::PAGE_SIZE;
+use super::{MIN_MAJOR_THRESHOLD, MARK_SLICE_BYTES};
 use super::span::{Span, get_size_class, get_obj_size, get_alloc_pages};
-use super::Arena;
+use super::{Arena, LargeObjects, Nursery};
+use super::collect::Roots;
+use super::config::{HeapConfig, OutOfMemory};
+use super::dump::DumpFormat;
+use super::profile::{AllocationProfile, Site};
+use super::roots::HostRoots;
 
 // TODO: Implement partial and full as Chunked Lists
+// Spans are boxed because their arena's pages point back at them.
+#[allow(clippy::vec_box)]
 pub struct SpanSet {
     pages: usize,
-    obj_size: usize,
-    partial: Vec<Span>,
-    full: Vec<Span>
+    pub obj_size: usize,
+    // The span allocations are served from until it fills up. It is on neither list.
+    pub current: Option<Box<Span>>,
+    pub partial: Vec<Box<Span>>,
+    pub full: Vec<Box<Span>>,
+    // Spans still holding the marks of the last major collection, waiting to be swept.
+    pub unswept: Vec<Box<Span>>,
 }
 
 impl SpanSet {
     fn new(class: usize, obj_size: usize) -> SpanSet {
         let pages = get_alloc_pages(class);
-        SpanSet { pages, obj_size, partial: vec![], full: vec![] }
+        SpanSet { pages, obj_size, current: None, partial: vec![], full: vec![], unswept: vec![] }
     }
 
-    fn is_full(&self) -> bool {
-        self.partial.len() == 0
-    }
-
-    fn add_span(&mut self, span: Span) {
+    fn add_span(&mut self, span: Box<Span>) {
         assert!(self.pages == span.pages as usize);
         assert!(self.obj_size == span.obj_size as usize);
         self.partial.push(span);
     }
 
     // Returns None only if span_set is full and needs a new span.
-    fn alloc(&mut self) -> Option<*mut u8> {
-        if self.partial.len() == 0 { return None }
-        let span = self.partial.last_mut().unwrap();
-        let val = span.alloc();
-        if span.is_full() {
-            let span = self.partial.pop().unwrap();
+    // Black allocations are marked so that a collection in progress keeps them.
+    fn alloc(&mut self, black: bool, object: bool) -> Option<*mut u8> {
+        loop {
+            if let Some(span) = &mut self.current && let Some(ptr) = span.alloc() {
+                if black || object {
+                    let slot = span.slot(ptr).unwrap();
+                    if black {
+                        span.mark(slot);
+                    }
+                    if object {
+                        span.set_object(slot);
+                    }
+                }
+                return Some(ptr);
+            }
+            if let Some(span) = self.current.take() {
+                self.full.push(span);
+            }
+            self.current = Some(self.partial.pop()?);
+        }
+    }
+
+    // Leaves every span waiting to be swept, so nothing is allocated from them until it is.
+    fn start_sweep(&mut self) {
+        self.unswept.append(&mut self.partial);
+        self.unswept.append(&mut self.full);
+        self.unswept.extend(self.current.take());
+    }
+
+    // Sweeps one span, handing it to release if it was left empty. Returns the bytes still in
+    // use in it, or None once every span has been swept.
+    fn sweep_span(&mut self, release: &mut dyn FnMut(Box<Span>)) -> Option<usize> {
+        let mut span = self.unswept.pop()?;
+        span.sweep();
+        let live = span.count as usize * self.obj_size;
+        if span.is_empty() {
+            release(span);
+        } else if span.is_full() {
             self.full.push(span);
+        } else {
+            self.partial.push(span);
         }
-        val
+        Some(live)
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Phase {
+    Idle,
+    Mark,
+    Sweep,
+}
+
+/// Runs once the object it was registered for has been found unreachable.
+pub enum Finalizer {
+    /// Called with the object at the first safepoint after the collection that found it dead
+    /// has finished sweeping.
+    Native(Box<dyn FnOnce(Val)>),
+    /// A script function, called by the VM with the object as its only argument.
+    /// It is held strongly, so it must not refer to the object itself.
+    Script(Val),
+}
+
+/// Counters describing the collector's work so far.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct HeapStats {
+    /// Bytes held by objects in the spans, as of the last sweep plus everything allocated since.
+    pub allocated: usize,
+    pub minor_collections: usize,
+    pub major_collections: usize,
+    /// Arenas currently mapped.
+    pub arenas: usize,
+    /// Bytes of free pages handed back to the OS so far.
+    pub released: usize,
+    /// The most work done by a single slice of a major collection, counting objects scanned,
+    /// spans swept and pages returned to the OS.
+    pub longest_pause: usize,
+    /// The longest a single slice of a major collection has taken.
+    pub slowest_slice: Duration,
+}
+
 pub struct HeapInner {
     // TODO: Doubly linked list for this part?
-    page_arenas: Vec<Arena>,
-    span_sets: Vec<SpanSet>
+    pub page_arenas: Vec<Arena>,
+    pub span_sets: Vec<SpanSet>,
+    pub large: LargeObjects,
+    pub nursery: Nursery,
+    // Mature objects that were written a pointer into the nursery. They are flagged
+    // `REMEMBERED` in their headers while they are in here.
+    pub remembered: Vec<Val>,
+    pub minor_requested: bool,
+    pub phase: Phase,
+    gray: Vec<Val>,
+    // Objects that are always live, such as interned symbols.
+    pub pinned: Vec<Val>,
+    // Values held by the host through roots and handles.
+    pub host_roots: HostRoots,
+    // Every weak reference and weak map still alive, so their contents can be cleared.
+    pub weak_objects: Vec<Val>,
+    // Objects with a finalizer, which minor collections always promote.
+    pub finalizable: Vec<(Val, Finalizer)>,
+    // Dead objects whose finalizers have not run yet. They are kept alive until they do.
+    pub finalize_queue: VecDeque<(Val, Finalizer)>,
+    // How many at the front of the queue were found dead by a collection that has finished
+    // sweeping, and so are due to run.
+    finalizers_due: usize,
+    pub full_requested: bool,
+    // Set once the gray stack has run dry, so that the next safepoint empties the nursery with
+    // an ordinary minor collection and then tries to finish marking straight away.
+    pub terminating: bool,
+    next_major: usize,
+    since_slice: usize,
+    // Objects scanned, spans swept and pages released during the current slice.
+    pub work: usize,
+    pub pause_budget: Option<usize>,
+    pub verify_interval: Option<usize>,
+    pub config: HeapConfig,
+    // Set by `(dump-heap)`, for the VM to carry out at its next safepoint.
+    pub dump_requested: Option<(PathBuf, DumpFormat)>,
+    // Present while allocations are being profiled.
+    pub profile: Option<AllocationProfile>,
+    pub stats: HeapStats,
 }
 
 impl HeapInner {
     pub fn new() -> HeapInner {
+        HeapInner::with_config(HeapConfig::default())
+    }
+
+    pub fn with_config(config: HeapConfig) -> HeapInner {
+        if let Err(e) = config.validate() {
+            panic!("Invalid heap configuration: {}", e);
+        }
         let mut span_sets = vec![];
         for i in 0..NUM_SIZE_CLASSES {
             span_sets.push(
                 SpanSet::new(i, get_obj_size(i))
             );
         }
-        let page_arenas = vec![Arena::new()]; 
-        HeapInner { page_arenas, span_sets }
+        let page_arenas: Vec<_> = (0..config.initial_arenas.max(1))
+            .map(|_| Arena::new(config.arena_size))
+            .collect();
+        let stats = HeapStats { arenas: page_arenas.len(), ..HeapStats::default() };
+        let mut heap = HeapInner {
+            page_arenas,
+            span_sets,
+            large: LargeObjects::default(),
+            nursery: Nursery::new(),
+            remembered: vec![],
+            minor_requested: false,
+            phase: Phase::Idle,
+            gray: vec![],
+            pinned: vec![],
+            host_roots: HostRoots::default(),
+            weak_objects: vec![],
+            finalizable: vec![],
+            finalize_queue: VecDeque::new(),
+            finalizers_due: 0,
+            full_requested: false,
+            terminating: false,
+            next_major: 0,
+            since_slice: 0,
+            work: 0,
+            pause_budget: None,
+            verify_interval: None,
+            config,
+            dump_requested: None,
+            profile: None,
+            stats,
+        };
+        heap.schedule_major(0);
+        heap
+    }
+
+    // Bump allocates in the nursery. Once it is exhausted we fall back to the spans
+    // and ask the VM for a minor collection at its next safepoint.
+    pub fn alloc_young(&mut self, size: usize) -> *mut u8 {
+        if size <= MAX_YOUNG_OBJ_SIZE {
+            if let Some(ptr) = self.nursery.alloc(size) {
+                self.since_slice += size;
+                return ptr;
+            }
+            self.minor_requested = true;
+        }
+        self.alloc_tenured(size)
+    }
+
+    pub fn write_barrier(&mut self, container: Val, value: Val) {
+        // Incremental marking relies on never storing a white object into a black one.
+        if self.phase == Phase::Mark {
+            self.shade(value);
+        }
+        if !value.is_ptr() || !self.nursery.contains(value.ptr()) {
+            return;
+        }
+        if self.nursery.contains(container.ptr()) {
+            return;
+        }
+        let header = container.header().expect("Wrote into a value that is not a heap object");
+        if header.gc & REMEMBERED == 0 {
+            header.gc |= REMEMBERED;
+            self.remembered.push(container);
+        }
+    }
+
+    // Empties the remembered set, handing back its objects.
+    pub fn take_remembered(&mut self) -> Vec<Val> {
+        let remembered = std::mem::take(&mut self.remembered);
+        for container in remembered.iter() {
+            container.header().unwrap().gc &= !REMEMBERED;
+        }
+        remembered
+    }
+
+    pub fn finish_minor(&mut self) {
+        // Weak objects that were not promoted are dead; the rest have moved.
+        let nursery = &self.nursery;
+        self.weak_objects.retain_mut(|weak| {
+            if !nursery.contains(weak.ptr()) {
+                return true;
+            }
+            match nursery.forwarding(weak.ptr()) {
+                Some(to) => {
+                    *weak = Val::from_ptr(weak.tag(), to);
+                    true
+                }
+                None => false,
+            }
+        });
+        self.nursery.reset();
+        self.take_remembered();
+        self.minor_requested = false;
+        self.stats.minor_collections += 1;
+    }
+
+    pub fn wants_major(&self) -> bool {
+        self.phase == Phase::Idle && (self.full_requested || self.stats.allocated >= self.next_major)
+    }
+
+    pub fn slice_due(&self) -> bool {
+        self.phase != Phase::Idle && (self.since_slice >= MARK_SLICE_BYTES || self.terminating)
+    }
+
+    // Starts counting the work done by a slice.
+    pub fn start_slice(&mut self) {
+        self.since_slice = 0;
+        self.work = 0;
+    }
+
+    pub fn finish_slice(&mut self, elapsed: Duration) {
+        self.stats.longest_pause = self.stats.longest_pause.max(self.work);
+        self.stats.slowest_slice = self.stats.slowest_slice.max(elapsed);
+    }
+
+    pub fn pin(&mut self, val: Val) {
+        self.pinned.push(val);
+        if self.phase == Phase::Mark {
+            self.shade(val);
+        }
+    }
+
+    pub fn start_major(&mut self) {
+        assert!(self.phase == Phase::Idle);
+        self.phase = Phase::Mark;
+        self.since_slice = 0;
+        for i in 0..self.pinned.len() {
+            self.shade(self.pinned[i]);
+        }
+        for i in 0..self.finalizable.len() {
+            if let Finalizer::Script(f) = self.finalizable[i].1 {
+                self.shade(f);
+            }
+        }
+        for i in 0..self.finalize_queue.len() {
+            let (obj, ref finalizer) = self.finalize_queue[i];
+            if let &Finalizer::Script(f) = finalizer {
+                self.shade(f);
+            }
+            self.shade(obj);
+        }
+    }
+
+    pub fn register_weak(&mut self, val: Val) {
+        self.weak_objects.push(val);
+    }
+
+    pub fn register_finalizer(&mut self, val: Val, finalizer: Finalizer) {
+        assert!(val.is_ptr(), "Only heap objects can have finalizers");
+        if self.phase == Phase::Mark && let Finalizer::Script(f) = finalizer {
+            self.shade(f);
+        }
+        self.finalizable.push((val, finalizer));
+    }
+
+    pub fn find_span(&mut self, ptr: *const u8) -> Option<&mut Span> {
+        self.page_arenas.iter()
+            .find_map(|arena| arena.span_at(ptr))
+            .map(|span| unsafe { &mut *span })
+    }
+
+    // Marks the object and queues it for scanning if it lives in the spans and was still white.
+    // Nursery objects are left to the minor collector.
+    pub fn shade(&mut self, val: Val) {
+        if !val.is_ptr() {
+            return;
+        }
+        let ptr = val.ptr();
+        let newly_marked = match self.find_span(ptr) {
+            Some(span) => span.slot(ptr).is_some_and(|i| span.mark(i)),
+            None => self.large.find(ptr).is_some_and(|large| !std::mem::replace(&mut large.marked, true)),
+        };
+        if newly_marked {
+            self.gray.push(val);
+        }
+    }
+
+    // Anything outside the spans is either immediate or kept alive some other way.
+    fn is_marked(&mut self, val: Val) -> bool {
+        if !val.is_ptr() {
+            return true;
+        }
+        let ptr = val.ptr();
+        match self.find_span(ptr) {
+            Some(span) => span.slot(ptr).is_none_or(|i| span.is_marked(i)),
+            None => self.large.find(ptr).is_none_or(|large| large.marked),
+        }
+    }
+
+    pub fn mark_buffer(&mut self, ptr: *const u8) {
+        if let Some(span) = self.find_span(ptr) {
+            if let Some(i) = span.slot(ptr) {
+                span.mark(i);
+            }
+        } else if let Some(large) = self.large.find(ptr) {
+            large.marked = true;
+        }
+    }
+
+    // Scans gray objects until there are none left or the slice has done `budget` work.
+    // Returns true once the gray stack is empty.
+    pub fn drain(&mut self, budget: Option<usize>) -> bool {
+        while budget.is_none_or(|budget| self.work < budget) && let Some(obj) = self.gray.pop() {
+            // What a weak object refers to is left for `mark_ephemerons` to decide.
+            if !obj.is_weak() {
+                obj.trace(&mut |slot| self.shade(*slot));
+            }
+            obj.buffers(&mut |ptr| self.mark_buffer(ptr));
+            self.work += 1;
+        }
+        self.gray.is_empty()
+    }
+
+    // A weak map keeps its value alive for as long as the key is, which can make more keys
+    // live in turn, so this runs until nothing new gets marked. Returns false if the slice did
+    // `budget` work first.
+    pub fn mark_ephemerons(&mut self, budget: Option<usize>) -> bool {
+        loop {
+            let weak_objects = std::mem::take(&mut self.weak_objects);
+            let mut found = false;
+            for &weak in weak_objects.iter() {
+                if !self.is_marked(weak) {
+                    continue;
+                }
+                if let Cases::Map(map) = weak.get() {
+                    for (k, v) in map.iter() {
+                        if self.is_marked(k) && !self.is_marked(v) {
+                            self.shade(v);
+                            found = true;
+                        }
+                    }
+                }
+            }
+            self.weak_objects = weak_objects;
+            if !self.drain(budget) {
+                return false;
+            }
+            if !found {
+                return true;
+            }
+        }
+    }
+
+    // Queues the finalizers of every object that was not marked, then marks those objects and
+    // everything they refer to so they are still intact when their finalizers run. They are
+    // dropped from `finalizable`, so they are freed by a later collection unless resurrected.
+    pub fn queue_finalizers(&mut self) {
+        let finalizable = std::mem::take(&mut self.finalizable);
+        for (obj, finalizer) in finalizable {
+            if self.is_marked(obj) {
+                self.finalizable.push((obj, finalizer));
+            } else {
+                self.finalize_queue.push_back((obj, finalizer));
+            }
+        }
+        for i in 0..self.finalize_queue.len() {
+            self.shade(self.finalize_queue[i].0);
+        }
+        self.mark_ephemerons(None);
+    }
+
+    // Finalizers only fall due once the collection that queued them has finished sweeping.
+    pub fn next_finalizer(&mut self) -> Option<(Val, Finalizer)> {
+        if self.finalizers_due == 0 {
+            return None;
+        }
+        self.finalizers_due -= 1;
+        self.finalize_queue.pop_front()
+    }
+
+    // Resets weak references and removes weak map entries whose referents were not marked,
+    // and forgets the weak objects that are about to be swept themselves.
+    pub fn clear_weak(&mut self) {
+        let weak_objects = std::mem::take(&mut self.weak_objects);
+        let mut survivors = Vec::with_capacity(weak_objects.len());
+        for weak in weak_objects {
+            if !self.is_marked(weak) {
+                continue;
+            }
+            match weak.get() {
+                Cases::WeakRef(weak) => {
+                    if !self.is_marked(weak.get()) {
+                        weak.clear();
+                    }
+                }
+                Cases::Map(map) => map.retain_keys(&mut |k| self.is_marked(k)),
+                _ => unreachable!(),
+            }
+            survivors.push(weak);
+        }
+        self.weak_objects = survivors;
+    }
+
+    // Frees everything left white, returning emptied spans to their arenas and unmapping
+    // large allocations.
+    pub fn sweep(&mut self) {
+        self.start_sweep();
+        self.sweep_spans(None);
+    }
+
+    // Ends marking. From here on the spans are swept a few at a time, and allocation sweeps the
+    // spans of its own size class before it takes a new one. What the spans hold is counted
+    // again as they are swept.
+    pub fn start_sweep(&mut self) {
+        assert!(self.phase == Phase::Mark && self.gray.is_empty());
+        for span_set in self.span_sets.iter_mut() {
+            span_set.start_sweep();
+        }
+        self.large.start_sweep();
+        self.stats.allocated = 0;
+        self.phase = Phase::Sweep;
+        self.full_requested = false;
+    }
+
+    // Sweeps spans and then returns the pages they freed to the OS until the slice has done
+    // `budget` work, finishing the collection if that was everything. Returns true if it did.
+    pub fn sweep_spans(&mut self, budget: Option<usize>) -> bool {
+        assert!(self.phase == Phase::Sweep);
+        for class in 0..self.span_sets.len() {
+            while budget.is_none_or(|budget| self.work < budget) && self.sweep_span(class) {}
+        }
+        while budget.is_none_or(|budget| self.work < budget) && let Some(live) = self.large.sweep_one() {
+            self.stats.allocated += live;
+            self.work += 1;
+        }
+        if !self.large.is_swept() || self.span_sets.iter().any(|span_set| !span_set.unswept.is_empty()) {
+            return false;
+        }
+        if !self.release_memory(budget) {
+            return false;
+        }
+        self.stats.major_collections += 1;
+        self.schedule_major(self.stats.allocated);
+        self.phase = Phase::Idle;
+        self.finalizers_due = self.finalize_queue.len();
+        true
+    }
+
+    // Sweeps one span of the given class, returning false if there were none left to sweep.
+    fn sweep_span(&mut self, class: usize) -> bool {
+        let arenas = &mut self.page_arenas;
+        let mut release = |span: Box<Span>| {
+            let arena = arenas.iter_mut()
+                .find(|arena| arena.contains(span.base))
+                .expect("Span did not belong to any arena");
+            arena.set_span(span.base, span.pages as usize, std::ptr::null_mut());
+            arena.dealloc(span.base, span.pages as usize);
+        };
+        match self.span_sets[class].sweep_span(&mut release) {
+            Some(live) => {
+                self.stats.allocated += live;
+                self.work += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Sets how much may be allocated before the next major collection starts. With a heap limit,
+    // collections start early enough to leave room for the objects allocated while marking.
+    fn schedule_major(&mut self, live: usize) {
+        let mut next = MIN_MAJOR_THRESHOLD.max((live as f64 * self.config.trigger_ratio) as usize);
+        if let Some(max_heap) = self.config.max_heap {
+            let halfway = live + max_heap.saturating_sub(live) / 2;
+            next = next.min(halfway).max(live + MARK_SLICE_BYTES);
+        }
+        self.next_major = next;
+    }
+
+    // Replaces the configuration, mapping more arenas if there are now fewer than requested.
+    // Arenas already mapped keep their size.
+    pub fn configure(&mut self, config: HeapConfig) {
+        if let Err(e) = config.validate() {
+            panic!("Invalid heap configuration: {}", e);
+        }
+        while self.page_arenas.len() < config.initial_arenas {
+            self.page_arenas.push(Arena::new(config.arena_size));
+        }
+        self.stats.arenas = self.page_arenas.len();
+        self.config = config;
+        self.schedule_major(self.stats.allocated);
+    }
+
+    // Bytes of pages currently handed out to spans or mapped for large allocations.
+    pub fn in_use(&self) -> usize {
+        self.page_arenas.iter().map(|arena| arena.count()).sum::<usize>() * PAGE_SIZE + self.large.size()
+    }
+
+    // Every object in the nursery, the spans or a large allocation, including dead ones not
+    // yet reclaimed.
+    // Nursery objects that were promoted are left out in favour of their copies.
+    pub fn objects(&self) -> Vec<Val> {
+        let object_at = |ptr: *mut u8| {
+            let header = unsafe { &*(ptr as *const Header) };
+            Val::from_ptr(header.desc.tag, ptr)
+        };
+        let nursery = &self.nursery;
+        let mut objects: Vec<Val> = nursery.objects()
+            .filter(|&ptr| nursery.forwarding(ptr).is_none())
+            .map(object_at)
+            .collect();
+        for span_set in self.span_sets.iter() {
+            let spans = span_set.current.iter()
+                .chain(span_set.partial.iter())
+                .chain(span_set.full.iter())
+                .chain(span_set.unswept.iter());
+            for span in spans {
+                objects.extend(span.objects().map(object_at));
+            }
+        }
+        objects.extend(self.large.objects().map(object_at));
+        objects
+    }
+
+    // Returns the pages freed since the last sweep to the OS, counting each one as work, and
+    // then unmaps empty arenas past the limit, which by then costs next to nothing. Returns
+    // false if the slice did `budget` work first.
+    fn release_memory(&mut self, budget: Option<usize>) -> bool {
+        for arena in self.page_arenas.iter_mut() {
+            let limit = budget.map_or(usize::MAX, |budget| budget.saturating_sub(self.work));
+            let released = arena.release_free_pages(limit);
+            self.work += released;
+            self.stats.released += released * PAGE_SIZE;
+            if budget.is_some_and(|budget| self.work >= budget) {
+                return false;
+            }
+        }
+        let mut empty = 0;
+        let limit = self.config.empty_arena_limit;
+        self.page_arenas.retain(|arena| {
+            if arena.count() > 0 {
+                return true;
+            }
+            empty += 1;
+            empty <= limit
+        });
+        self.stats.arenas = self.page_arenas.len();
+        true
     }
     
+    // Allocates a raw buffer for the mutator, which may not take the heap past its limit.
     pub fn alloc(&mut self, size: usize) -> *mut u8 {
+        self.charge("buffer", size);
+        self.alloc_in(size, true, false)
+    }
+
+    // Charges a mutator allocation to the current site, if allocations are being profiled.
+    pub fn charge(&mut self, type_name: &'static str, size: usize) {
+        if let Some(profile) = &mut self.profile {
+            profile.record(type_name, size);
+        }
+    }
+
+    // Allocates an object, which must start with a header, for the mutator. It goes straight
+    // into the spans.
+    pub fn alloc_tenured(&mut self, size: usize) -> *mut u8 {
+        self.alloc_in(size, true, true)
+    }
+
+    // Allocates for the collector, which promotes objects the mutator was already allowed to
+    // allocate. A collection cannot stop halfway, so the limit is ignored.
+    pub fn promote(&mut self, size: usize) -> *mut u8 {
+        self.alloc_in(size, false, true)
+    }
+
+    fn alloc_in(&mut self, size: usize, limited: bool, object: bool) -> *mut u8 {
+        let black = self.phase == Phase::Mark;
         if size > super::MAX_SMALL_OBJ_SIZE {
-            return self.alloc_large(size);
+            return self.alloc_large(size, limited, black, object);
         }
 
         let size_class = super::span::get_size_class(size);
-        // TODO: cacheing last span
-        if self.span_sets[size_class].is_full() {
-            let new_span = self.alloc_span(size_class);
+        let ptr = loop {
+            if let Some(ptr) = self.span_sets[size_class].alloc(black, object) {
+                break ptr;
+            }
+            if self.sweep_span(size_class) {
+                continue;
+            }
+            let new_span = self.alloc_span(size_class, limited);
             self.span_sets[size_class].add_span(new_span);
-        }
-        let ptr = self.span_sets[size_class].alloc().unwrap();
+            break self.span_sets[size_class].alloc(black, object).expect("A fresh span had no free slots");
+        };
+        let obj_size = get_obj_size(size_class);
+        self.stats.allocated += obj_size;
+        self.since_slice += obj_size;
         return ptr;
 
     }
 
-    // TODO: just default to system calculator?
-    fn alloc_large(&mut self, size: usize) -> *mut u8 {
-        unimplemented!()
+    // Maps the allocation on its own, outside the arenas.
+    fn alloc_large(&mut self, size: usize, limited: bool, black: bool, object: bool) -> *mut u8 {
+        let size = size.next_multiple_of(PAGE_SIZE);
+        if limited {
+            self.check_limit(size);
+        }
+        let ptr = self.large.alloc(size, black, object);
+        self.stats.allocated += size;
+        self.since_slice += size;
+        ptr
     }
 
-    // Gets a new span reservation from a page_arena, allocating a new arena if necessary.
-    fn alloc_span(&mut self, class: usize) -> Span {
-        for arena in self.page_arenas.iter_mut() {
-            if let Some(base) = arena.try_alloc(get_alloc_pages(class)) {
-                return Span::new(base, class)
+    // Unwinds with `OutOfMemory` if `size` more bytes would take the heap past its limit.
+    fn check_limit(&self, size: usize) {
+        if let Some(limit) = self.config.max_heap {
+            let in_use = self.in_use();
+            if in_use + size > limit {
+                // Unwinding without a panic message, since this is expected to be caught.
+                std::panic::resume_unwind(Box::new(OutOfMemory { requested: size, in_use, limit }));
             }
         }
-        // if we got here, there must not be enough arenas
-        let mut arena = Arena::new();
-        let base = arena.try_alloc(get_alloc_pages(class))
-            .expect("Allocation from a fresh arena should not have failed.");
-        self.page_arenas.push(arena);
-        Span::new(base, class)
+    }
+
+    // Gets a new span reservation from a page_arena, allocating a new arena if necessary.
+    // Of the arenas with room, the one whose free pages are split into the fewest runs wins,
+    // and then the fullest, so that spans pack into few arenas and the rest can drain.
+    fn alloc_span(&mut self, class: usize, limited: bool) -> Box<Span> {
+        let pages = get_alloc_pages(class);
+        if limited {
+            self.check_limit(pages * PAGE_SIZE);
+        }
+        let best = self.page_arenas.iter()
+            .enumerate()
+            .filter(|(_, arena)| arena.largest_free_run() >= pages)
+            .min_by_key(|(_, arena)| (arena.fragments(), usize::MAX - arena.count()))
+            .map(|(i, _)| i);
+        let i = match best {
+            Some(i) => i,
+            None => {
+                self.page_arenas.push(Arena::new(self.config.arena_size));
+                self.stats.arenas = self.page_arenas.len();
+                self.page_arenas.len() - 1
+            }
+        };
+        let arena = &mut self.page_arenas[i];
+        let base = arena.try_alloc(pages)
+            .expect("Arena did not have the free run it reported");
+        let mut span = Box::new(Span::new(base, class));
+        arena.set_span(base, pages, &mut *span);
+        span
     }
 }
 
@@ -106,10 +735,188 @@ impl Heap {
         )
     }
 
-    pub fn new<T: Sized>() -> *mut T {
-        let ptr = Heap::alloc(size_of::<T>());
+    /// Allocates a fresh object in the nursery. It may be moved by the next minor collection.
+    pub fn alloc_object<T: Object>() -> *mut T {
+        let ptr = super::HEAP.with(|heap| {
+            let mut heap = heap.borrow_mut();
+            heap.charge(T::desc().name, size_of::<T>());
+            heap.alloc_young(size_of::<T>())
+        });
+        ptr as *mut T
+    }
+
+    /// Allocates a long-lived object directly in the spans, bypassing the nursery.
+    pub fn alloc_tenured<T: Object>() -> *mut T {
+        let ptr = super::HEAP.with(|heap| {
+            let mut heap = heap.borrow_mut();
+            heap.charge(T::desc().name, size_of::<T>());
+            heap.alloc_tenured(size_of::<T>())
+        });
         ptr as *mut T
     }
+
+    /// Must be called whenever `value` is stored into the heap object `container`,
+    /// so that mature objects pointing into the nursery are scanned by minor collections.
+    pub fn write_barrier(container: Val, value: Val) {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().write_barrier(container, value)
+        )
+    }
+
+    /// Keeps an object alive for as long as the heap exists.
+    pub fn pin(val: Val) {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().pin(val)
+        )
+    }
+
+    /// Remembers a weak reference or weak map so that its contents are cleared once they die.
+    pub fn register_weak(val: Val) {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().register_weak(val)
+        )
+    }
+
+    /// Arranges for `finalizer` to run once `val` is unreachable. Objects referenced only by
+    /// the finalized object stay alive until the finalizer has run, and weak references to them
+    /// are not cleared before then either.
+    pub fn register_finalizer(val: Val, finalizer: Finalizer) {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().register_finalizer(val, finalizer)
+        )
+    }
+
+    /// Takes the next finalizer that is due, along with its object.
+    pub fn next_finalizer() -> Option<(Val, Finalizer)> {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().next_finalizer()
+        )
+    }
+
+    /// Asks for a full collection at the next safepoint, ignoring the pause budget.
+    pub fn request_collection() {
+        super::HEAP.with(|heap| {
+            let mut heap = heap.borrow_mut();
+            heap.full_requested = true;
+            heap.minor_requested = true;
+        })
+    }
+
+    /// Sets how much work a single slice of a major collection may do, counting objects scanned,
+    /// spans swept and pages returned to the OS. With no budget, major collections run in one
+    /// pause.
+    pub fn set_pause_budget(budget: Option<usize>) {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().pause_budget = budget
+        )
+    }
+
+    /// Checks the allocator's bookkeeping and every value reachable from the heap or `roots`,
+    /// describing the first inconsistency found.
+    pub fn verify(roots: Roots) -> Result<(), String> {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().verify(roots)
+        )
+    }
+
+    /// Makes the VM verify the heap every `interval` steps. Only VMs created afterwards are affected.
+    pub fn set_verify_interval(interval: Option<usize>) {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().verify_interval = interval
+        )
+    }
+
+    pub fn verify_interval() -> Option<usize> {
+        super::HEAP.with(|heap|
+            heap.borrow().verify_interval
+        )
+    }
+
+    /// Sets how many completely empty arenas are kept mapped after a collection,
+    /// ready for the heap to grow again. The rest are returned to the OS.
+    pub fn set_empty_arena_limit(limit: usize) {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().config.empty_arena_limit = limit
+        )
+    }
+
+    /// Applies `config` to this thread's heap. Only arenas mapped afterwards use the new arena size.
+    pub fn configure(config: HeapConfig) {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().configure(config)
+        )
+    }
+
+    pub fn stats() -> HeapStats {
+        super::HEAP.with(|heap|
+            heap.borrow().stats
+        )
+    }
+
+    /// Writes every object reachable from `roots`, the pinned objects and the finalizer queue.
+    pub fn dump(roots: Roots, format: DumpFormat, out: &mut dyn Write) -> io::Result<()> {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().dump(roots, format, out)
+        )
+    }
+
+    pub fn dump_to_file(roots: Roots, format: DumpFormat, path: &Path) -> io::Result<()> {
+        let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+        Heap::dump(roots, format, &mut out)?;
+        out.flush()
+    }
+
+    /// Asks the VM to dump the heap to `path` at its next safepoint, where its stack is known.
+    pub fn request_dump(path: PathBuf, format: DumpFormat) {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().dump_requested = Some((path, format))
+        )
+    }
+
+    pub fn take_dump_request() -> Option<(PathBuf, DumpFormat)> {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().dump_requested.take()
+        )
+    }
+
+    /// Starts counting allocations by site and type, discarding any profile already being taken.
+    /// Only VMs created afterwards record their sites.
+    pub fn start_profiling() {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().profile = Some(AllocationProfile::default())
+        )
+    }
+
+    /// Stops profiling, handing back what was counted.
+    pub fn stop_profiling() -> Option<AllocationProfile> {
+        super::HEAP.with(|heap|
+            heap.borrow_mut().profile.take()
+        )
+    }
+
+    pub fn profiling() -> bool {
+        super::HEAP.with(|heap|
+            heap.borrow().profile.is_some()
+        )
+    }
+
+    /// Charges the allocations that follow to `site`.
+    pub fn set_site(site: Option<Site>) {
+        super::HEAP.with(|heap| {
+            if let Some(profile) = &mut heap.borrow_mut().profile {
+                profile.set_site(site);
+            }
+        })
+    }
+
+    /// Calls `f` with every object on the heap, including unreachable ones that have not been
+    /// collected yet. Allocating from `f` is allowed, but the new objects are not visited.
+    pub fn walk(mut f: impl FnMut(Val)) {
+        let objects = super::HEAP.with(|heap|
+            heap.borrow().objects()
+        );
+        objects.into_iter().for_each(&mut f)
+    }
 }
 
 unsafe impl alloc::Allocator for Heap {
@@ -124,4 +931,174 @@ unsafe impl alloc::Allocator for Heap {
     unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: std::alloc::Layout) {
         // No op for now :)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn alloc_mixed(heap: &mut HeapInner) {
+        for i in 0..2000 {
+            heap.alloc(8 + (i % 13) * 40);
+        }
+    }
+
+    #[test]
+    fn allocation_keeps_heap_consistent() {
+        let mut heap = HeapInner::new();
+        alloc_mixed(&mut heap);
+        assert_eq!(heap.verify(&mut |_| {}), Ok(()));
+    }
+
+    #[test]
+    fn sweeping_unmarked_heap_releases_every_page() {
+        let mut heap = HeapInner::new();
+        alloc_mixed(&mut heap);
+        heap.start_major();
+        heap.sweep();
+        assert_eq!(heap.verify(&mut |_| {}), Ok(()));
+        assert_eq!(heap.page_arenas[0].count(), 0);
+        assert_eq!(heap.stats.allocated, 0);
+    }
+
+    #[test]
+    fn verify_detects_corrupt_span_count() {
+        let mut heap = HeapInner::new();
+        alloc_mixed(&mut heap);
+        let span_set = heap.span_sets.iter_mut().find(|set| set.current.is_some()).unwrap();
+        span_set.current.as_mut().unwrap().count -= 1;
+        assert!(heap.verify(&mut |_| {}).unwrap_err().contains("allocated slots"));
+    }
+
+    #[test]
+    fn verify_detects_leaked_pages() {
+        let mut heap = HeapInner::new();
+        alloc_mixed(&mut heap);
+        heap.page_arenas[0].try_alloc(1);
+        assert!(heap.verify(&mut |_| {}).unwrap_err().contains("spans hold"));
+    }
+
+    // Every round keeps a seventh of what it allocated and drops the previous round's
+    // survivors, leaving the spans of every class fragmented.
+    #[test]
+    fn span_churn_reuses_pages() {
+        let mut heap = HeapInner::new();
+        let sizes = [16, 1024, 4096, 9472, 20480, 32768];
+        for round in 0..50 {
+            let mut survivors = vec![];
+            for i in 0..400 {
+                let ptr = heap.alloc(sizes[(i + round) % sizes.len()]);
+                if i % 7 == 0 {
+                    survivors.push(ptr);
+                }
+            }
+            heap.start_major();
+            for ptr in survivors {
+                let span = heap.find_span(ptr).unwrap();
+                let slot = span.slot(ptr).unwrap();
+                span.mark(slot);
+            }
+            heap.sweep();
+            assert_eq!(heap.verify(&mut |_| {}), Ok(()));
+        }
+        assert_eq!(heap.page_arenas.len(), 1);
+    }
+
+    #[test]
+    fn empty_arenas_are_unmapped() {
+        let mut heap = HeapInner::new();
+        heap.config.empty_arena_limit = 1;
+        // Each arena fits 2048 of these, so this needs five.
+        for _ in 0..10000 {
+            heap.alloc(32768);
+        }
+        assert_eq!(heap.stats.arenas, 5);
+        heap.start_major();
+        heap.sweep();
+        assert_eq!(heap.verify(&mut |_| {}), Ok(()));
+        assert_eq!(heap.stats.arenas, 1);
+        assert_eq!(heap.page_arenas.len(), 1);
+
+        // The heap can still grow after shrinking.
+        for _ in 0..3000 {
+            heap.alloc(32768);
+        }
+        assert_eq!(heap.stats.arenas, 2);
+        assert_eq!(heap.verify(&mut |_| {}), Ok(()));
+    }
+
+    #[test]
+    fn freed_pages_are_released_once() {
+        let mut heap = HeapInner::new();
+        for _ in 0..100 {
+            let ptr = heap.alloc(1024);
+            unsafe { std::ptr::write_bytes(ptr, 0xAB, 1024) };
+        }
+        heap.start_major();
+        heap.sweep();
+        let released = heap.stats.released;
+        assert!(released >= 100 * 1024);
+        heap.start_major();
+        heap.sweep();
+        assert_eq!(heap.stats.released, released);
+    }
+
+    #[test]
+    fn large_allocations_are_swept_with_the_spans() {
+        let mut heap = HeapInner::new();
+        let kept = heap.alloc(100_000);
+        heap.alloc(40_000);
+        assert_eq!(heap.large.count(), 2);
+        heap.start_major();
+        heap.mark_buffer(unsafe { kept.add(50_000) });
+        heap.sweep();
+        assert_eq!(heap.large.count(), 1);
+        assert_eq!(heap.stats.allocated, 100_000usize.next_multiple_of(PAGE_SIZE));
+        assert_eq!(heap.in_use(), heap.stats.allocated);
+        assert_eq!(heap.verify(&mut |_| {}), Ok(()));
+    }
+
+    #[test]
+    fn verify_detects_dangling_values() {
+        use crate::values::{Tag, Vector};
+        let mut heap = HeapInner::new();
+        let mut vectors = [0, 1].map(|_| {
+            let ptr = heap.alloc_tenured(size_of::<Vector>());
+            unsafe { std::ptr::write(ptr as *mut Vector, Vector::new()) };
+            Val::from_ptr(Tag::Vector, ptr)
+        });
+        assert_eq!(heap.verify(&mut |visit| vectors.iter_mut().for_each(visit)), Ok(()));
+
+        // Only the second survives, so the first is left pointing at a free slot of a live span.
+        heap.start_major();
+        heap.shade(vectors[1]);
+        heap.drain(None);
+        heap.sweep();
+        let mut vector = vectors[0];
+        let problem = heap.verify(&mut |visit| visit(&mut vector)).unwrap_err();
+        assert!(problem.contains("free slot"), "{}", problem);
+    }
+
+    #[test]
+    fn walk_finds_objects_but_not_buffers() {
+        use crate::values::{Map, Tag, Vector};
+        let mut heap = HeapInner::new();
+        let young = heap.alloc_young(size_of::<Vector>());
+        unsafe { std::ptr::write(young as *mut Vector, Vector::new()) };
+        let tenured = heap.alloc_tenured(size_of::<Map>());
+        unsafe { std::ptr::write(tenured as *mut Map, Map::new()) };
+        heap.alloc(64);
+        let objects = heap.objects();
+        assert_eq!(objects, [Val::from_ptr(Tag::Vector, young), Val::from_ptr(Tag::Map, tenured)]);
+    }
+
+    #[test]
+    fn verify_detects_mistagged_values() {
+        use crate::values::{Tag, Vector};
+        let mut heap = HeapInner::new();
+        let ptr = heap.alloc_tenured(size_of::<Vector>());
+        unsafe { std::ptr::write(ptr as *mut Vector, Vector::new()) };
+        let mut val = Val::from_ptr(Tag::Map, ptr);
+        let problem = heap.verify(&mut |visit| visit(&mut val)).unwrap_err();
+        assert!(problem.contains("points at a vector object"), "{}", problem);
+    }
+}
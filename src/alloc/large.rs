@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use super::PAGE_SIZE;
+use super::os;
+
+// An allocation too big for the largest size class. It gets a mapping of its own, rounded up
+// to whole pages, and carries its own mark and object bits in place of a span's bitmaps.
+pub struct Large {
+    pub base: *mut u8,
+    pub size: usize,
+    pub marked: bool,
+    pub object: bool,
+}
+
+// Every large allocation, indexed by address so that interior pointers map back to theirs.
+// Sweeping unmaps the ones left unmarked.
+#[derive(Default)]
+pub struct LargeObjects {
+    // base address -> allocation
+    allocations: BTreeMap<usize, Large>,
+    // Bytes mapped for the allocations.
+    size: usize,
+    // Allocations still holding the marks of the last major collection, waiting to be swept.
+    unswept: Vec<usize>,
+}
+
+impl LargeObjects {
+    // Black allocations are marked so that a collection in progress keeps them.
+    pub fn alloc(&mut self, size: usize, black: bool, object: bool) -> *mut u8 {
+        let size = size.next_multiple_of(PAGE_SIZE);
+        let base = os::reserve(size, PAGE_SIZE);
+        self.allocations.insert(base.addr(), Large { base, size, marked: black, object });
+        self.size += size;
+        base
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn count(&self) -> usize {
+        self.allocations.len()
+    }
+
+    // Finds the allocation containing ptr. Interior pointers are accepted.
+    pub fn find(&mut self, ptr: *const u8) -> Option<&mut Large> {
+        let (_, large) = self.allocations.range_mut(..=ptr.addr()).next_back()?;
+        (ptr.addr() < large.base.addr() + large.size).then_some(large)
+    }
+
+    /// The addresses of the allocations that hold objects.
+    pub fn objects(&self) -> impl Iterator<Item = *mut u8> + '_ {
+        self.allocations.values()
+            .filter(|large| large.object)
+            .map(|large| large.base)
+    }
+
+    pub fn start_sweep(&mut self) {
+        self.unswept = self.allocations.keys().copied().collect();
+    }
+
+    pub fn is_swept(&self) -> bool {
+        self.unswept.is_empty()
+    }
+
+    // Sweeps one allocation, unmapping it unless it was marked. Returns the bytes it still
+    // holds, or None once every allocation has been swept.
+    pub fn sweep_one(&mut self) -> Option<usize> {
+        let addr = self.unswept.pop()?;
+        let large = self.allocations.get_mut(&addr).expect("Swept a large allocation twice");
+        if large.marked {
+            large.marked = false;
+            return Some(large.size);
+        }
+        let large = self.allocations.remove(&addr).unwrap();
+        os::release(large.base, large.size, PAGE_SIZE);
+        self.size -= large.size;
+        Some(0)
+    }
+}
+
+impl Drop for LargeObjects {
+    fn drop(&mut self) {
+        for large in self.allocations.values() {
+            os::release(large.base, large.size, PAGE_SIZE);
+        }
+    }
+}
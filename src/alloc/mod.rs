@@ -1,17 +1,47 @@
 mod heap;
 mod arena;
 mod span;
+mod large;
+mod nursery;
+mod collect;
+mod verify;
+mod os;
+mod config;
+mod dump;
+mod profile;
+mod roots;
+#[doc(hidden)]
+pub mod testing;
 
-const ARENA_SIZE: usize = 1 << 26;
+const DEFAULT_ARENA_SIZE: usize = 1 << 26;
 const PAGE_SIZE: usize = 1 << 13;
 const NUM_SIZE_CLASSES: usize = 66;
 const MAX_SMALL_OBJ_SIZE: usize = 32768;
+const NURSERY_SIZE: usize = 1 << 20;
+const NURSERY_GRANULE: usize = 16;
+const MAX_YOUNG_OBJ_SIZE: usize = 1024;
+// A major collection starts once the spans hold this many bytes, or the configured
+// trigger ratio times what survived the last one, whichever is larger.
+const MIN_MAJOR_THRESHOLD: usize = 1 << 22;
+const DEFAULT_TRIGGER_RATIO: f64 = 2.0;
+// While marking, a slice runs every time this many bytes have been allocated.
+const MARK_SLICE_BYTES: usize = 1 << 16;
+// Empty arenas kept mapped after a collection, so that a heap hovering around an arena
+// boundary does not map and unmap one on every cycle.
+const DEFAULT_EMPTY_ARENA_LIMIT: usize = 1;
 
 use arena::Arena;
 use span::Span;
+use large::LargeObjects;
+use nursery::Nursery;
 
-pub use heap::Heap;
+pub use heap::{Finalizer, Heap, HeapStats};
+pub use config::{HeapConfig, OutOfMemory};
+pub use dump::DumpFormat;
+pub use profile::{AllocationProfile, Site, Tally};
+pub use roots::{Handle, HandleScope, Root};
 pub use span::print_size_classes;
+pub use collect::{safepoint, Roots};
 
 thread_local! {
     static HEAP: std::cell::RefCell<heap::HeapInner> = std::cell::RefCell::new(heap::HeapInner::new());
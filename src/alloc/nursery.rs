@@ -0,0 +1,103 @@
+use crate::values::Header;
+use super::{NURSERY_SIZE, NURSERY_GRANULE};
+
+const NURSERY_GRANULES: usize = NURSERY_SIZE / NURSERY_GRANULE;
+const FORWARD_BITS_SIZE: usize = NURSERY_GRANULES / 8;
+type ForwardBits = [u8; FORWARD_BITS_SIZE];
+
+// Young generation. Objects are bump allocated here and either die or get promoted into
+// the size-classed spans by a minor collection, after which the whole region is reused.
+// A promoted object leaves a forwarding address in its first word, flagged in `forwarded`.
+pub struct Nursery {
+    base: *mut u8,
+    top: usize,
+    forwarded: Box<ForwardBits>,
+}
+
+impl Nursery {
+    pub fn new() -> Nursery {
+        use std::alloc::*;
+        unsafe {
+            let layout = Layout::from_size_align(
+                NURSERY_SIZE, NURSERY_GRANULE
+            ).expect("Nursery allocation was misaligned.");
+            let base = alloc(layout);
+            Nursery {
+                base, top: 0, forwarded: Box::new([0u8; FORWARD_BITS_SIZE])
+            }
+        }
+    }
+
+    // Returns None once the nursery is exhausted.
+    pub fn alloc(&mut self, size: usize) -> Option<*mut u8> {
+        let size = size.next_multiple_of(NURSERY_GRANULE);
+        if self.top + size > NURSERY_SIZE {
+            return None;
+        }
+        let ptr = unsafe { self.base.add(self.top) };
+        self.top += size;
+        Some(ptr)
+    }
+
+    pub fn contains(&self, ptr: *const u8) -> bool {
+        let addr = ptr.addr();
+        addr >= self.base.addr() && addr < self.base.addr() + self.top
+    }
+
+    pub fn used(&self) -> usize {
+        self.top
+    }
+
+    fn granule(&self, ptr: *const u8) -> usize {
+        debug_assert!(self.contains(ptr));
+        (ptr.addr() - self.base.addr()) / NURSERY_GRANULE
+    }
+
+    pub fn forwarding(&self, ptr: *const u8) -> Option<*mut u8> {
+        let i = self.granule(ptr);
+        if self.forwarded[i / 8] & (1 << (i % 8)) == 0 {
+            return None;
+        }
+        Some(unsafe { *(ptr as *const *mut u8) })
+    }
+
+    pub fn forward(&mut self, ptr: *mut u8, to: *mut u8) {
+        let i = self.granule(ptr);
+        self.forwarded[i / 8] |= 1 << (i % 8);
+        unsafe { *(ptr as *mut *mut u8) = to };
+    }
+
+    // The address of every object allocated since the last reset, promoted or not. Promotion
+    // only overwrites the first word of the header, so the size can still be read.
+    pub fn objects(&self) -> impl Iterator<Item = *mut u8> + '_ {
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            if offset >= self.top {
+                return None;
+            }
+            let ptr = unsafe { self.base.add(offset) };
+            let size = unsafe { (*(ptr as *const Header)).size as usize };
+            offset += size.next_multiple_of(NURSERY_GRANULE);
+            Some(ptr)
+        })
+    }
+
+    // Every survivor has been promoted, so the region can be handed out again.
+    pub fn reset(&mut self) {
+        let used = self.top.div_ceil(NURSERY_GRANULE).div_ceil(8);
+        self.forwarded[..used].fill(0);
+        self.top = 0;
+    }
+}
+
+impl Drop for Nursery {
+    fn drop(&mut self) {
+        use std::alloc::*;
+        let layout = Layout::from_size_align(
+                NURSERY_SIZE, NURSERY_GRANULE
+        ).expect("Nursery allocation was misaligned.");
+        unsafe {
+            std::alloc::dealloc(self.base, layout);
+        }
+    }
+}
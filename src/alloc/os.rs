@@ -0,0 +1,159 @@
+//! Memory straight from the kernel. Arenas are reserved as anonymous mappings, so a page only
+//! takes up memory once it is touched, and free pages can be handed back without unmapping.
+//!
+//! Only Linux on x86_64 and aarch64 is supported, through raw system calls. Everywhere else
+//! this falls back to the global allocator and `decommit` does nothing.
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod sys {
+    use std::arch::asm;
+
+    #[cfg(target_arch = "x86_64")]
+    mod nr {
+        pub const MMAP: usize = 9;
+        pub const MUNMAP: usize = 11;
+        pub const MADVISE: usize = 28;
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    mod nr {
+        pub const MMAP: usize = 222;
+        pub const MUNMAP: usize = 215;
+        pub const MADVISE: usize = 233;
+    }
+
+    const PROT_READ: usize = 0x1;
+    const PROT_WRITE: usize = 0x2;
+    const MAP_PRIVATE: usize = 0x02;
+    const MAP_ANONYMOUS: usize = 0x20;
+    const MAP_NORESERVE: usize = 0x4000;
+    const MADV_DONTNEED: usize = 4;
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe fn syscall6(n: usize, a: [usize; 6]) -> isize {
+        let ret: isize;
+        unsafe {
+            asm!(
+                "syscall",
+                inlateout("rax") n as isize => ret,
+                in("rdi") a[0], in("rsi") a[1], in("rdx") a[2],
+                in("r10") a[3], in("r8") a[4], in("r9") a[5],
+                lateout("rcx") _, lateout("r11") _,
+                options(nostack),
+            );
+        }
+        ret
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe fn syscall6(n: usize, a: [usize; 6]) -> isize {
+        let ret: isize;
+        unsafe {
+            asm!(
+                "svc 0",
+                in("x8") n,
+                inlateout("x0") a[0] as isize => ret,
+                in("x1") a[1], in("x2") a[2], in("x3") a[3], in("x4") a[4], in("x5") a[5],
+                options(nostack),
+            );
+        }
+        ret
+    }
+
+    // The kernel reports failure as a negated errno.
+    fn check(ret: isize, call: &str) -> usize {
+        if (-4095..0).contains(&ret) {
+            panic!("{} failed with errno {}", call, -ret);
+        }
+        ret as usize
+    }
+
+    pub const RETURNS_MEMORY: bool = true;
+
+    fn map(size: usize) -> *mut u8 {
+        let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE;
+        let ret = unsafe { syscall6(nr::MMAP, [0, size, PROT_READ | PROT_WRITE, flags, usize::MAX, 0]) };
+        check(ret, "mmap") as *mut u8
+    }
+
+    fn unmap(ptr: *mut u8, size: usize) {
+        let ret = unsafe { syscall6(nr::MUNMAP, [ptr.addr(), size, 0, 0, 0, 0]) };
+        check(ret, "munmap");
+    }
+
+    pub fn decommit(ptr: *mut u8, size: usize) {
+        let ret = unsafe { syscall6(nr::MADVISE, [ptr.addr(), size, MADV_DONTNEED, 0, 0, 0]) };
+        check(ret, "madvise");
+    }
+
+    pub fn reserve(size: usize, align: usize) -> *mut u8 {
+        // mmap only aligns to the system page, so over-allocate and trim the ends.
+        let ptr = map(size + align);
+        let head = ptr.addr().next_multiple_of(align) - ptr.addr();
+        if head > 0 {
+            unmap(ptr, head);
+        }
+        let tail = align - head;
+        if tail > 0 {
+            unmap(unsafe { ptr.add(head + size) }, tail);
+        }
+        unsafe { ptr.add(head) }
+    }
+
+    pub fn release(ptr: *mut u8, size: usize, align: usize) {
+        unmap(ptr, size)
+    }
+}
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+mod sys {
+    use std::alloc::*;
+
+    pub const RETURNS_MEMORY: bool = false;
+
+    pub fn reserve(size: usize, align: usize) -> *mut u8 {
+        let layout = Layout::from_size_align(size, align).expect("Reservation was misaligned.");
+        unsafe { alloc_zeroed(layout) }
+    }
+
+    pub fn release(ptr: *mut u8, size: usize, align: usize) {
+        let layout = Layout::from_size_align(size, align).expect("Reservation was misaligned.");
+        unsafe { dealloc(ptr, layout) }
+    }
+
+    pub fn decommit(ptr: *mut u8, size: usize) {}
+}
+
+/// Whether `decommit` actually gives memory back.
+pub use sys::RETURNS_MEMORY;
+/// `reserve(size, align)` maps `size` bytes aligned to `align`, a power of two. The memory
+/// reads as zero. `release` unmaps a reservation, and `decommit` hands a range of its pages
+/// back to the kernel; they stay mapped and read as zero when next touched.
+pub use sys::{reserve, release, decommit};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reservations_are_aligned() {
+        let align = 1 << 16;
+        let ptr = reserve(1 << 20, align);
+        assert_eq!(ptr.addr() % align, 0);
+        unsafe { ptr.add((1 << 20) - 1).write(1) };
+        release(ptr, 1 << 20, align);
+    }
+
+    #[test]
+    fn decommitted_pages_read_as_zero() {
+        let size = 1 << 16;
+        let ptr = reserve(size, 1 << 13);
+        unsafe { std::ptr::write_bytes(ptr, 0xAB, size) };
+        decommit(ptr, size);
+        if RETURNS_MEMORY {
+            assert!(unsafe { std::slice::from_raw_parts(ptr, size) }.iter().all(|&b| b == 0));
+        }
+        unsafe { std::ptr::write_bytes(ptr, 0xCD, size) };
+        release(ptr, size, 1 << 13);
+    }
+}
@@ -0,0 +1,147 @@
+//! Allocation profiling, for finding out which parts of a program allocate the most.
+//!
+//! While a profile is being taken, every allocation the mutator makes is charged to the
+//! instruction the VM was executing at the time, which covers both the allocating opcodes and
+//! the intrinsics they call. Objects are counted under the name of their type, and the raw
+//! buffers behind vectors, maps and symbol names under `buffer`. Sizes are the bytes requested,
+//! before rounding up to a size class. Allocations made outside the VM, such as by the
+//! compiler, are charged to no site at all.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// An instruction in a code object.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Site {
+    /// Address of the code object's instructions.
+    pub code: usize,
+    pub offset: usize,
+    pub op: &'static str,
+}
+
+impl fmt::Display for Site {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "code@{:x}+{} {}", self.code, self.offset, self.op)
+    }
+}
+
+/// Allocations counted together.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Tally {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+impl Tally {
+    fn add(&mut self, other: Tally) {
+        self.count += other.count;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Allocations counted by the site making them and the type allocated.
+#[derive(Clone, Debug, Default)]
+pub struct AllocationProfile {
+    // The site the next allocation is charged to.
+    site: Option<Site>,
+    tallies: HashMap<(Option<Site>, &'static str), Tally>,
+}
+
+impl AllocationProfile {
+    pub fn set_site(&mut self, site: Option<Site>) {
+        self.site = site;
+    }
+
+    pub fn record(&mut self, type_name: &'static str, bytes: usize) {
+        self.tallies.entry((self.site, type_name)).or_default().add(Tally { count: 1, bytes });
+    }
+
+    pub fn total(&self) -> Tally {
+        let mut total = Tally::default();
+        self.tallies.values().for_each(|tally| total.add(*tally));
+        total
+    }
+
+    /// Every site and type allocated from it, most bytes first.
+    pub fn entries(&self) -> Vec<(Option<Site>, &'static str, Tally)> {
+        let mut entries: Vec<_> = self.tallies.iter()
+            .map(|(&(site, type_name), &tally)| (site, type_name, tally))
+            .collect();
+        entries.sort_by(|a, b| b.2.bytes.cmp(&a.2.bytes).then(a.0.cmp(&b.0)).then(a.1.cmp(b.1)));
+        entries
+    }
+
+    /// Totals for each site, most bytes first.
+    pub fn by_site(&self) -> Vec<(Option<Site>, Tally)> {
+        sorted(self.tallies.iter().map(|(&(site, _), &tally)| (site, tally)))
+    }
+
+    /// Totals for each type, most bytes first.
+    pub fn by_type(&self) -> Vec<(&'static str, Tally)> {
+        sorted(self.tallies.iter().map(|(&(_, type_name), &tally)| (type_name, tally)))
+    }
+}
+
+fn sorted<K: Ord + std::hash::Hash>(tallies: impl Iterator<Item = (K, Tally)>) -> Vec<(K, Tally)> {
+    let mut totals: HashMap<K, Tally> = HashMap::new();
+    for (key, tally) in tallies {
+        totals.entry(key).or_default().add(tally);
+    }
+    let mut totals: Vec<_> = totals.into_iter().collect();
+    totals.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then(a.0.cmp(&b.0)));
+    totals
+}
+
+/// The report: totals by type, then by site and type.
+impl fmt::Display for AllocationProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        writeln!(f, "# {} allocations, {} bytes", total.count, total.bytes)?;
+        writeln!(f, "# by type")?;
+        for (type_name, tally) in self.by_type() {
+            writeln!(f, "{:>10} {:>8}  {}", tally.bytes, tally.count, type_name)?;
+        }
+        writeln!(f, "# by site")?;
+        for (site, type_name, tally) in self.entries() {
+            match site {
+                Some(site) => writeln!(f, "{:>10} {:>8}  {} at {}", tally.bytes, tally.count, type_name, site)?,
+                None => writeln!(f, "{:>10} {:>8}  {} outside the vm", tally.bytes, tally.count, type_name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tallies_are_grouped_by_site_and_type() {
+        let mut profile = AllocationProfile::default();
+        let site = |offset| Some(Site { code: 0x10, offset, op: "mapnew" });
+        profile.record("symbol", 8);
+        profile.set_site(site(2));
+        profile.record("map", 40);
+        profile.record("buffer", 64);
+        profile.set_site(site(5));
+        profile.record("map", 40);
+        profile.record("map", 40);
+
+        assert_eq!(profile.total(), Tally { count: 5, bytes: 192 });
+        assert_eq!(profile.by_type(), vec![
+            ("map", Tally { count: 3, bytes: 120 }),
+            ("buffer", Tally { count: 1, bytes: 64 }),
+            ("symbol", Tally { count: 1, bytes: 8 }),
+        ]);
+        assert_eq!(profile.by_site(), vec![
+            (site(2), Tally { count: 2, bytes: 104 }),
+            (site(5), Tally { count: 2, bytes: 80 }),
+            (None, Tally { count: 1, bytes: 8 }),
+        ]);
+        let report = profile.to_string();
+        assert!(report.starts_with("# 5 allocations, 192 bytes\n"), "{}", report);
+        assert!(report.contains("        80        2  map at code@10+5 mapnew\n"), "{}", report);
+        assert!(report.contains("  symbol outside the vm\n"), "{}", report);
+    }
+}
@@ -0,0 +1,156 @@
+//! Values held by the host between VM runs.
+//!
+//! The collector only knows about values on the VM's stack and inside the heap, so a `Val` kept
+//! in a Rust struct or local may be freed, or left pointing at the nursery after its object
+//! has been promoted. A `Root` registers a single long-lived value as a root until it is
+//! dropped. A `HandleScope` does the same for any number of temporaries, such as the values a
+//! native function works on, and releases them all at once when it goes out of scope.
+//!
+//! Both read the value back from the heap on every access, since a collection may rewrite it.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::values::Val;
+
+// The host's roots, kept by each thread's heap and visited by every collection.
+#[derive(Default)]
+pub struct HostRoots {
+    // Slots for `Root`s, reused once they are dropped.
+    slots: Vec<Val>,
+    free: Vec<usize>,
+    // A stack of handles, each scope owning the ones pushed since it was opened.
+    handles: Vec<Val>,
+    scopes: usize,
+}
+
+impl HostRoots {
+    pub fn visit(&mut self, visit: &mut dyn FnMut(&mut Val)) {
+        self.slots.iter_mut().chain(self.handles.iter_mut()).for_each(visit);
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = Val> + '_ {
+        self.slots.iter().chain(self.handles.iter()).copied()
+    }
+}
+
+fn with_roots<R>(f: impl FnOnce(&mut HostRoots) -> R) -> R {
+    super::HEAP.with(|heap| f(&mut heap.borrow_mut().host_roots))
+}
+
+/// A value kept alive for as long as the `Root` is, on the heap of the thread that created it.
+pub struct Root {
+    index: usize,
+    // The heap is per thread, so a root cannot leave it.
+    _thread: PhantomData<*const ()>,
+}
+
+impl Root {
+    pub fn new(val: Val) -> Root {
+        let index = with_roots(|roots| match roots.free.pop() {
+            Some(index) => {
+                roots.slots[index] = val;
+                index
+            }
+            None => {
+                roots.slots.push(val);
+                roots.slots.len() - 1
+            }
+        });
+        Root { index, _thread: PhantomData }
+    }
+
+    pub fn get(&self) -> Val {
+        with_roots(|roots| roots.slots[self.index])
+    }
+
+    pub fn set(&self, val: Val) {
+        with_roots(|roots| roots.slots[self.index] = val)
+    }
+}
+
+impl Clone for Root {
+    fn clone(&self) -> Root {
+        Root::new(self.get())
+    }
+}
+
+impl Drop for Root {
+    fn drop(&mut self) {
+        with_roots(|roots| {
+            roots.slots[self.index] = Val::nil();
+            roots.free.push(self.index);
+        })
+    }
+}
+
+impl fmt::Debug for Root {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Root({:?})", self.get())
+    }
+}
+
+/// Roots every value handed to `handle` until the scope is dropped. Scopes must be dropped in
+/// the reverse order they were opened in.
+pub struct HandleScope {
+    base: usize,
+    depth: usize,
+    _thread: PhantomData<*const ()>,
+}
+
+impl HandleScope {
+    pub fn new() -> HandleScope {
+        with_roots(|roots| {
+            roots.scopes += 1;
+            HandleScope { base: roots.handles.len(), depth: roots.scopes, _thread: PhantomData }
+        })
+    }
+
+    pub fn handle(&self, val: Val) -> Handle<'_> {
+        let index = with_roots(|roots| {
+            assert!(roots.scopes == self.depth, "Handle created in a scope that is not the innermost");
+            roots.handles.push(val);
+            roots.handles.len() - 1
+        });
+        Handle { index, _scope: PhantomData }
+    }
+}
+
+impl Default for HandleScope {
+    fn default() -> HandleScope {
+        HandleScope::new()
+    }
+}
+
+impl Drop for HandleScope {
+    fn drop(&mut self) {
+        with_roots(|roots| {
+            assert!(roots.scopes == self.depth, "Handle scopes dropped out of order");
+            roots.handles.truncate(self.base);
+            roots.scopes -= 1;
+        })
+    }
+}
+
+/// A value rooted by a `HandleScope`.
+#[derive(Copy, Clone)]
+pub struct Handle<'scope> {
+    index: usize,
+    _scope: PhantomData<&'scope HandleScope>,
+}
+
+impl Handle<'_> {
+    pub fn get(&self) -> Val {
+        with_roots(|roots| roots.handles[self.index])
+    }
+
+    pub fn set(&self, val: Val) {
+        with_roots(|roots| roots.handles[self.index] = val)
+    }
+}
+
+impl fmt::Debug for Handle<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({:?})", self.get())
+    }
+}
@@ -15,7 +15,7 @@ const CLASS_TO_SIZE: [u64; NUM_SIZE_CLASSES] = [
 ];
 
 const NUM_SMALL_BUCKETS: usize = (1024 + 7) / 8;
-const NUM_MEDIUM_BUCKETS: usize = (32768 - 1024 + 127) / 128;
+const NUM_MEDIUM_BUCKETS: usize = (32768 - 1024) / 128 + 1;
 const TOTAL_BUCKETS: usize = NUM_SMALL_BUCKETS + NUM_MEDIUM_BUCKETS;
 
 const fn generate_size_to_class() -> [u64; TOTAL_BUCKETS] {
@@ -122,8 +122,8 @@ pub fn print_size_classes() {
 }
 
 // The most amount of objects a span can hold is going to be 512. This bitfield fits perfectly in cache.
-const SPAN_BITS_SIZE: usize = 64;
-type SpanBits = [u8; SPAN_BITS_SIZE];
+const SPAN_WORDS: usize = 8;
+type SpanBits = [u64; SPAN_WORDS];
 
 // Metadata for a continuous run of pages for allocating objects of size obj_size
 // TODO: Allocate Spanbits separately instead of inline? 
@@ -133,7 +133,13 @@ pub struct Span {
     pub obj_size: u16,
     pub capacity: u16,
     pub count: u16,
+    // Every word of alloc_bits before this one is full. Only sweeping frees slots, so
+    // allocation never has to look behind it.
+    next_word: u8,
     pub alloc_bits: SpanBits,
+    pub mark_bits: SpanBits,
+    // Slots holding objects rather than raw buffers, which have no header.
+    pub object_bits: SpanBits,
 }
 
 impl Span {
@@ -147,28 +153,177 @@ impl Span {
             obj_size: obj_size as u16,
             capacity: capacity as u16,
             count: 0,
-            alloc_bits: [0u8; SPAN_BITS_SIZE ] 
+            next_word: 0,
+            alloc_bits: [0; SPAN_WORDS],
+            mark_bits: [0; SPAN_WORDS],
+            object_bits: [0; SPAN_WORDS],
         }
     }
 
+    // Takes the lowest free slot. The cursor only moves forward between sweeps, so filling
+    // a span visits each bitmap word once.
     pub fn alloc(&mut self) -> Option<*mut u8> {
         if self.count == self.capacity {
             return None;
         }
 
-        for i in 0..self.capacity as usize {
-            if self.alloc_bits[i / 8] & 1 << (i % 8) == 0 {
-                self.count += 1;
-                self.alloc_bits[i / 8] |= 1 << i % 8;
-                let offset = self.obj_size as usize * i;
-                return Some(unsafe { self.base.add(offset) })
-            }
+        let mut word = self.next_word as usize;
+        while word < SPAN_WORDS && self.alloc_bits[word] == !0 {
+            word += 1;
         }
+        assert!(word < SPAN_WORDS, "During slot allocation, discovered span count was corrupted");
+        self.next_word = word as u8;
 
-        panic!("During slot allocation, discovered span count was corrupted");
+        // Every slot below this one is taken and the span is not full, so it is within capacity.
+        let i = word * 64 + (!self.alloc_bits[word]).trailing_zeros() as usize;
+        self.count += 1;
+        self.alloc_bits[word] |= 1 << (i % 64);
+        let offset = self.obj_size as usize * i;
+        Some(unsafe { self.base.add(offset) })
     }
 
     pub fn is_full(&self) -> bool {
         self.count == self.capacity
     }
-}
\ No newline at end of file
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    // Finds the allocated slot containing ptr. Interior pointers are accepted.
+    pub fn slot(&self, ptr: *const u8) -> Option<usize> {
+        let offset = ptr.addr().checked_sub(self.base.addr())?;
+        let i = offset / self.obj_size as usize;
+        if i >= self.capacity as usize || self.alloc_bits[i / 64] & 1 << (i % 64) == 0 {
+            return None;
+        }
+        Some(i)
+    }
+
+    // Returns true if the slot was not already marked.
+    pub fn mark(&mut self, i: usize) -> bool {
+        let unmarked = self.mark_bits[i / 64] & 1 << (i % 64) == 0;
+        self.mark_bits[i / 64] |= 1 << (i % 64);
+        unmarked
+    }
+
+    pub fn is_marked(&self, i: usize) -> bool {
+        self.mark_bits[i / 64] & 1 << (i % 64) != 0
+    }
+
+    pub fn set_object(&mut self, i: usize) {
+        self.object_bits[i / 64] |= 1 << (i % 64);
+    }
+
+    pub fn is_object(&self, i: usize) -> bool {
+        self.object_bits[i / 64] & 1 << (i % 64) != 0
+    }
+
+    /// The addresses of the allocated slots that hold objects.
+    pub fn objects(&self) -> impl Iterator<Item = *mut u8> + '_ {
+        (0..self.capacity as usize)
+            .filter(|&i| self.is_object(i))
+            .map(|i| unsafe { self.base.add(i * self.obj_size as usize) })
+    }
+
+    // Checks that the bitmaps agree with count and capacity.
+    pub fn verify(&self) -> Result<(), String> {
+        let allocated: u32 = self.alloc_bits.iter().map(|byte| byte.count_ones()).sum();
+        if allocated != self.count as u32 {
+            return Err(format!("span at {:x} counts {} objects but has {} allocated slots",
+                self.base.addr(), self.count, allocated));
+        }
+        if self.count > self.capacity {
+            return Err(format!("span at {:x} holds {} objects past its capacity of {}",
+                self.base.addr(), self.count, self.capacity));
+        }
+        for i in self.capacity as usize..SPAN_WORDS * 64 {
+            if self.alloc_bits[i / 64] & 1 << (i % 64) != 0 {
+                return Err(format!("span at {:x} allocated slot {} past its capacity of {}",
+                    self.base.addr(), i, self.capacity));
+            }
+        }
+        for i in 0..self.next_word as usize {
+            if self.alloc_bits[i] != !0 {
+                return Err(format!("span at {:x} has a free slot behind its allocation cursor",
+                    self.base.addr()));
+            }
+        }
+        for i in 0..SPAN_WORDS {
+            if self.mark_bits[i] & !self.alloc_bits[i] != 0 {
+                return Err(format!("span at {:x} marked a free slot", self.base.addr()));
+            }
+            if self.object_bits[i] & !self.alloc_bits[i] != 0 {
+                return Err(format!("span at {:x} has an object in a free slot", self.base.addr()));
+            }
+        }
+        Ok(())
+    }
+
+    // Frees every unmarked slot and clears the marks for the next cycle.
+    // Returns the number of objects freed.
+    pub fn sweep(&mut self) -> usize {
+        let mut count = 0;
+        for i in 0..SPAN_WORDS {
+            self.alloc_bits[i] &= self.mark_bits[i];
+            self.object_bits[i] &= self.mark_bits[i];
+            count += self.alloc_bits[i].count_ones() as u16;
+        }
+        self.mark_bits = [0; SPAN_WORDS];
+        self.next_word = 0;
+        let freed = self.count - count;
+        self.count = count;
+        freed as usize
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn span(class: usize) -> (Span, Vec<u8>) {
+        let mut memory = vec![0u8; get_alloc_pages(class) * PAGE_SIZE];
+        (Span::new(memory.as_mut_ptr(), class), memory)
+    }
+
+    #[test]
+    fn largest_size_has_a_class() {
+        assert_eq!(get_obj_size(get_size_class(32768)), 32768);
+    }
+
+    #[test]
+    fn class_sizes_map_to_their_own_class() {
+        for class in 1..NUM_SIZE_CLASSES {
+            let size = get_obj_size(class);
+            assert_eq!(get_size_class(size), class, "{} bytes", size);
+            assert_eq!(get_size_class(size - 1), class, "{} bytes", size - 1);
+        }
+    }
+
+    #[test]
+    fn fills_every_slot_in_order() {
+        let (mut span, _memory) = span(1);
+        for i in 0..span.capacity as usize {
+            let ptr = span.alloc().unwrap();
+            assert_eq!(ptr.addr(), span.base.addr() + i * span.obj_size as usize);
+        }
+        assert!(span.is_full());
+        assert_eq!(span.alloc(), None);
+        assert_eq!(span.verify(), Ok(()));
+    }
+
+    #[test]
+    fn reuses_swept_slots() {
+        let (mut span, _memory) = span(3);
+        while span.alloc().is_some() {}
+        for i in (0..span.capacity as usize).filter(|i| i % 3 != 0) {
+            span.mark(i);
+        }
+        span.sweep();
+        assert_eq!(span.verify(), Ok(()));
+        while let Some(ptr) = span.alloc() {
+            assert_eq!(span.slot(ptr).unwrap() % 3, 0);
+        }
+        assert!(span.is_full());
+        assert_eq!(span.verify(), Ok(()));
+    }
+}
@@ -0,0 +1,26 @@
+//! Hooks for the crate's own tests and benchmarks, which need to reach past the VM.
+//! They are not part of the API, and nothing they allocate is rooted.
+
+use super::Heap;
+
+/// Allocates a raw buffer of `size` bytes straight from the spans.
+pub fn alloc_buffer(size: usize) {
+    Heap::alloc(size);
+}
+
+/// Counts the objects of the type named `name` on the heap, including unreachable ones
+/// that have not been collected yet.
+pub fn count_objects(name: &str) -> usize {
+    let mut count = 0;
+    Heap::walk(|val| {
+        if val.header().is_some_and(|header| header.desc.name == name) {
+            count += 1;
+        }
+    });
+    count
+}
+
+/// Whether a major collection is under way, marking or sweeping.
+pub fn collecting() -> bool {
+    super::HEAP.with(|heap| heap.borrow().phase != super::heap::Phase::Idle)
+}
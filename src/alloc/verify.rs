@@ -0,0 +1,132 @@
+//! Consistency checks for tracking down heap corruption. Nothing here runs unless asked for.
+//!
+//! The allocator's bookkeeping is cross-checked first: arenas against the spans holding their
+//! pages, and spans against their own bitmaps. Then the object graph is walked from the roots,
+//! checking that each pointer lands on an object whose header agrees with its tag.
+
+use std::collections::HashSet;
+
+use crate::values::{Header, Symbol, Tag, Val};
+use super::collect::Roots;
+use super::heap::{Finalizer, HeapInner};
+use super::span::get_obj_size;
+
+impl HeapInner {
+    pub fn verify(&mut self, roots: Roots) -> Result<(), String> {
+        self.verify_spans()?;
+        self.verify_graph(roots)
+    }
+
+    fn verify_spans(&self) -> Result<(), String> {
+        let mut span_pages = vec![0; self.page_arenas.len()];
+        for (class, span_set) in self.span_sets.iter().enumerate() {
+            let current = span_set.current.iter().map(|span| (span, None));
+            let partial = span_set.partial.iter().map(|span| (span, Some(false)));
+            let full = span_set.full.iter().map(|span| (span, Some(true)));
+            let unswept = span_set.unswept.iter().map(|span| (span, None));
+            for (span, on_full_list) in current.chain(partial).chain(full).chain(unswept) {
+                span.verify()?;
+                if span.obj_size as usize != get_obj_size(class) {
+                    return Err(format!("span at {:x} holds {} byte objects but belongs to class {}",
+                        span.base.addr(), span.obj_size, class));
+                }
+                if on_full_list.is_some_and(|on_full_list| span.is_full() != on_full_list) {
+                    return Err(format!("span at {:x} is on the wrong list of class {}",
+                        span.base.addr(), class));
+                }
+                let i = self.page_arenas.iter()
+                    .position(|arena| arena.contains(span.base))
+                    .ok_or_else(|| format!("span at {:x} is outside every arena", span.base.addr()))?;
+                self.page_arenas[i].verify_span(span)?;
+                span_pages[i] += span.pages as usize;
+            }
+        }
+        for (arena, pages) in self.page_arenas.iter().zip(span_pages) {
+            arena.verify(pages)?;
+        }
+        Ok(())
+    }
+
+    fn verify_graph(&mut self, roots: Roots) -> Result<(), String> {
+        let mut work = self.pinned.clone();
+        work.extend(self.host_roots.values());
+        work.extend(self.weak_objects.iter().copied());
+        work.extend(self.remembered.iter().copied());
+        for (obj, finalizer) in self.finalizable.iter().chain(self.finalize_queue.iter()) {
+            work.push(*obj);
+            if let Finalizer::Script(f) = finalizer {
+                work.push(*f);
+            }
+        }
+        roots(&mut |slot| work.push(*slot));
+
+        let mut visited = HashSet::new();
+        while let Some(val) = work.pop() {
+            if !self.verify_val(val)? || !visited.insert(val.bits()) {
+                continue;
+            }
+            let mut buffers = vec![];
+            val.buffers(&mut |ptr| buffers.push(ptr));
+            for ptr in buffers {
+                self.verify_buffer(val, ptr)?;
+            }
+            val.trace(&mut |slot| work.push(*slot));
+        }
+        Ok(())
+    }
+
+    // Returns true if the value is a heap object whose contents should be checked as well.
+    fn verify_val(&mut self, val: Val) -> Result<bool, String> {
+        if !val.is_ptr() || matches!(val.tag(), Tag::NativeFn) || val == Symbol::nil() || val == Symbol::t() {
+            return Ok(false);
+        }
+        let (tag, ptr) = (val.tag(), val.ptr());
+        let describe = || format!("{:?} value at {:x}", tag, ptr.addr());
+        if self.nursery.contains(ptr) {
+            if self.nursery.forwarding(ptr).is_some() {
+                return Err(format!("{} points at a nursery object that was promoted", describe()));
+            }
+            return Ok(true);
+        }
+        if let Some(large) = self.large.find(ptr) {
+            if ptr != large.base || !large.object {
+                return Err(format!("{} points into a large buffer", describe()));
+            }
+            return Ok(true);
+        }
+        let span = self.find_span(ptr)
+            .ok_or_else(|| format!("{} points outside the heap", describe()))?;
+        let slot = span.slot(ptr)
+            .ok_or_else(|| format!("{} points at a free slot", describe()))?;
+        if ptr.addr() != span.base.addr() + slot * span.obj_size as usize {
+            return Err(format!("{} points into the middle of a slot", describe()));
+        }
+        if !span.is_object(slot) {
+            return Err(format!("{} points at a buffer rather than an object", describe()));
+        }
+        let slot_size = span.obj_size as usize;
+
+        // Only read the header once its address is known to be good.
+        let header = unsafe { &*(ptr as *const Header) };
+        if header.desc.tag as u8 != tag as u8 {
+            return Err(format!("{} points at a {} object", describe(), header.desc.name));
+        }
+        let size = header.size as usize;
+        if slot_size < size {
+            return Err(format!("{} needs {} bytes but its slot holds {}", describe(), size, slot_size));
+        }
+        Ok(true)
+    }
+
+    fn verify_buffer(&mut self, owner: Val, ptr: *const u8) -> Result<(), String> {
+        let valid = match self.find_span(ptr) {
+            Some(span) => span.slot(ptr).is_some_and(|slot| !span.is_object(slot)),
+            None => self.large.find(ptr).is_some_and(|large| !large.object),
+        };
+        if !valid {
+            return Err(format!("{:?} value at {:x} owns a buffer at {:x} that is not allocated",
+                owner.tag(), owner.ptr().addr(), ptr.addr()));
+        }
+        Ok(())
+    }
+}
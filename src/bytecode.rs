@@ -1,6 +1,6 @@
 use crate::common::*;
 
-use crate::values::Val;
+use crate::values::{Header, Object, Tag, TypeDesc, Val};
 
 #[derive(Copy, Clone, Debug)]
 #[repr(u8)]
@@ -14,11 +14,21 @@ pub enum OpCode {
     Sub,         // {num a, num b} -> {a - b}
     Mul,         // {num a, num b} -> {a * b}
     Div,         // {num a, num b} -> {a / b}
+    Quot,        // {int a, int b} -> {a / b rounded towards zero}
+    Rem,         // {int a, int b} -> {a - b * quot(a, b)}, with the sign of a
+    Mod,         // {int a, int b} -> {a - b * floor(a / b)}, with the sign of b
+    BitAnd,      // {int a, int b} -> {a & b}
+    BitOr,       // {int a, int b} -> {a | b}
+    BitXor,      // {int a, int b} -> {a ^ b}
+    BitNot,      // {int a} -> {!a}
+    Shl,         // {int a, int n} -> {a * 2^n}
+    Shr,         // {int a, int n} -> {floor(a / 2^n)}
     Lt,          // {num a, num b} -> {a < b}
     Gt,          // {num a, num b} -> {a > b}
     Lte,         // {num a, num b} -> { a <= b }
     Gte,         // {num a, num b} -> { a >= b } 
-    Eq,          // {num a, num b} -> { a == b }
+    Eq,          // {a, b} -> { a is b }
+    NumEq,       // {num a, num b} -> { a = b }
 
     BrNil,       // brnil ip. Checks for nil and jumps to ip
     Jmp,         // jmp ip.   unconditional jump to ip
@@ -52,7 +62,7 @@ pub fn to_op(code: u8) -> OpCode {
 }
 
 impl OpCode {
-    pub fn to_str(&self) -> &str {
+    pub fn to_str(&self) -> &'static str {
         use OpCode::*;
         match *self {
             Const => "const",
@@ -63,11 +73,21 @@ impl OpCode {
             Sub => "sub",
             Mul => "mul",
             Div => "div",
+            Quot => "quot",
+            Rem => "rem",
+            Mod => "mod",
+            BitAnd => "bitand",
+            BitOr => "bitor",
+            BitXor => "bitxor",
+            BitNot => "bitnot",
+            Shl => "shl",
+            Shr => "shr",
             Lt => "lt",
             Gt => "gt",
             Gte => "gte",
             Lte => "lte",
             Eq => "eq",
+            NumEq => "numeq",
             BrNil => "brnil",
             Jmp => "jmp",
             Call => "call",
@@ -103,24 +123,88 @@ impl OpCode {
             _ => false,
         }
     }
+
+    /// The number of operands a primitive operation pops.
+    pub fn arity(&self) -> usize {
+        match *self {
+            OpCode::BitNot => 1,
+            _ => 2,
+        }
+    }
 }
 
+/// A compiled function: its instructions and the constants they refer to, both kept in heap
+/// buffers owned by the code object. Code objects are reclaimed once no closure, frame or
+/// other code object refers to them.
+///
+/// A `ByteCode` outside the heap, as handed out by `compile` and `assemble`, is a view of a
+/// code object that nothing refers to yet. It is only valid until the next collection, so it
+/// should be handed to a `Vm` straight away.
+#[repr(C)]
 pub struct ByteCode {
+    pub header: Header,
     pub consts: *const [Val],
     pub code: *const [u8],
 }
 
 impl Clone for ByteCode {
     fn clone(&self) -> ByteCode {
-        ByteCode { consts: self.consts, code: self.code }
+        ByteCode::from_parts(self.consts, self.code)
     }
 }
 
 impl ByteCode {
-    pub fn new(consts: *const [Val], code: *const [u8]) -> Val {
-       let mut ptr = Heap::new::<ByteCode>();
-       unsafe { std::ptr::write(ptr, ByteCode {consts, code}) };
-       Val::from_ptr(crate::values::Tag::Object, ptr as *mut _)
+    /// Copies `consts` and `code` into a new code object on the heap.
+    pub fn alloc(consts: &[Val], code: &[u8]) -> Val {
+        let consts = copy_to_heap(consts);
+        let code = copy_to_heap(code);
+        // Code objects tend to live as long as the functions defined with them, so skip the nursery.
+        let ptr = Heap::alloc_tenured::<ByteCode>();
+        unsafe { std::ptr::write(ptr, ByteCode::from_parts(consts, code)) };
+        let val = Val::from_ptr(Tag::Object, ptr as *mut _);
+        for c in unsafe { &*consts } {
+            Heap::write_barrier(val, *c);
+        }
+        val
+    }
+
+    /// A view of a code object's buffers, or of buffers outside the heap.
+    pub fn from_parts(consts: *const [Val], code: *const [u8]) -> ByteCode {
+        ByteCode { header: Header::new::<ByteCode>(), consts, code }
+    }
+}
+
+fn copy_to_heap<T: Copy>(items: &[T]) -> *const [T] {
+    if items.is_empty() {
+        return &[];
+    }
+    let ptr = Heap::alloc(size_of_val(items)) as *mut T;
+    unsafe {
+        std::ptr::copy_nonoverlapping(items.as_ptr(), ptr, items.len());
+        std::ptr::slice_from_raw_parts(ptr, items.len())
+    }
+}
+
+static BYTECODE: TypeDesc = TypeDesc::of::<ByteCode>("code", Tag::Object);
+
+unsafe impl Object for ByteCode {
+    fn desc() -> &'static TypeDesc {
+        &BYTECODE
+    }
+
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut Val)) {
+        for slot in unsafe { &mut *(self.consts as *mut [Val]) } {
+            visit(slot);
+        }
+    }
+
+    fn buffers(&self, mark: &mut dyn FnMut(*const u8)) {
+        if !self.consts.is_empty() {
+            mark(self.consts as *const u8);
+        }
+        if !self.code.is_empty() {
+            mark(self.code as *const u8);
+        }
     }
 }
 
@@ -167,7 +251,7 @@ impl<'a> TryFrom<Val> for ByteCode {
     fn try_from(v: Val) -> Result<ByteCode, crate::values::Tag> {
         use crate::values::Cases;
         match v.get() {
-            Cases::Object(p) => {
+            Cases::Code(p) => {
                 Ok(p.clone())
             }
             _ => {
@@ -182,7 +266,7 @@ impl<'a> TryFrom<&'a Val> for &'a ByteCode {
     fn try_from(v: &'a Val) -> Result<&'a ByteCode, crate::values::Tag> {
         use crate::values::Cases;
         match v.get() {
-            Cases::Object(p) => {
+            Cases::Code(p) => {
                 Ok(p)
             }
             _ => {
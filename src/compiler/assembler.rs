@@ -57,6 +57,33 @@ pub fn assemble(text: &str, global: &mut Global) -> Result<ByteCode, String> {
             "div" => {
                 code.push(Div as u8);
             }
+            "quot" => {
+                code.push(Quot as u8);
+            }
+            "rem" => {
+                code.push(Rem as u8);
+            }
+            "mod" => {
+                code.push(Mod as u8);
+            }
+            "bitand" => {
+                code.push(BitAnd as u8);
+            }
+            "bitor" => {
+                code.push(BitOr as u8);
+            }
+            "bitxor" => {
+                code.push(BitXor as u8);
+            }
+            "bitnot" => {
+                code.push(BitNot as u8);
+            }
+            "shl" => {
+                code.push(Shl as u8);
+            }
+            "shr" => {
+                code.push(Shr as u8);
+            }
             "gt" => {
                 code.push(Gt as u8);
             }
@@ -72,6 +99,9 @@ pub fn assemble(text: &str, global: &mut Global) -> Result<ByteCode, String> {
             "eq" => {
                 code.push(Eq as u8);
             }
+            "numeq" => {
+                code.push(NumEq as u8);
+            }
             "halt" => {
                 code.push(Halt as u8);
             }
@@ -129,20 +159,8 @@ pub fn assemble(text: &str, global: &mut Global) -> Result<ByteCode, String> {
         code[i as usize] = dest as u8;
     }
 
-    let code = code.into_boxed_slice();
-    let consts = consts.into_boxed_slice();
-
-    let bytecode =  ByteCode {
-        code: &*code as *const [u8],
-        consts: &*consts as *const [Val],
-    };
-
-    unsafe {
-        std::mem::forget(code);
-        std::mem::forget(consts);
-    }
-    
-    Ok(bytecode)
+    let code_obj = ByteCode::alloc(&consts, &code);
+    Ok(code_obj.try_into().unwrap())
 }
 
 fn parse_val(s: &str, global: &mut Global) -> Result<Val, String> {
@@ -2,7 +2,7 @@ use std::result;
 
 use super::*;
 use parse::Expr;
-use crate::{bytecode::ByteCode, compiler::parse::Primitives, values::{SymbolTable, Val}};
+use crate::{bytecode::ByteCode, compiler::parse::{Fold, Primitives}, values::{Str, SymbolTable, Val}};
 /// Walks an AST, emitting bytecode instructions into bytecode objects in the program heap
 pub struct Emitter<'scope, 'idents, 'symbols, 'primitives> {
     is_fn: bool,
@@ -42,10 +42,7 @@ impl<'scope, 'idents, 'symbols, 'primitives> Emitter<'scope, 'idents, 'symbols,
         if !self.is_fn {
             self.push_code(OpCode::Halt as u8);
         }
-        // todo: allocate this in the heap
-        let consts = Box::leak(self.consts.into_boxed_slice()) as *mut _;
-        let code = Box::leak(self.code.into_boxed_slice()) as *mut _;
-        let code_obj = ByteCode::new(consts, code);
+        let code_obj = ByteCode::alloc(&self.consts, &self.code);
         self.code_objs.push(code_obj);
         self.code_objs
     }
@@ -69,6 +66,49 @@ impl<'scope, 'idents, 'symbols, 'primitives> Emitter<'scope, 'idents, 'symbols,
         self.code.len()
     }
 
+    fn emit_const(&mut self, val: Val) {
+        self.push_code(OpCode::Const as u8);
+        self.push_code(self.consts.len() as u8);
+        self.push_const(val);
+    }
+
+    // Compares each pair of neighbouring operands, which are all evaluated first, and stops at
+    // the first comparison that fails.
+    fn emit_chain(&mut self, opcode: OpCode, args: &[Expr]) -> Result<(), EmitError> {
+        let base = self.sp;
+        for arg in args {
+            self.emit(arg)?;
+            self.sp += 1;
+        }
+        if self.sp > 256 {
+            return Err(EmitError::SlotTooLarge(self.sp))
+        }
+        let mut br_on_false_params = Vec::new();
+        for i in 0..args.len() - 1 {
+            self.push_code(OpCode::Dup as u8);
+            self.push_code((base + i) as u8);
+            self.push_code(OpCode::Dup as u8);
+            self.push_code((base + i + 1) as u8);
+            self.push_code(opcode as u8);
+            if i < args.len() - 2 {
+                self.push_code(OpCode::BrNil as u8);
+                br_on_false_params.push(self.push_code(0));
+            }
+        }
+        // The last comparison's result is the chain's.
+        self.push_code(OpCode::Jmp as u8);
+        let jmp_exit_param = self.push_code(0);
+        for param in br_on_false_params {
+            self.write(param, (self.end() - param - 1) as u8);
+        }
+        self.emit_const(Val::nil());
+        self.write(jmp_exit_param, (self.end() - jmp_exit_param - 1) as u8);
+        self.push_code(OpCode::PopSave as u8);
+        self.push_code(args.len() as u8);
+        self.sp = base;
+        Ok(())
+    }
+
     fn emit(&mut self, expr: &Expr) -> Result<(), EmitError> {
         use Expr::*;
         use crate::bytecode::OpCode;
@@ -76,7 +116,19 @@ impl<'scope, 'idents, 'symbols, 'primitives> Emitter<'scope, 'idents, 'symbols,
             NumLiteral(num) => {
                 self.push_code(OpCode::Const as u8);
                 self.push_code(self.consts.len() as u8);
-                self.push_const(Val::from_num(*num));
+                self.push_const(num.to_val());
+                Ok(())
+            }
+            StrLiteral(string) => {
+                self.push_code(OpCode::Const as u8);
+                self.push_code(self.consts.len() as u8);
+                self.push_const(Str::alloc(string));
+                Ok(())
+            }
+            CharLiteral(c) => {
+                self.push_code(OpCode::Const as u8);
+                self.push_code(self.consts.len() as u8);
+                self.push_const(Val::from_char(*c));
                 Ok(())
             }
             VectorLiteral(items) => {
@@ -139,13 +191,46 @@ impl<'scope, 'idents, 'symbols, 'primitives> Emitter<'scope, 'idents, 'symbols,
                 self.push_const(interned_symbol.as_val());
                 Ok(())
             }
-            PrimOp { op, left, right } => {
-                let opcode = self.primitives.get(*op).unwrap();
-                self.emit(left);
-                self.sp += 1;
-                self.emit(right);
-                self.push_code(*opcode as u8);
-                self.sp -= 1;
+            PrimOp { op, args } => {
+                let opcode = *self.primitives.get(*op).unwrap();
+                match (Fold::of(opcode), &args[..]) {
+                    (Fold::Left { unit, .. }, []) => {
+                        self.emit_const(Val::from_int(unit));
+                    }
+                    (Fold::Left { unit, .. }, [arg]) => {
+                        self.emit_const(Val::from_int(unit));
+                        self.sp += 1;
+                        self.emit(arg)?;
+                        self.push_code(opcode as u8);
+                        self.sp -= 1;
+                    }
+                    (Fold::Left { .. }, [first, rest @ ..]) => {
+                        self.emit(first)?;
+                        self.sp += 1;
+                        for arg in rest {
+                            self.emit(arg)?;
+                            self.push_code(opcode as u8);
+                        }
+                        self.sp -= 1;
+                    }
+                    (Fold::Chain, [arg]) => {
+                        self.emit(arg)?;
+                        self.push_code(OpCode::Pop as u8);
+                        self.push_code(1);
+                        self.emit_const(Val::t());
+                    }
+                    (Fold::Chain, args) if args.len() > 2 => {
+                        self.emit_chain(opcode, args)?;
+                    }
+                    (_, args) => {
+                        for arg in args {
+                            self.emit(arg)?;
+                            self.sp += 1;
+                        }
+                        self.push_code(opcode as u8);
+                        self.sp -= args.len();
+                    }
+                }
                 Ok(())
             }
             Apply { _fn, args } => {
@@ -231,9 +316,9 @@ impl<'scope, 'idents, 'symbols, 'primitives> Emitter<'scope, 'idents, 'symbols,
                 let jmp_exit_on_true_param = self.push_code(0); // after resultant block, jmp past end of the else-block
 
                 self.emit(else_branch)?;
-                // patch jmps
+                // patch jmps; offsets count from the byte after the operand
                 self.write(br_on_false_param, (jmp_exit_on_true_param - br_on_false_param) as u8);
-                self.write(jmp_exit_on_true_param, (self.end() - jmp_exit_on_true_param) as u8);
+                self.write(jmp_exit_on_true_param, (self.end() - jmp_exit_on_true_param - 1) as u8);
                 Ok(())
             }
             Set(symbol, value) => {
@@ -246,7 +331,9 @@ impl<'scope, 'idents, 'symbols, 'primitives> Emitter<'scope, 'idents, 'symbols,
                     self.push_code(OpCode::Const as u8);
                     self.push_code(self.consts.len() as u8);
                     self.push_const(interned_symbol.as_val());
+                    self.sp += 1;
                     self.emit(value)?;
+                    self.sp -= 1;
                     self.push_code(OpCode::SymSet as u8);
                     Ok(())
                 }
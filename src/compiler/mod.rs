@@ -9,8 +9,9 @@ use sexp::Sexp;
 use idents::{Ident, IdentTable};
 use read::Reader;
 use parse::{Specials, parse};
-use crate::{bytecode::OpCode, compiler::{emit::EmitError, parse::Primitives, read::ReadError, parse::ParseError}, values::{Symbol, SymbolTable}};
+use crate::{bytecode::OpCode, compiler::{emit::EmitError, parse::Primitives, read::ReadError, parse::ParseError}, values::{Number, Symbol, SymbolTable}};
 pub use assembler::assemble;
+pub use read::parse_number;
 use crate::bytecode::ByteCode;
 
 const PRIMITIVES: &[(&'static str, OpCode)] = &[
@@ -18,11 +19,21 @@ const PRIMITIVES: &[(&'static str, OpCode)] = &[
     ("-", OpCode::Sub),
     ("*", OpCode::Mul),
     ("/", OpCode::Div),
+    ("quot", OpCode::Quot),
+    ("rem", OpCode::Rem),
+    ("mod", OpCode::Mod),
+    ("bit-and", OpCode::BitAnd),
+    ("bit-or", OpCode::BitOr),
+    ("bit-xor", OpCode::BitXor),
+    ("bit-not", OpCode::BitNot),
+    ("bit-shift-left", OpCode::Shl),
+    ("bit-shift-right", OpCode::Shr),
     ("<", OpCode::Lt),
     (">", OpCode::Gt),
     ("<=", OpCode::Lte),
     (">=", OpCode::Gte),
     ("eq", OpCode::Eq),
+    ("=", OpCode::NumEq),
 ];
 
 #[derive(Debug)]
@@ -146,7 +157,7 @@ mod test {
         match parsed {
             Let { bindings, body } => {
                 match &bindings[..] {
-                    [(x, NumLiteral(0.0)), (y, NumLiteral(1.0)), (z, PrimOp { op: times, left, right })] 
+                    [(x, NumLiteral(Number::Int(0))), (y, NumLiteral(Number::Int(1))), (z, PrimOp { op: times, args })] 
                     if name_of(x) == "x" && name_of(y) == "y" && name_of(z) == "z" 
                     && name_of(times) == "*" => {
                         // at this point I got tired of matching through boxes without box patterns
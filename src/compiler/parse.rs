@@ -1,6 +1,7 @@
 //! Lowers an sexp into an AST after validating the structure.
 
 use crate::bytecode::OpCode;
+use crate::values::{Number, Val};
 
 use super::*;
 
@@ -22,7 +23,9 @@ pub enum ParseError {
 use ParseError::*;
 
 pub enum Expr {
-    NumLiteral(f64),
+    NumLiteral(Number),
+    StrLiteral(String),
+    CharLiteral(char),
     VectorLiteral(Vec<Expr>),
     MapLiteral(Vec<(Expr, Expr)>),
     Ident(Ident),
@@ -47,8 +50,7 @@ pub enum Expr {
     Cond(Vec<(Expr, Expr)>),
     PrimOp {
         op: Ident,
-        left: Box<Expr>,
-        right: Box<Expr>,
+        args: Vec<Expr>,
     },
     Do(Vec<Expr>),
     Set(Ident, Box<Expr>),
@@ -99,10 +101,47 @@ impl Primitives {
     }
 }
 
+/// How a call to a primitive is compiled into a chain of its opcode.
+#[derive(Clone, Copy)]
+pub enum Fold {
+    /// Takes exactly as many operands as the opcode.
+    Fixed,
+    /// Applied left to right, as in `(+ a b c)`. `unit` is the result of calling it with no
+    /// operands, and the left operand when there is only one, so `(- x)` is `(- 0 x)`.
+    Left { unit: i32, min: usize },
+    /// Applied to each pair of neighbours, holding only if all of them do, as in `(< a b c)`.
+    Chain,
+}
+
+impl Fold {
+    pub fn of(op: OpCode) -> Fold {
+        use OpCode::*;
+        match op {
+            Add | BitOr | BitXor => Fold::Left { unit: 0, min: 0 },
+            Mul => Fold::Left { unit: 1, min: 0 },
+            BitAnd => Fold::Left { unit: -1, min: 0 },
+            Sub => Fold::Left { unit: 0, min: 1 },
+            Div => Fold::Left { unit: 1, min: 1 },
+            Lt | Gt | Lte | Gte | NumEq | Eq => Fold::Chain,
+            _ => Fold::Fixed,
+        }
+    }
+
+    pub fn accepts(&self, op: OpCode, operands: usize) -> bool {
+        match self {
+            Fold::Fixed => operands == op.arity(),
+            Fold::Left { min, .. } => operands >= *min,
+            Fold::Chain => operands >= 1,
+        }
+    }
+}
+
 pub fn parse(sexp: &Sexp, specials: &Specials, primitives: &Primitives) -> Result<Expr, ParseError> {
     use Sexp::*;
     match sexp {
-        Number(num) => Ok(Expr::NumLiteral(*num)),
+        Number(num) => Ok(Expr::NumLiteral(num.clone())),
+        String(string) => Ok(Expr::StrLiteral(string.clone())),
+        Char(c) => Ok(Expr::CharLiteral(*c)),
         Ident(sym) => Ok(Expr::Ident(*sym)),
         Keyword(sym) => Ok(Expr::Keyword(*sym)),
         List(items) => {
@@ -223,15 +262,14 @@ pub fn parse(sexp: &Sexp, specials: &Specials, primitives: &Primitives) -> Resul
                     }
                     Ok(Expr::Ret(Box::new(parse(&items[1], specials, primitives)?)))
                 }
-                Ident(sym) if primitives.get(*sym).is_some() => {
+                Ident(sym) if let Some(opcode) = primitives.get(*sym) => {
                     let args = &items[1..];
-                    if args.len() != 2 {
+                    if !Fold::of(*opcode).accepts(*opcode, args.len()) {
                         return Err(PrimOpWrongArity)
                     } else {
                         Ok(Expr::PrimOp {
                             op: *sym,
-                            left: Box::new(parse(&args[0], specials, primitives)?),
-                            right: Box::new(parse(&args[1], specials, primitives)?),
+                            args: parse_list(args, specials, primitives)?,
                         })
                     }
                 }
@@ -270,7 +308,19 @@ pub fn parse(sexp: &Sexp, specials: &Specials, primitives: &Primitives) -> Resul
                 }
                 Number(num) => {
                     Ok(Expr::Apply {
-                        _fn: Box::new(Expr::NumLiteral(*num)),
+                        _fn: Box::new(Expr::NumLiteral(num.clone())),
+                        args: parse_list(&items[1..], specials, primitives)?
+                    })
+                }
+                String(string) => {
+                    Ok(Expr::Apply {
+                        _fn: Box::new(Expr::StrLiteral(string.clone())),
+                        args: parse_list(&items[1..], specials, primitives)?
+                    })
+                }
+                Char(c) => {
+                    Ok(Expr::Apply {
+                        _fn: Box::new(Expr::CharLiteral(*c)),
                         args: parse_list(&items[1..], specials, primitives)?
                     })
                 }
@@ -306,6 +356,8 @@ impl Expr {
         use parse::Expr::*;
         match self {
             NumLiteral(num) => print!("{}i\n", num),
+            StrLiteral(string) => println!("{:indent_level$}{:?}", "", string),
+            CharLiteral(c) => println!("{:indent_level$}{:?}", "", Val::from_char(*c)),
             VectorLiteral(items) => {
                 print!("{:indent_level$}VEC\n", "");
                 for i in items {
@@ -347,12 +399,12 @@ impl Expr {
             print!("{:indent_level$}", "");
             else_branch.pprint(idents, indent_level + 2);
         }
-        PrimOp { op, left, right } => {
+        PrimOp { op, args } => {
             print!("{}\n", idents.get_name(*op));
-            print!("{:width$}", "", width=indent_level + 2);
-            left.pprint(idents, indent_level + 2);
-            print!("{:width$}", "", width=indent_level + 2);
-            right.pprint(idents, indent_level + 2);
+            for arg in args {
+                print!("{:width$}", "", width=indent_level + 2);
+                arg.pprint(idents, indent_level + 2);
+            }
         }
         Do(exprs) => {
             print!("DO ");
@@ -3,6 +3,7 @@
 use std::str::Chars;
 use super::Sexp;
 use super::{IdentTable, Ident};
+use crate::values::{BigInt, Number, Ratio, CHAR_NAMES};
 
 #[derive(Debug, PartialEq, Eq)]
 enum ReadErrorReason {
@@ -13,6 +14,9 @@ enum ReadErrorReason {
     UnbalancedBrace,
     UnbalancedMapItems,
     BareColon,
+    InvalidEscape(String),
+    InvalidChar(String),
+    UnterminatedString,
     EOF
 }
 use ReadErrorReason::*;
@@ -51,6 +55,15 @@ impl std::fmt::Debug for ReadError {
             BareColon => {
                 write!(f, "Invalid symbol name: ':' ")
             }
+            InvalidEscape(ref escape) => {
+                write!(f, "\\{} is not a valid escape", escape)
+            }
+            InvalidChar(ref name) => {
+                write!(f, "\\{} is not a valid character", name)
+            }
+            UnterminatedString => {
+                write!(f, "Unexpected end of file in string literal; missing closing '\"'")
+            }
         }
     }
 }
@@ -125,7 +138,13 @@ impl<'src, 'sym> Reader<'src, 'sym> {
             Some((i, ':')) => {
                 self.read_keyword(i)
             }
-            Some((i, c)) if is_number_start_char(c) => {
+            Some((_, '"')) => {
+                self.read_string()
+            }
+            Some((_, '\\')) => {
+                self.read_char()
+            }
+            Some((i, c)) if is_number_start_char(c) && self.starts_number(i) => {
                 self.read_number(i)
             }
             Some((i, c)) if is_symbol_start_char(c) => {
@@ -245,6 +264,80 @@ impl<'src, 'sym> Reader<'src, 'sym> {
         Ok(Sexp::Ident(self.idents.intern(chars)))
     }
 
+    // Strings may span lines, and understand the escapes \n, \t, \r, \0, \\, \" and \u{...}.
+    fn read_string(&mut self) -> Result<Sexp, ReadError> {
+        self.next(); // trim '"'
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                None => return Err(self.error(UnterminatedString)),
+                Some((_, '"')) => return Ok(Sexp::String(string)),
+                Some((_, '\\')) => string.push(self.read_escape()?),
+                Some((_, c)) => string.push(c),
+            }
+        }
+    }
+
+    fn read_escape(&mut self) -> Result<char, ReadError> {
+        let c = match self.next() {
+            None => return Err(self.error(UnterminatedString)),
+            Some((_, c)) => c,
+        };
+        match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '\\' | '"' => Ok(c),
+            'u' => {
+                let mut escape = String::from("u");
+                while let Some((_, c)) = self.next() {
+                    escape.push(c);
+                    if c == '}' || c == '"' {
+                        break;
+                    }
+                }
+                let code = escape.strip_prefix("u{").and_then(|rest| rest.strip_suffix('}'));
+                match code.and_then(|hex| u32::from_str_radix(hex, 16).ok()).and_then(char::from_u32) {
+                    Some(c) => Ok(c),
+                    None => Err(self.error(InvalidEscape(escape))),
+                }
+            }
+            c => Err(self.error(InvalidEscape(c.to_string()))),
+        }
+    }
+
+    // A character is `\` and then the character itself, a name from `CHAR_NAMES`, or `u{...}`.
+    // Letters and digits run on into a name, so a letter must be followed by a delimiter.
+    fn read_char(&mut self) -> Result<Sexp, ReadError> {
+        self.next(); // trim '\\'
+        let first = match self.next() {
+            None => return Err(self.error(EOF)),
+            Some((_, c)) => c,
+        };
+        let mut name = String::from(first);
+        if first.is_alphanumeric() {
+            while let Some(&(_, c)) = self.peek() && (c.is_alphanumeric() || (name == "u" && c == '{') || name.starts_with("u{")) {
+                name.push(c);
+                self.next();
+                if c == '}' {
+                    break;
+                }
+            }
+        }
+        if name.chars().count() == 1 {
+            return Ok(Sexp::Char(first));
+        }
+        if let Some((_, c)) = CHAR_NAMES.iter().find(|(named, _)| *named == name) {
+            return Ok(Sexp::Char(*c));
+        }
+        let code = name.strip_prefix("u{").and_then(|rest| rest.strip_suffix('}'));
+        match code.and_then(|hex| u32::from_str_radix(hex, 16).ok()).and_then(char::from_u32) {
+            Some(c) => Ok(Sexp::Char(c)),
+            None => Err(self.error(InvalidChar(name))),
+        }
+    }
+
     fn read_number(&mut self, start: usize) -> Result<Sexp, ReadError> {
         let mut last_index = 0;
         while let Some((i, c)) = self.chars.peek() {
@@ -255,12 +348,17 @@ impl<'src, 'sym> Reader<'src, 'sym> {
             self.chars.next();
         }
         let digits = &self.src[start..last_index + 1];
-        match digits.parse::<f64>() {
-            Ok(num) => Ok(Sexp::Number(num)),
-            Err(_) => Err(self.error(NumberParseErr(digits.to_string())))
+        match parse_number(digits) {
+            Some(num) => Ok(Sexp::Number(num)),
+            None => Err(self.error(NumberParseErr(digits.to_string())))
         }
     }
 
+    // A lone `-`, as in `(- a b)`, is a symbol. It only starts a number when digits follow.
+    fn starts_number(&self, start: usize) -> bool {
+        self.src[start..].chars().take_while(|c| is_number_char(*c)).any(|c| c.is_ascii_digit())
+    }
+
     fn trim_whitespace(&mut self) {
         while let Some((i, c)) = self.chars.peek() && is_whitespace(*c) {
             self.chars.next();
@@ -268,7 +366,28 @@ impl<'src, 'sym> Reader<'src, 'sym> {
     }
 }
 
-const SYMBOL_CHARS: &'static str = "+-*/_!<>=";
+/// Parses a number written as the reader would read it, or `None` if `digits` is not one.
+pub fn parse_number(digits: &str) -> Option<Number> {
+    if !digits.chars().all(is_number_char) || !digits.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    // Integer literals of any size are read as integers.
+    if let Some(int) = BigInt::parse(digits) {
+        return Some(Number::integer(int));
+    }
+    // Ratios are read in lowest terms, so `2/4` is `1/2` and `4/2` is `2`.
+    if let Some((numerator, denominator)) = digits.split_once('/') {
+        return match (BigInt::parse(numerator), BigInt::parse(denominator)) {
+            (Some(n), Some(d)) if !d.is_zero() && !denominator.starts_with('-') => {
+                Some(Number::rational(Ratio::new(n, d)))
+            }
+            _ => None
+        }
+    }
+    digits.parse::<f64>().ok().map(Number::Float)
+}
+
+const SYMBOL_CHARS: &'static str = "+-*/_!?<>=";
 
 fn is_symbol_start_char(c: char) -> bool {
     c.is_alphanumeric() || SYMBOL_CHARS.contains(c)
@@ -279,7 +398,7 @@ fn is_number_start_char(c: char) -> bool {
 }
 
 fn is_number_char(c: char) -> bool {
-    c.is_ascii_digit() || c == '.' || c == '_' || c == '-'
+    c.is_ascii_digit() || c == '.' || c == '_' || c == '-' || c == '/'
 }
 
 fn is_symbol_char(c: char) -> bool {
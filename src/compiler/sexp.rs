@@ -1,5 +1,6 @@
 use super::Ident;
 use super::IdentTable;
+use crate::values::{Number, Val};
 
 pub enum Sexp {
     List(Vec<Sexp>),
@@ -7,7 +8,9 @@ pub enum Sexp {
     Map(Vec<(Sexp, Sexp)>),
     Ident(Ident),
     Keyword(Ident),
-    Number(f64),
+    Number(Number),
+    String(String),
+    Char(char),
 }
 
 impl Sexp {
@@ -91,8 +94,14 @@ pub fn print_sexp(sexp: &Sexp, ident_table: &IdentTable) {
             let name = ident_table.get_name(ident);
             print!(":{}", name)
         }
-        &Sexp::Number(num) => {
+        Sexp::Number(num) => {
             print!("{}", num)
         }
+        Sexp::String(string) => {
+            print!("{:?}", string)
+        }
+        Sexp::Char(c) => {
+            print!("{:?}", Val::from_char(*c))
+        }
     }
 }
\ No newline at end of file
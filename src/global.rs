@@ -1,21 +1,114 @@
+use std::io::{self, Write};
+use std::path::Path;
+
 use crate::values::{Symbol, SymbolTable};
-use crate::intrinsics;
+use crate::{intrinsics, math, random};
+use crate::random::Rng;
+use crate::values::Val;
+use crate::alloc::{AllocationProfile, DumpFormat, Finalizer, HandleScope, Heap, HeapConfig, HeapStats, Root};
 
 pub struct Global {
     pub st: SymbolTable,
+    pub(crate) rng: Rng,
 }
 
 impl Global {
+    /// Creates a global environment on this thread's heap, leaving the heap's configuration as it is.
     pub fn new() -> Global {
         let mut st = SymbolTable::new();
-        for (name, function) in intrinsics::INTRINSICS {
+        for (name, function) in intrinsics::INTRINSICS.iter().chain(math::INTRINSICS).chain(random::INTRINSICS) {
             let mut sym = st.intern(name);
             sym.set(function.to_val())
         }
-        Global { st }
+        for (name, value) in math::CONSTANTS {
+            let mut sym = st.intern(name);
+            sym.set(Val::from_num(*value))
+        }
+        Global { st, rng: Rng::from_entropy() }
+    }
+
+    /// Creates a global environment after applying `config` to this thread's heap.
+    /// The heap is shared by every `Global` on the thread, so the latest configuration wins.
+    pub fn with_config(config: HeapConfig) -> Global {
+        Heap::configure(config);
+        Global::new()
     }
     
+    /// Restarts the random number generator from `seed`, so that the numbers scripts draw from
+    /// then on are the same on every run.
+    pub fn seed_random(&mut self, seed: u64) {
+        self.rng = Rng::new(seed)
+    }
+
     pub fn intern(&mut self, name: &str) -> Symbol {
         self.st.intern(name)
     }
-}
\ No newline at end of file
+
+    /// Runs major collections incrementally, doing at most `budget` work per slice: objects
+    /// scanned, spans swept and pages returned to the OS. `None` collects the whole heap in a
+    /// single pause.
+    pub fn set_pause_budget(&mut self, budget: Option<usize>) {
+        Heap::set_pause_budget(budget)
+    }
+
+    /// Keeps `val` alive, and up to date as collections move it, until the `Root` is dropped.
+    /// Values the host holds on to between VM runs must be rooted.
+    pub fn root(&mut self, val: Val) -> Root {
+        Root::new(val)
+    }
+
+    /// Opens a scope rooting short-lived values, such as those a native function holds while it
+    /// calls back into the VM.
+    pub fn handle_scope(&mut self) -> HandleScope {
+        HandleScope::new()
+    }
+
+    /// Calls `f` with `val` after a collection finds it unreachable, for objects that own
+    /// resources outside the heap.
+    pub fn set_finalizer(&mut self, val: Val, f: impl FnOnce(Val) + 'static) {
+        Heap::register_finalizer(val, Finalizer::Native(Box::new(f)))
+    }
+
+    /// Checks the heap for corruption, describing the first problem found.
+    pub fn verify_heap(&self) -> Result<(), String> {
+        Heap::verify(&mut |_| {})
+    }
+
+    /// Makes every VM created from now on verify the heap after each `interval` steps,
+    /// panicking if it finds a problem. `None` turns verification off.
+    pub fn set_verify_interval(&mut self, interval: Option<usize>) {
+        Heap::set_verify_interval(interval)
+    }
+
+    /// Sets how many completely empty arenas the heap keeps after a collection.
+    /// The rest are unmapped.
+    pub fn set_empty_arena_limit(&mut self, limit: usize) {
+        Heap::set_empty_arena_limit(limit)
+    }
+
+    pub fn heap_stats(&self) -> HeapStats {
+        Heap::stats()
+    }
+
+    /// Writes every object reachable from the global environment to `path`,
+    /// as Graphviz if it ends in `.dot` and as text otherwise.
+    pub fn dump_heap(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        Heap::dump_to_file(&mut |_| {}, DumpFormat::for_path(path), path)
+    }
+
+    pub fn write_heap_dump(&self, format: DumpFormat, out: &mut dyn Write) -> io::Result<()> {
+        Heap::dump(&mut |_| {}, format, out)
+    }
+
+    /// Starts counting allocations by the instruction making them and the type allocated.
+    /// Only VMs created afterwards charge allocations to their instructions.
+    pub fn start_allocation_profile(&mut self) {
+        Heap::start_profiling()
+    }
+
+    /// Stops profiling allocations and hands back the counts, or `None` if no profile was started.
+    pub fn stop_allocation_profile(&mut self) -> Option<AllocationProfile> {
+        Heap::stop_profiling()
+    }
+}
@@ -1,5 +1,9 @@
 use crate::{common::*, global::Global};
-use crate::values::{Cases, NativeFn, Val};
+use crate::alloc::{DumpFormat, Finalizer};
+use std::cmp::Ordering;
+
+use crate::compiler;
+use crate::values::{Cases, Map, NativeFn, Number, Str, Symbol, Tag, Val, Vector, WeakRef};
 
 pub const INTRINSICS: &[(&str, NativeFn)] = &[
     ("print", NativeFn(print)),
@@ -13,7 +17,62 @@ pub const INTRINSICS: &[(&str, NativeFn)] = &[
     ("map-get", NativeFn(map_get)),
     ("map-length", NativeFn(map_length)),
     ("map-remove!", NativeFn(map_remove)),
-    ("map-clear!", NativeFn(map_clear))
+    ("map-clear!", NativeFn(map_clear)),
+    ("str", NativeFn(str)),
+    ("string-length", NativeFn(string_length)),
+    ("substring", NativeFn(substring)),
+    ("split", NativeFn(split)),
+    ("join", NativeFn(join)),
+    ("trim", NativeFn(trim)),
+    ("upper", NativeFn(upper)),
+    ("lower", NativeFn(lower)),
+    ("starts-with?", NativeFn(starts_with)),
+    ("ends-with?", NativeFn(ends_with)),
+    ("index-of", NativeFn(index_of)),
+    ("replace", NativeFn(replace)),
+    ("parse-number", NativeFn(parse_number)),
+    ("format", NativeFn(format)),
+    ("chars", NativeFn(chars)),
+    ("char-at", NativeFn(char_at)),
+    ("char->int", NativeFn(char_to_int)),
+    ("int->char", NativeFn(int_to_char)),
+    ("alpha?", NativeFn(is_alpha)),
+    ("digit?", NativeFn(is_digit)),
+    ("whitespace?", NativeFn(is_whitespace)),
+    ("char=?", NativeFn(char_equal)),
+    ("char<?", NativeFn(char_less)),
+    ("char>?", NativeFn(char_greater)),
+    ("char<=?", NativeFn(char_less_or_equal)),
+    ("char>=?", NativeFn(char_greater_or_equal)),
+    ("weak-ref", NativeFn(weak_ref)),
+    ("weak-get", NativeFn(weak_get)),
+    ("weak-map", NativeFn(weak_map)),
+    ("gc", NativeFn(gc)),
+    ("set-finalizer!", NativeFn(set_finalizer)),
+    ("dump-heap", NativeFn(dump_heap)),
+    ("equal?", NativeFn(equal)),
+    ("numerator", NativeFn(numerator)),
+    ("denominator", NativeFn(denominator)),
+    ("float", NativeFn(float)),
+    ("+", NativeFn(add)),
+    ("-", NativeFn(sub)),
+    ("*", NativeFn(mul)),
+    ("/", NativeFn(div)),
+    ("quot", NativeFn(quot)),
+    ("rem", NativeFn(rem)),
+    ("mod", NativeFn(modulo)),
+    ("bit-and", NativeFn(bit_and)),
+    ("bit-or", NativeFn(bit_or)),
+    ("bit-xor", NativeFn(bit_xor)),
+    ("bit-not", NativeFn(bit_not)),
+    ("bit-shift-left", NativeFn(shift_left)),
+    ("bit-shift-right", NativeFn(shift_right)),
+    ("<", NativeFn(less)),
+    (">", NativeFn(greater)),
+    ("<=", NativeFn(less_or_equal)),
+    (">=", NativeFn(greater_or_equal)),
+    ("=", NativeFn(numeric_equal)),
+    ("eq", NativeFn(eq)),
 ];
 
 pub fn print(args: &[Val], global: &mut Global) -> (Val, bool) {
@@ -40,6 +99,7 @@ pub fn vector_push(args: &[Val], global: &mut Global) -> (Val, bool) {
     let (_vector, to_push) = (args[0], args[1]);
     match _vector.get() {
         Cases::Vector(vector) => {
+            Heap::write_barrier(_vector, to_push);
             vector.push(to_push);
         }
         _ => unimplemented!()
@@ -98,10 +158,12 @@ pub fn vector_set(args: &[Val], global: &mut Global) -> (Val, bool) {
      match (_vector.get(), _index.get()) {
         (Cases::Vector(vector), Cases::Num(index)) => {
             if index < 0.0 { unimplemented!() }
+            Heap::write_barrier(_vector, to_add);
             vector.set(index as usize, to_add)
         }
         (Cases::Vector(vector), Cases::Int(index)) => {
             if index < 0 { unimplemented!() }
+            Heap::write_barrier(_vector, to_add);
             vector.set(index as usize, to_add)
         }
         _ => unimplemented!()
@@ -115,6 +177,8 @@ pub fn map_put(args: &[Val], global: &mut Global) -> (Val, bool) {
     let (_map, _key, _val) = (args[0], args[1], args[2]);
     match _map.get() {
         Cases::Map(map) => {
+            Heap::write_barrier(_map, _key);
+            Heap::write_barrier(_map, _val);
             (map.insert(_key, _val), false)
         }
         _ => unimplemented!()
@@ -168,4 +232,535 @@ pub fn map_clear(args: &[Val], global: &mut Global) -> (Val, bool) {
         }
         _ => unimplemented!()
     }
-}
\ No newline at end of file
+}
+
+fn string<'a>(name: &str, val: &'a Val) -> &'a str {
+    match val.get() {
+        Cases::Str(s) => s.as_str(),
+        _ => panic!("{} expects a string, got {:?}", name, val),
+    }
+}
+
+// Strings index by characters rather than bytes, so that they never split one.
+fn index(name: &str, val: Val) -> usize {
+    match val.get() {
+        Cases::Int(i) if i >= 0 => i as usize,
+        _ => panic!("{} expects an index, got {:?}", name, val),
+    }
+}
+
+fn boolean(b: bool) -> Val {
+    if b { Symbol::t() } else { Symbol::nil() }
+}
+
+// How a value reads in text built from it: strings and characters as themselves, without
+// quotes or backslashes, and anything else as it prints.
+fn display(val: Val) -> String {
+    match (val.get(), Number::of(val)) {
+        (Cases::Str(s), _) => s.as_str().to_string(),
+        (Cases::Char(c), _) => c.to_string(),
+        (_, Some(n)) => n.to_string(),
+        _ => format!("{:?}", val),
+    }
+}
+
+fn new_vector(items: impl IntoIterator<Item = Val>) -> Val {
+    let ptr = Heap::alloc_object::<Vector>();
+    unsafe { std::ptr::write(ptr, Vector::new()) };
+    let val = Val::from_ptr(Tag::Vector, ptr as *mut u8);
+    for item in items {
+        Heap::write_barrier(val, item);
+        unsafe { (*ptr).push(item) };
+    }
+    val
+}
+
+// (str x ...) -> the values written out one after another, strings and characters as themselves
+pub fn str(args: &[Val], global: &mut Global) -> (Val, bool) {
+    let text: String = args.iter().map(|arg| display(*arg)).collect();
+    (Str::alloc(&text), false)
+}
+
+// (string-length s) -> the number of characters in s
+// (string-length s :bytes) -> the number of bytes in s's UTF-8 encoding
+pub fn string_length(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1 || args.len() == 2);
+    let s = string("string-length", &args[0]);
+    let len = match args.get(1).map(|arg| arg.get()) {
+        None => s.chars().count(),
+        Some(Cases::Symbol(sym)) if sym.name() == "bytes" => s.len(),
+        Some(Cases::Symbol(sym)) if sym.name() == "chars" => s.chars().count(),
+        _ => panic!("string-length counts :chars or :bytes, got {:?}", args[1]),
+    };
+    (Number::Int(len as i64).to_val(), false)
+}
+
+// (substring s start) or (substring s start end) -> the characters of s from start up to end,
+// or to the end of s
+pub fn substring(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2 || args.len() == 3);
+    let s = string("substring", &args[0]);
+    let len = s.chars().count();
+    let start = index("substring", args[1]);
+    let end = args.get(2).map_or(len, |end| index("substring", *end));
+    assert!(start <= end && end <= len, "substring {}..{} is out of bounds for a string of length {}", start, end, len);
+    let text: String = s.chars().skip(start).take(end - start).collect();
+    (Str::alloc(&text), false)
+}
+
+// (split s separator) -> a vector of the pieces of s between each separator, or of its
+// characters if the separator is empty
+pub fn split(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    let (s, separator) = (string("split", &args[0]), string("split", &args[1]));
+    let pieces: Vec<Val> = match separator {
+        "" => s.chars().map(|c| Str::alloc(c.encode_utf8(&mut [0; 4]))).collect(),
+        _ => s.split(separator).map(Str::alloc).collect(),
+    };
+    (new_vector(pieces), false)
+}
+
+// (join v) or (join v separator) -> the items of v written out with separator between them
+pub fn join(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1 || args.len() == 2);
+    let separator = args.get(1).map_or("", |separator| string("join", separator));
+    let text = match args[0].get() {
+        Cases::Vector(vector) => vector.iter().map(display).collect::<Vec<_>>().join(separator),
+        _ => panic!("join expects a vector, got {:?}", args[0]),
+    };
+    (Str::alloc(&text), false)
+}
+
+// (trim s) -> s without whitespace at either end
+pub fn trim(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (Str::alloc(string("trim", &args[0]).trim()), false)
+}
+
+// (upper s) -> s in upper case
+pub fn upper(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (Str::alloc(&string("upper", &args[0]).to_uppercase()), false)
+}
+
+// (lower s) -> s in lower case
+pub fn lower(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (Str::alloc(&string("lower", &args[0]).to_lowercase()), false)
+}
+
+// (starts-with? s prefix) -> t if s begins with prefix, nil otherwise
+pub fn starts_with(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    (boolean(string("starts-with?", &args[0]).starts_with(string("starts-with?", &args[1]))), false)
+}
+
+// (ends-with? s suffix) -> t if s ends with suffix, nil otherwise
+pub fn ends_with(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    (boolean(string("ends-with?", &args[0]).ends_with(string("ends-with?", &args[1]))), false)
+}
+
+// (index-of s needle) -> the character index of the first needle in s, or nil
+pub fn index_of(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    let s = string("index-of", &args[0]);
+    match s.find(string("index-of", &args[1])) {
+        Some(byte) => (Number::Int(s[..byte].chars().count() as i64).to_val(), false),
+        None => (Val::nil(), false),
+    }
+}
+
+// (replace s from to) -> s with every from replaced by to
+pub fn replace(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 3);
+    let (s, from, to) = (string("replace", &args[0]), string("replace", &args[1]), string("replace", &args[2]));
+    assert!(!from.is_empty(), "replace cannot replace the empty string");
+    (Str::alloc(&s.replace(from, to)), false)
+}
+
+// (parse-number s) -> the number s is written as, read as a literal would be, or nil
+pub fn parse_number(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    match compiler::parse_number(string("parse-number", &args[0]).trim()) {
+        Some(n) => (n.to_val(), false),
+        None => (Val::nil(), false),
+    }
+}
+
+// (format template x ...) -> template with each `{}` replaced by the next x and each `{i}` by
+// the i-th x, counting from 0. `{{` and `}}` stand for braces.
+pub fn format(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(!args.is_empty());
+    let template = string("format", &args[0]);
+    let values = &args[1..];
+    let mut text = String::new();
+    let mut next = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut position = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => position.push(c),
+                        None => panic!("Unclosed placeholder in format string {:?}", template),
+                    }
+                }
+                let i = match position.as_str() {
+                    "" => {
+                        next += 1;
+                        next - 1
+                    }
+                    _ => position.parse().unwrap_or_else(|_| panic!("Invalid placeholder {{{}}} in format string {:?}", position, template)),
+                };
+                let value = values.get(i).unwrap_or_else(|| panic!("Format string {:?} has no argument {}", template, i));
+                text.push_str(&display(*value));
+            }
+            '}' => panic!("Unmatched '}}' in format string {:?}", template),
+            c => text.push(c),
+        }
+    }
+    (Str::alloc(&text), false)
+}
+
+fn character(name: &str, val: Val) -> char {
+    val.get_char().unwrap_or_else(|| panic!("{} expects a character, got {:?}", name, val))
+}
+
+// (chars s) -> a vector of the characters of s, in order
+pub fn chars(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (new_vector(string("chars", &args[0]).chars().map(Val::from_char)), false)
+}
+
+// (char-at s i) -> the i-th character of s
+pub fn char_at(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    let (s, i) = (string("char-at", &args[0]), index("char-at", args[1]));
+    match s.chars().nth(i) {
+        Some(c) => (Val::from_char(c), false),
+        None => panic!("char-at {} is out of bounds for a string of length {}", i, s.chars().count()),
+    }
+}
+
+// (char->int c) -> the Unicode code point of c
+pub fn char_to_int(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (Val::from_int(character("char->int", args[0]) as i32), false)
+}
+
+// (int->char n) -> the character whose Unicode code point is n
+pub fn int_to_char(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    let c = args[0].get_int().and_then(|i| u32::try_from(i).ok()).and_then(char::from_u32);
+    match c {
+        Some(c) => (Val::from_char(c), false),
+        None => panic!("{:?} is not a Unicode code point", args[0]),
+    }
+}
+
+// (alpha? c) -> t if c is a letter in any script, nil otherwise
+pub fn is_alpha(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (boolean(character("alpha?", args[0]).is_alphabetic()), false)
+}
+
+// (digit? c) -> t if c is one of the digits 0 to 9, nil otherwise
+pub fn is_digit(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (boolean(character("digit?", args[0]).is_ascii_digit()), false)
+}
+
+// (whitespace? c) -> t if c is whitespace, nil otherwise
+pub fn is_whitespace(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (boolean(character("whitespace?", args[0]).is_whitespace()), false)
+}
+
+// Characters order by their code points.
+fn char_chain(name: &str, args: &[Val], holds: fn(char, char) -> bool) -> (Val, bool) {
+    assert!(!args.is_empty());
+    let chars: Vec<char> = args.iter().map(|arg| character(name, *arg)).collect();
+    (boolean(chars.windows(2).all(|pair| holds(pair[0], pair[1]))), false)
+}
+
+// (char=? c ...) -> t if every c is the same character
+pub fn char_equal(args: &[Val], global: &mut Global) -> (Val, bool) {
+    char_chain("char=?", args, |a, b| a == b)
+}
+
+// (char<? c ...) -> t if every c comes before the next
+pub fn char_less(args: &[Val], global: &mut Global) -> (Val, bool) {
+    char_chain("char<?", args, |a, b| a < b)
+}
+
+// (char>? c ...) -> t if every c comes after the next
+pub fn char_greater(args: &[Val], global: &mut Global) -> (Val, bool) {
+    char_chain("char>?", args, |a, b| a > b)
+}
+
+// (char<=? c ...) -> t if no c comes after the next
+pub fn char_less_or_equal(args: &[Val], global: &mut Global) -> (Val, bool) {
+    char_chain("char<=?", args, |a, b| a <= b)
+}
+
+// (char>=? c ...) -> t if no c comes before the next
+pub fn char_greater_or_equal(args: &[Val], global: &mut Global) -> (Val, bool) {
+    char_chain("char>=?", args, |a, b| a >= b)
+}
+
+// (weak-ref value) -> weak reference
+pub fn weak_ref(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (WeakRef::alloc(args[0]), false)
+}
+
+// (weak-get weak) -> value, or nil once it has been collected
+pub fn weak_get(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    match args[0].get() {
+        Cases::WeakRef(weak) => (weak.get(), false),
+        _ => unimplemented!()
+    }
+}
+
+// (weak-map) -> map whose entries are dropped once their keys are collected
+pub fn weak_map(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.is_empty());
+    let ptr = Heap::alloc_object::<Map>();
+    unsafe { std::ptr::write(ptr, Map::new_weak()) };
+    let map = Val::from_ptr(Tag::Map, ptr as *mut u8);
+    Heap::register_weak(map);
+    (map, false)
+}
+
+// (gc) -> nil, after a full collection has been scheduled for the next instruction
+pub fn gc(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.is_empty());
+    Heap::request_collection();
+    (Val::nil(), false)
+}
+
+// (set-finalizer! object f) -> nil; f is called with object once it becomes unreachable
+pub fn set_finalizer(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    Heap::register_finalizer(args[0], Finalizer::Script(args[1]));
+    (Val::nil(), false)
+}
+
+// (dump-heap path) or (dump-heap path :dot) -> nil, after a heap dump has been scheduled for
+// the next instruction so that the stack is included. Without a format, paths ending in `.dot`
+// get Graphviz and anything else gets text. The path is a string, or named by a symbol.
+pub fn dump_heap(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1 || args.len() == 2);
+    let path = match args[0].get() {
+        Cases::Str(s) => std::path::PathBuf::from(s.as_str()),
+        Cases::Symbol(sym) => std::path::PathBuf::from(sym.name()),
+        _ => unimplemented!()
+    };
+    let format = match args.get(1).map(|arg| arg.get()) {
+        None => DumpFormat::for_path(&path),
+        Some(Cases::Symbol(sym)) if sym.name() == "dot" => DumpFormat::Dot,
+        Some(Cases::Symbol(sym)) if sym.name() == "text" => DumpFormat::Text,
+        _ => unimplemented!()
+    };
+    Heap::request_dump(path, format);
+    (Val::nil(), false)
+}
+
+// (equal? a b) -> t if the values are structurally equal, nil otherwise
+pub fn equal(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    (if args[0].equal(&args[1]) { Symbol::t() } else { Symbol::nil() }, false)
+}
+
+fn number(name: &str, val: Val) -> Number {
+    Number::of(val).unwrap_or_else(|| panic!("{} expects a number, got {:?}", name, val))
+}
+
+// (numerator q) -> the numerator of q in lowest terms; an integer is its own numerator
+pub fn numerator(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    match number("numerator", args[0]) {
+        Number::Ratio(ratio) => (Number::integer(ratio.numerator().clone()).to_val(), false),
+        n if n.is_integer() => (args[0], false),
+        n => panic!("numerator expects an exact number, got {}", n),
+    }
+}
+
+// (denominator q) -> the denominator of q in lowest terms, which is 1 for an integer
+pub fn denominator(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    match number("denominator", args[0]) {
+        Number::Ratio(ratio) => (Number::integer(ratio.denominator().clone()).to_val(), false),
+        n if n.is_integer() => (Val::from_int(1), false),
+        n => panic!("denominator expects an exact number, got {}", n),
+    }
+}
+
+// (float n) -> the float nearest to n
+pub fn float(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (Val::from_num(number("float", args[0]).to_f64()), false)
+}
+
+// The primitives as values, for passing to other functions. Calls written out in full compile
+// to opcodes instead, and these behave the same way.
+
+fn numbers(name: &str, args: &[Val]) -> Vec<Number> {
+    args.iter().map(|arg| number(name, *arg)).collect()
+}
+
+fn integers(name: &str, args: &[Val]) -> Vec<Number> {
+    args.iter().map(|arg| match Number::of(*arg) {
+        Some(n) if n.is_integer() => n,
+        _ => panic!("{} expects an integer, got {:?}", name, arg),
+    }).collect()
+}
+
+fn divide(name: &str, integers: &[Number], op: fn(&Number, &Number) -> Option<Number>) -> (Val, bool) {
+    match op(&integers[0], &integers[1]) {
+        Some(result) => (result.to_val(), false),
+        None => panic!("Division by zero in ({} {} {})", name, integers[0], integers[1]),
+    }
+}
+
+// Applies `op` left to right, starting from `unit` when there are fewer than two operands.
+fn fold(numbers: Vec<Number>, unit: i32, op: fn(&Number, &Number) -> Number) -> (Val, bool) {
+    let unit = Number::Int(unit as i64);
+    let result = match &numbers[..] {
+        [] => unit,
+        [only] => op(&unit, only),
+        [first, rest @ ..] => rest.iter().fold(first.clone(), |acc, n| op(&acc, n)),
+    };
+    (result.to_val(), false)
+}
+
+fn chain(name: &str, args: &[Val], holds: fn(Option<Ordering>) -> bool) -> (Val, bool) {
+    assert!(!args.is_empty());
+    let numbers = numbers(name, args);
+    let result = numbers.windows(2).all(|pair| holds(pair[0].compare(&pair[1])));
+    (if result { Symbol::t() } else { Symbol::nil() }, false)
+}
+
+// (+ n ...) -> the sum, or 0
+pub fn add(args: &[Val], global: &mut Global) -> (Val, bool) {
+    fold(numbers("+", args), 0, Number::add)
+}
+
+// (- n ...) -> n minus the rest, or n negated
+pub fn sub(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(!args.is_empty());
+    fold(numbers("-", args), 0, Number::sub)
+}
+
+// (* n ...) -> the product, or 1
+pub fn mul(args: &[Val], global: &mut Global) -> (Val, bool) {
+    fold(numbers("*", args), 1, Number::mul)
+}
+
+// (/ n ...) -> n divided by the rest, or 1/n
+pub fn div(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(!args.is_empty());
+    let quotient = |a: &Number, b: &Number| {
+        a.div(b).unwrap_or_else(|| panic!("Division by zero in (/ {} {})", a, b))
+    };
+    fold(numbers("/", args), 1, quotient)
+}
+
+// (quot a b) -> a divided by b, rounded towards zero
+pub fn quot(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    let integers = integers("quot", args);
+    divide("quot", &integers, Number::quot)
+}
+
+// (rem a b) -> the remainder of (quot a b), with the sign of a
+pub fn rem(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    let integers = integers("rem", args);
+    divide("rem", &integers, Number::rem)
+}
+
+// (mod a b) -> the remainder of a divided by b rounded down, with the sign of b
+pub fn modulo(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    let integers = integers("mod", args);
+    divide("mod", &integers, Number::modulo)
+}
+
+// (bit-and n ...) -> the bits set in every n, or -1
+pub fn bit_and(args: &[Val], global: &mut Global) -> (Val, bool) {
+    fold(integers("bit-and", args), -1, Number::bit_and)
+}
+
+// (bit-or n ...) -> the bits set in any n, or 0
+pub fn bit_or(args: &[Val], global: &mut Global) -> (Val, bool) {
+    fold(integers("bit-or", args), 0, Number::bit_or)
+}
+
+// (bit-xor n ...) -> the bits set in an odd number of the n, or 0
+pub fn bit_xor(args: &[Val], global: &mut Global) -> (Val, bool) {
+    fold(integers("bit-xor", args), 0, Number::bit_xor)
+}
+
+// (bit-not n) -> n with every bit flipped
+pub fn bit_not(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (integers("bit-not", args)[0].bit_not().to_val(), false)
+}
+
+// (bit-shift-left n bits) -> n times 2^bits
+pub fn shift_left(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    let integers = integers("bit-shift-left", args);
+    (integers[0].shift_left(&integers[1]).to_val(), false)
+}
+
+// (bit-shift-right n bits) -> n divided by 2^bits, rounded down
+pub fn shift_right(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    let integers = integers("bit-shift-right", args);
+    (integers[0].shift_right(&integers[1]).to_val(), false)
+}
+
+// (< n ...) -> t if every n is less than the next
+pub fn less(args: &[Val], global: &mut Global) -> (Val, bool) {
+    chain("<", args, |ordering| ordering == Some(Ordering::Less))
+}
+
+// (> n ...) -> t if every n is greater than the next
+pub fn greater(args: &[Val], global: &mut Global) -> (Val, bool) {
+    chain(">", args, |ordering| ordering == Some(Ordering::Greater))
+}
+
+// (<= n ...) -> t if no n is greater than the next
+pub fn less_or_equal(args: &[Val], global: &mut Global) -> (Val, bool) {
+    chain("<=", args, |ordering| matches!(ordering, Some(Ordering::Less | Ordering::Equal)))
+}
+
+// (>= n ...) -> t if no n is less than the next
+pub fn greater_or_equal(args: &[Val], global: &mut Global) -> (Val, bool) {
+    chain(">=", args, |ordering| matches!(ordering, Some(Ordering::Greater | Ordering::Equal)))
+}
+
+// (= n ...) -> t if every n is numerically equal to the next
+pub fn numeric_equal(args: &[Val], global: &mut Global) -> (Val, bool) {
+    chain("=", args, |ordering| ordering == Some(Ordering::Equal))
+}
+
+// (eq a ...) -> t if every value is identical to the next
+pub fn eq(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(!args.is_empty());
+    (if args.windows(2).all(|pair| pair[0] == pair[1]) { Symbol::t() } else { Symbol::nil() }, false)
+}
@@ -6,10 +6,15 @@ pub mod global;
 pub mod compiler;
 mod common;
 mod intrinsics;
+mod math;
+mod random;
 
 use std::ptr;
 
 pub use vm::Vm;
+pub use alloc::{AllocationProfile, DumpFormat, Handle, HandleScope, HeapConfig, HeapStats, OutOfMemory, Root, Site, Tally};
+#[doc(hidden)]
+pub use alloc::testing;
 use values::{Val, Tag, Closure};
 
 use crate::{bytecode::ByteCode, global::Global};
@@ -27,6 +32,7 @@ fn main() {
     ", &mut global).unwrap();
 
     let mut closure = values::Closure {
+        header: values::Header::new::<values::Closure>(),
         env: &[],
         code_obj: &func_obj
     };
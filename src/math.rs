@@ -0,0 +1,209 @@
+//! The math library.
+//!
+//! These follow the numeric tower: functions that can give an exact answer for exact arguments
+//! do, so `(sqrt 16)` is `4` and `(floor 7/2)` is `3`, while the transcendental functions always
+//! give floats. Arguments that are not numbers are an error, and so are numbers a function has
+//! no real value at, such as `(sqrt -1)` or `(log 0)`.
+
+use std::cmp::Ordering;
+use std::f64::consts;
+
+use crate::global::Global;
+use crate::values::{NativeFn, Number, Val};
+
+pub const INTRINSICS: &[(&str, NativeFn)] = &[
+    ("int", NativeFn(int)),
+    ("abs", NativeFn(abs)),
+    ("min", NativeFn(min)),
+    ("max", NativeFn(max)),
+    ("clamp", NativeFn(clamp)),
+    ("floor", NativeFn(floor)),
+    ("ceil", NativeFn(ceil)),
+    ("round", NativeFn(round)),
+    ("truncate", NativeFn(truncate)),
+    ("sqrt", NativeFn(sqrt)),
+    ("pow", NativeFn(pow)),
+    ("exp", NativeFn(exp)),
+    ("log", NativeFn(log)),
+    ("sin", NativeFn(sin)),
+    ("cos", NativeFn(cos)),
+    ("tan", NativeFn(tan)),
+    ("asin", NativeFn(asin)),
+    ("acos", NativeFn(acos)),
+    ("atan", NativeFn(atan)),
+];
+
+pub const CONSTANTS: &[(&str, f64)] = &[
+    ("pi", consts::PI),
+    ("tau", consts::TAU),
+    ("euler", consts::E),
+    ("infinity", f64::INFINITY),
+];
+
+fn number(name: &str, val: Val) -> Number {
+    Number::of(val).unwrap_or_else(|| panic!("{} expects a number, got {:?}", name, val))
+}
+
+// Applies a float function to the argument, whatever kind of number it is.
+fn float_fn(name: &str, args: &[Val], f: fn(f64) -> f64) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (Val::from_num(f(number(name, args[0]).to_f64())), false)
+}
+
+// Takes a number the function is defined for. A NaN is let through, since it already stands
+// for an undefined result.
+fn in_domain(name: &str, val: Val, defined: fn(f64) -> bool) -> Number {
+    let n = number(name, val);
+    let f = n.to_f64();
+    if !f.is_nan() && !defined(f) {
+        panic!("{} is undefined for {}", name, n);
+    }
+    n
+}
+
+/// (int n) -> n as an integer, rounded towards zero
+pub fn int(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (number("int", args[0]).to_integer().to_val(), false)
+}
+
+/// (abs n) -> the magnitude of n, of the same kind
+pub fn abs(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (number("abs", args[0]).abs().to_val(), false)
+}
+
+// Picks the argument that `wins` against every other, or a NaN if there is one.
+fn extreme(name: &str, args: &[Val], wins: Ordering) -> (Val, bool) {
+    assert!(!args.is_empty());
+    let mut best = (args[0], number(name, args[0]));
+    for arg in &args[1..] {
+        let n = number(name, *arg);
+        match n.compare(&best.1) {
+            Some(ordering) if ordering == wins => best = (*arg, n),
+            Some(_) => {}
+            None if n.to_f64().is_nan() => best = (*arg, n),
+            None => {}
+        }
+    }
+    (best.0, false)
+}
+
+/// (min n ...) -> the least n; NaN if any n is
+pub fn min(args: &[Val], global: &mut Global) -> (Val, bool) {
+    extreme("min", args, Ordering::Less)
+}
+
+/// (max n ...) -> the greatest n; NaN if any n is
+pub fn max(args: &[Val], global: &mut Global) -> (Val, bool) {
+    extreme("max", args, Ordering::Greater)
+}
+
+/// (clamp n lo hi) -> n if it is between lo and hi, otherwise the nearer of them
+pub fn clamp(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 3);
+    let (n, lo, hi) = (number("clamp", args[0]), number("clamp", args[1]), number("clamp", args[2]));
+    assert!(lo.compare(&hi).is_some_and(Ordering::is_le), "clamp expects lo <= hi, got {} and {}", lo, hi);
+    match (n.compare(&lo), n.compare(&hi)) {
+        (Some(Ordering::Less), _) => (args[1], false),
+        (_, Some(Ordering::Greater)) => (args[2], false),
+        _ => (args[0], false),
+    }
+}
+
+/// (floor n) -> the largest integer not above n
+pub fn floor(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (number("floor", args[0]).floor().to_val(), false)
+}
+
+/// (ceil n) -> the smallest integer not below n
+pub fn ceil(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (number("ceil", args[0]).ceil().to_val(), false)
+}
+
+/// (round n) -> the integer nearest to n, rounding ties to even
+pub fn round(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (number("round", args[0]).round().to_val(), false)
+}
+
+/// (truncate n) -> n rounded towards zero
+pub fn truncate(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (number("truncate", args[0]).truncate().to_val(), false)
+}
+
+/// (sqrt n) -> the square root of n, exact if n is the square of an exact number
+pub fn sqrt(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (in_domain("sqrt", args[0], |f| f >= 0.0).sqrt().to_val(), false)
+}
+
+/// (pow base exponent) -> base raised to exponent, exact for an exact base and integer exponent
+pub fn pow(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 2);
+    let (base, exponent) = (number("pow", args[0]), number("pow", args[1]));
+    match base.pow(&exponent) {
+        Some(result) => (result.to_val(), false),
+        None => panic!("Division by zero in (pow {} {})", base, exponent),
+    }
+}
+
+/// (exp n) -> e raised to n
+pub fn exp(args: &[Val], global: &mut Global) -> (Val, bool) {
+    float_fn("exp", args, f64::exp)
+}
+
+/// (log n) -> the natural logarithm of n
+/// (log n base) -> the logarithm of n in base
+pub fn log(args: &[Val], global: &mut Global) -> (Val, bool) {
+    let positive = |f: f64| f > 0.0;
+    match args {
+        [n] => (Val::from_num(in_domain("log", *n, positive).to_f64().ln()), false),
+        [n, base] => {
+            let n = in_domain("log", *n, positive).to_f64();
+            let base = in_domain("log", *base, |f| f > 0.0 && f != 1.0).to_f64();
+            (Val::from_num(n.log(base)), false)
+        }
+        _ => panic!("log expects 1 or 2 arguments, got {}", args.len()),
+    }
+}
+
+/// (sin x) -> the sine of x radians
+pub fn sin(args: &[Val], global: &mut Global) -> (Val, bool) {
+    float_fn("sin", args, f64::sin)
+}
+
+/// (cos x) -> the cosine of x radians
+pub fn cos(args: &[Val], global: &mut Global) -> (Val, bool) {
+    float_fn("cos", args, f64::cos)
+}
+
+/// (tan x) -> the tangent of x radians
+pub fn tan(args: &[Val], global: &mut Global) -> (Val, bool) {
+    float_fn("tan", args, f64::tan)
+}
+
+/// (asin x) -> the angle in radians whose sine is x
+pub fn asin(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (Val::from_num(in_domain("asin", args[0], |f| f.abs() <= 1.0).to_f64().asin()), false)
+}
+
+/// (acos x) -> the angle in radians whose cosine is x
+pub fn acos(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    (Val::from_num(in_domain("acos", args[0], |f| f.abs() <= 1.0).to_f64().acos()), false)
+}
+
+/// (atan x) -> the angle in radians whose tangent is x
+/// (atan y x) -> the angle in radians of the point (x, y)
+pub fn atan(args: &[Val], global: &mut Global) -> (Val, bool) {
+    match args {
+        [_] => float_fn("atan", args, f64::atan),
+        [y, x] => (Val::from_num(number("atan", *y).to_f64().atan2(number("atan", *x).to_f64())), false),
+        _ => panic!("atan expects 1 or 2 arguments, got {}", args.len()),
+    }
+}
@@ -0,0 +1,149 @@
+//! Pseudo-random numbers.
+//!
+//! Each `Global` has its own generator, seeded from the clock when it is created. Scripts can
+//! reseed it with `seed!` to draw the same numbers on every run. The generator is xoshiro256**,
+//! which is fast and passes the usual statistical tests, but is not for cryptography.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::global::Global;
+use crate::values::{Cases, NativeFn, Number, Val};
+
+pub const INTRINSICS: &[(&str, NativeFn)] = &[
+    ("rand", NativeFn(rand)),
+    ("rand-int", NativeFn(rand_int)),
+    ("rand-nth", NativeFn(rand_nth)),
+    ("shuffle!", NativeFn(shuffle)),
+    ("seed!", NativeFn(seed)),
+];
+
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // Spreads the seed over the whole state with SplitMix64, as xoshiro's authors suggest,
+        // so that similar seeds give unrelated streams and the state is never all zeros.
+        let mut seed = seed;
+        let mut next = || {
+            seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+        Rng { state: [next(), next(), next(), next()] }
+    }
+
+    pub fn from_entropy() -> Rng {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        Rng::new(now.as_nanos() as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    /// A float in `[0, 1)`, from the top 53 bits.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// An integer in `[0, bound)`, each equally likely. Panics if `bound` is zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert!(bound > 0);
+        // Draws that fall in the partial range at the top would favour the smaller results.
+        let zone = u64::MAX - (u64::MAX - bound + 1) % bound;
+        loop {
+            let n = self.next_u64();
+            if n <= zone {
+                return n % bound;
+            }
+        }
+    }
+}
+
+fn vector_len(name: &str, val: Val) -> usize {
+    match val.get() {
+        Cases::Vector(vector) => vector.len().get_int().unwrap() as usize,
+        _ => panic!("{} expects a vector, got {:?}", name, val),
+    }
+}
+
+/// (rand) -> a float between 0 inclusive and 1 exclusive
+pub fn rand(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.is_empty());
+    (Val::from_num(global.rng.next_f64()), false)
+}
+
+/// (rand-int n) -> an integer between 0 inclusive and n exclusive
+pub fn rand_int(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    match Number::of(args[0]) {
+        Some(Number::Int(n)) if n > 0 => (Number::Int(global.rng.below(n as u64) as i64).to_val(), false),
+        _ => panic!("rand-int expects a positive integer, got {:?}", args[0]),
+    }
+}
+
+/// (rand-nth v) -> an element of v chosen at random
+pub fn rand_nth(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    let len = vector_len("rand-nth", args[0]);
+    assert!(len > 0, "rand-nth of an empty vector");
+    let i = global.rng.below(len as u64) as usize;
+    match args[0].get() {
+        Cases::Vector(vector) => (vector.get(i).unwrap(), false),
+        _ => unreachable!(),
+    }
+}
+
+/// (shuffle! v) -> v, with its elements put in a random order
+pub fn shuffle(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    let len = vector_len("shuffle!", args[0]);
+    if let Cases::Vector(vector) = args[0].get() {
+        // Fisher-Yates, which makes every order equally likely.
+        for i in (1..len).rev() {
+            let j = global.rng.below(i as u64 + 1) as usize;
+            vector.swap(i, j);
+        }
+    }
+    (args[0], false)
+}
+
+/// (seed! n) -> nil, restarting the generator so the numbers after it are the same on every run
+pub fn seed(args: &[Val], global: &mut Global) -> (Val, bool) {
+    assert!(args.len() == 1);
+    match Number::of(args[0]) {
+        Some(Number::Int(n)) => global.seed_random(n as u64),
+        _ => panic!("seed! expects a 64-bit integer, got {:?}", args[0]),
+    }
+    (Val::nil(), false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bounded_draws_cover_the_range_evenly() {
+        let mut rng = Rng::new(7);
+        let mut counts = [0; 6];
+        for _ in 0..60000 {
+            counts[rng.below(6) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&count| (9000..11000).contains(&count)), "{:?}", counts);
+        assert_eq!(Rng::new(7).below(u64::MAX), Rng::new(7).below(u64::MAX));
+        assert!((0..1000).map(|_| rng.next_f64()).all(|f| (0.0..1.0).contains(&f)));
+    }
+}
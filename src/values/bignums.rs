@@ -0,0 +1,579 @@
+//! Integers too large for a fixnum.
+//!
+//! `BigInt` does the arithmetic, on a sign and a magnitude of 32-bit digits, least significant
+//! first. A `Bignum` is the heap object a `BigInt` is stored in once it becomes a value. Only
+//! integers outside the fixnum range are ever stored as bignums, so two integer values that are
+//! numerically equal are always of the same kind.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::Hasher;
+
+use crate::alloc::Heap;
+use super::{Header, Object, Tag, TypeDesc, Val};
+
+/// An integer of any size. Kept normalized: no leading zero digits, and zero is never negative.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+const BASE: u64 = 1 << 32;
+
+impl BigInt {
+    fn new(negative: bool, mut digits: Vec<u32>) -> BigInt {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        BigInt { negative: negative && !digits.is_empty(), digits }
+    }
+
+    pub fn zero() -> BigInt {
+        BigInt { negative: false, digits: Vec::new() }
+    }
+
+    pub fn from_i64(int: i64) -> BigInt {
+        let magnitude = int.unsigned_abs();
+        BigInt::new(int < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+
+    /// The integer a finite float with no fractional part is exactly equal to.
+    pub fn from_f64(float: f64) -> BigInt {
+        assert!(float.is_finite() && float.fract() == 0.0, "{} is not an integer", float);
+        let bits = float.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64 - 1075;
+        let mantissa = (bits & ((1 << 52) - 1)) | if exponent > -1075 { 1 << 52 } else { 0 };
+        let magnitude = if exponent < 0 {
+            BigInt::from_i64(mantissa.checked_shr(-exponent as u32).unwrap_or(0) as i64)
+        } else {
+            BigInt::from_i64(mantissa as i64).shl(exponent as usize)
+        };
+        BigInt { negative: float < 0.0 && !magnitude.digits.is_empty(), ..magnitude }
+    }
+
+    /// Parses an optionally signed string of decimal digits.
+    pub fn parse(src: &str) -> Option<BigInt> {
+        let (negative, digits) = match src.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, src),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut magnitude = Vec::new();
+        for chunk in digits.as_bytes().chunks(9) {
+            let value = chunk.iter().fold(0, |acc, b| acc * 10 + (b - b'0') as u32);
+            mul_add_small(&mut magnitude, 10u32.pow(chunk.len() as u32), value);
+        }
+        Some(BigInt::new(negative, magnitude))
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.digits.len() > 2 {
+            return None;
+        }
+        let magnitude = self.digits.iter().rev().fold(0u64, |acc, d| acc << 32 | *d as u64);
+        if self.negative {
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+
+    /// The nearest float, rounding ties to even.
+    pub fn to_f64(&self) -> f64 {
+        let bits = self.bit_len();
+        let magnitude = if bits <= 64 {
+            self.digits.iter().rev().fold(0u64, |acc, d| acc << 32 | *d as u64) as f64
+        } else {
+            // The top 64 bits round the same way as the whole number, as long as the bits
+            // below them still count towards rounding up.
+            let shift = bits - 64;
+            let top = self.shr(shift);
+            let mut top = top.digits.iter().rev().fold(0u64, |acc, d| acc << 32 | *d as u64);
+            if self.trailing_zeros() < shift {
+                top |= 1;
+            }
+            top as f64 * 2f64.powi(shift.min(i32::MAX as usize) as i32)
+        };
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_one(&self) -> bool {
+        !self.negative && self.digits == [1]
+    }
+
+    pub fn is_odd(&self) -> bool {
+        self.digits.first().is_some_and(|d| d & 1 == 1)
+    }
+
+    pub fn neg(&self) -> BigInt {
+        BigInt::new(!self.negative, self.digits.clone())
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::new(false, self.digits.clone())
+    }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitudes(&self.digits, &other.digits));
+        }
+        match compare_magnitudes(&self.digits, &other.digits) {
+            Ordering::Less => BigInt::new(other.negative, sub_magnitudes(&other.digits, &self.digits)),
+            _ => BigInt::new(self.negative, sub_magnitudes(&self.digits, &other.digits)),
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        let mut product = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, a) in self.digits.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.digits.iter().enumerate() {
+                let t = *a as u64 * *b as u64 + product[i + j] as u64 + carry;
+                product[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            product[i + other.digits.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, product)
+    }
+
+    /// The quotient rounded towards zero, and the remainder, which takes the sign of `self`.
+    /// Panics if `other` is zero.
+    pub fn div_rem(&self, other: &BigInt) -> (BigInt, BigInt) {
+        assert!(!other.is_zero(), "Division by zero");
+        let (quotient, remainder) = div_rem_magnitudes(&self.digits, &other.digits);
+        (BigInt::new(self.negative != other.negative, quotient), BigInt::new(self.negative, remainder))
+    }
+
+    /// Bitwise operations act as if on infinitely sign-extended two's complement.
+    pub fn bit_and(&self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a & b)
+    }
+
+    pub fn bit_or(&self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a | b)
+    }
+
+    pub fn bit_xor(&self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a ^ b)
+    }
+
+    pub fn bit_not(&self) -> BigInt {
+        self.neg().sub(&BigInt::from_i64(1))
+    }
+
+    fn bitwise(&self, other: &BigInt, op: fn(u32, u32) -> u32) -> BigInt {
+        // One digit more than either needs leaves room for the sign.
+        let len = self.digits.len().max(other.digits.len()) + 1;
+        let (a, b) = (self.twos_complement(len), other.twos_complement(len));
+        let digits: Vec<u32> = a.iter().zip(&b).map(|(x, y)| op(*x, *y)).collect();
+        if digits[len - 1] >> 31 == 0 {
+            return BigInt::new(false, digits);
+        }
+        BigInt::new(true, negate_digits(&digits))
+    }
+
+    fn twos_complement(&self, len: usize) -> Vec<u32> {
+        let mut digits = self.digits.clone();
+        digits.resize(len, 0);
+        if self.negative { negate_digits(&digits) } else { digits }
+    }
+
+    /// Multiplies by `2^bits`, or divides by `2^-bits` rounding down when `bits` is negative.
+    pub fn shift(&self, bits: i64) -> BigInt {
+        if bits >= 0 {
+            return self.shl(bits as usize);
+        }
+        let bits = bits.unsigned_abs() as usize;
+        if !self.negative {
+            return self.shr(bits);
+        }
+        // Rounding down a negative quotient rounds its magnitude up.
+        let one = BigInt::from_i64(1);
+        self.abs().sub(&one).shr(bits).add(&one).neg()
+    }
+
+    /// The largest integer whose square is at most `self`. Panics if `self` is negative.
+    pub fn sqrt(&self) -> BigInt {
+        assert!(!self.negative, "Square root of a negative integer");
+        if self.is_zero() {
+            return BigInt::zero();
+        }
+        // Newton's method, from a power of two above the root, decreases until it reaches it.
+        let mut x = BigInt::from_i64(1).shl(self.bit_len().div_ceil(2));
+        loop {
+            let next = x.add(&self.div_rem(&x).0).shr(1);
+            if next >= x {
+                return x;
+            }
+            x = next;
+        }
+    }
+
+    /// The greatest common divisor of the two magnitudes, or zero if both are zero.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while !b.is_zero() {
+            let remainder = a.div_rem(&b).1;
+            a = std::mem::replace(&mut b, remainder);
+        }
+        a
+    }
+
+    /// The number of bits in the magnitude.
+    pub fn bit_len(&self) -> usize {
+        match self.digits.last() {
+            Some(top) => self.digits.len() * 32 - top.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn trailing_zeros(&self) -> usize {
+        let zeros = self.digits.iter().take_while(|d| **d == 0).count();
+        zeros * 32 + self.digits.get(zeros).map_or(0, |d| d.trailing_zeros() as usize)
+    }
+
+    /// Shifts the magnitude left, keeping the sign.
+    pub fn shl(&self, bits: usize) -> BigInt {
+        let mut digits = vec![0; bits / 32];
+        digits.extend(shl_digits(&self.digits, (bits % 32) as u32));
+        BigInt::new(self.negative, digits)
+    }
+
+    /// Shifts the magnitude right, dropping the bits shifted out.
+    pub fn shr(&self, bits: usize) -> BigInt {
+        let digits = self.digits.get(bits / 32..).unwrap_or(&[]);
+        BigInt::new(self.negative, shr_digits(digits, (bits % 32) as u32))
+    }
+
+    pub fn hash_into(&self, state: &mut dyn Hasher) {
+        state.write_u8(self.negative as u8);
+        for digit in &self.digits {
+            state.write_u32(*digit);
+        }
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.digits, &other.digits),
+            (true, true) => compare_magnitudes(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return f.write_str("0");
+        }
+        // Peel off nine decimal digits at a time, least significant first.
+        let mut chunks = Vec::new();
+        let mut rest = self.digits.clone();
+        while !rest.is_empty() {
+            chunks.push(div_rem_small(&mut rest, 1_000_000_000));
+        }
+        if self.negative {
+            f.write_str("-")?;
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, digit) in long.iter().enumerate() {
+        let t = *digit as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push(t as u32);
+        carry = t >> 32;
+    }
+    sum.push(carry as u32);
+    sum
+}
+
+// Requires a >= b.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, digit) in a.iter().enumerate() {
+        let mut t = *digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = (t < 0) as i64;
+        if t < 0 {
+            t += BASE as i64;
+        }
+        difference.push(t as u32);
+    }
+    difference
+}
+
+// The two's complement negation of a fixed number of digits.
+fn negate_digits(digits: &[u32]) -> Vec<u32> {
+    let mut carry = 1u64;
+    digits.iter().map(|d| {
+        let t = !*d as u64 + carry;
+        carry = t >> 32;
+        t as u32
+    }).collect()
+}
+
+fn mul_add_small(digits: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for digit in digits.iter_mut() {
+        let t = *digit as u64 * factor as u64 + carry;
+        *digit = t as u32;
+        carry = t >> 32;
+    }
+    if carry != 0 {
+        digits.push(carry as u32);
+    }
+}
+
+// Divides in place, returning the remainder.
+fn div_rem_small(digits: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for digit in digits.iter_mut().rev() {
+        let t = remainder << 32 | *digit as u64;
+        *digit = (t / divisor as u64) as u32;
+        remainder = t % divisor as u64;
+    }
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    remainder as u32
+}
+
+fn shl_digits(digits: &[u32], bits: u32) -> Vec<u32> {
+    if bits == 0 {
+        return digits.to_vec();
+    }
+    let mut shifted = Vec::with_capacity(digits.len() + 1);
+    let mut carry = 0;
+    for digit in digits {
+        shifted.push(digit << bits | carry);
+        carry = digit >> (32 - bits);
+    }
+    shifted.push(carry);
+    shifted
+}
+
+fn shr_digits(digits: &[u32], bits: u32) -> Vec<u32> {
+    if bits == 0 {
+        return digits.to_vec();
+    }
+    (0..digits.len())
+        .map(|i| digits[i] >> bits | digits.get(i + 1).map_or(0, |next| next << (32 - bits)))
+        .collect()
+}
+
+// Long division, as in Knuth's Algorithm D.
+fn div_rem_magnitudes(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if compare_magnitudes(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let mut quotient = a.to_vec();
+        let remainder = div_rem_small(&mut quotient, b[0]);
+        return (quotient, vec![remainder]);
+    }
+    // Shift both so the divisor's top digit has its high bit set, which keeps each estimate
+    // of a quotient digit within two of the real one.
+    let shift = b.last().unwrap().leading_zeros();
+    let v = shl_digits(b, shift);
+    let v = &v[..b.len()];
+    let mut u = shl_digits(a, shift);
+    if u.len() == a.len() {
+        u.push(0);
+    }
+    let n = v.len();
+    let m = a.len() - n;
+    let mut quotient = vec![0u32; m + 1];
+    for j in (0..=m).rev() {
+        let top = (u[j + n] as u64) << 32 | u[j + n - 1] as u64;
+        let mut qhat = top / v[n - 1] as u64;
+        let mut rhat = top % v[n - 1] as u64;
+        while qhat >= BASE || qhat * v[n - 2] as u64 > (rhat << 32 | u[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += v[n - 1] as u64;
+            if rhat >= BASE {
+                break;
+            }
+        }
+        // Subtract qhat times the divisor from the current window of u.
+        let mut borrow = 0i64;
+        for i in 0..n {
+            let product = qhat * v[i] as u64;
+            let t = u[i + j] as i64 - borrow - (product & 0xffff_ffff) as i64;
+            u[i + j] = t as u32;
+            borrow = (product >> 32) as i64 - (t >> 32);
+        }
+        let t = u[j + n] as i64 - borrow;
+        u[j + n] = t as u32;
+        // qhat was one too large, so add the divisor back.
+        if t < 0 {
+            qhat -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let t = u[i + j] as u64 + v[i] as u64 + carry;
+                u[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            u[j + n] = u[j + n].wrapping_add(carry as u32);
+        }
+        quotient[j] = qhat as u32;
+    }
+    (quotient, shr_digits(&u[..n], shift))
+}
+
+/// A `BigInt` on the heap. Immutable, with its digits in a buffer it owns.
+#[repr(C)]
+pub struct Bignum {
+    header: Header,
+    negative: bool,
+    digits: *const [u32],
+}
+
+impl Bignum {
+    /// Allocates a bignum holding `int`.
+    pub fn alloc(int: &BigInt) -> Val {
+        let digits = Heap::alloc(size_of_val(int.digits.as_slice())) as *mut u32;
+        let digits = unsafe {
+            std::ptr::copy_nonoverlapping(int.digits.as_ptr(), digits, int.digits.len());
+            std::ptr::slice_from_raw_parts(digits as *const u32, int.digits.len())
+        };
+        let ptr = Heap::alloc_object::<Bignum>();
+        unsafe { std::ptr::write(ptr, Bignum { header: Header::new::<Bignum>(), negative: int.negative, digits }) };
+        Val::from_ptr(Tag::Object, ptr as *mut u8)
+    }
+
+    pub fn to_bigint(&self) -> BigInt {
+        BigInt { negative: self.negative, digits: unsafe { (*self.digits).to_vec() } }
+    }
+}
+
+static BIGNUM: TypeDesc = TypeDesc::of::<Bignum>("bignum", Tag::Object);
+
+unsafe impl Object for Bignum {
+    fn desc() -> &'static TypeDesc {
+        &BIGNUM
+    }
+
+    fn buffers(&self, mark: &mut dyn FnMut(*const u8)) {
+        mark(self.digits as *const u8);
+    }
+
+    fn print(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_bigint())
+    }
+
+    fn equals(&self, other: &Bignum) -> bool {
+        self.negative == other.negative && unsafe { *self.digits == *other.digits }
+    }
+
+    fn hash(&self, state: &mut dyn Hasher) {
+        self.to_bigint().hash_into(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn big(src: &str) -> BigInt {
+        BigInt::parse(src).unwrap()
+    }
+
+    #[test]
+    fn arithmetic_carries_across_digits() {
+        let max = BigInt::from_i64(i64::MAX);
+        assert_eq!(max.add(&BigInt::from_i64(1)), big("9223372036854775808"));
+        assert_eq!(max.mul(&max), big("85070591730234615847396907784232501249"));
+        assert_eq!(big("-18446744073709551616").add(&big("18446744073709551615")), BigInt::from_i64(-1));
+        assert_eq!(big("100000000000000000000").sub(&big("100000000000000000000")), BigInt::zero());
+        assert_eq!(BigInt::from_i64(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(max.add(&BigInt::from_i64(1)).to_i64(), None);
+    }
+
+    #[test]
+    fn bitwise_operations_sign_extend() {
+        let a = big("-18446744073709551617");
+        assert_eq!(a.bit_and(&BigInt::from_i64(-1)), a);
+        assert_eq!(a.bit_and(&big("18446744073709551615")), big("18446744073709551615"));
+        assert_eq!(a.bit_or(&BigInt::from_i64(1)), a);
+        assert_eq!(a.bit_xor(&a), BigInt::zero());
+        assert_eq!(a.bit_not(), big("18446744073709551616"));
+        assert_eq!(BigInt::from_i64(-6).bit_and(&BigInt::from_i64(5)), BigInt::from_i64(-6 & 5));
+        assert_eq!(BigInt::from_i64(-6).bit_xor(&BigInt::from_i64(3)), BigInt::from_i64(-6 ^ 3));
+        assert_eq!(BigInt::from_i64(-5).shift(-1), BigInt::from_i64(-3));
+        assert_eq!(BigInt::from_i64(-4).shift(-1), BigInt::from_i64(-2));
+        assert_eq!(BigInt::from_i64(-1).shift(-100), BigInt::from_i64(-1));
+        assert_eq!(BigInt::from_i64(3).shift(64), big("55340232221128654848"));
+    }
+
+    #[test]
+    fn square_roots_round_down() {
+        for n in [0, 1, 2, 3, 4, 15, 16, 17, 1 << 40, (1 << 40) - 1] {
+            let root = BigInt::from_i64(n).sqrt();
+            assert_eq!(root, BigInt::from_i64(n.isqrt()), "sqrt {}", n);
+        }
+        let square = big("123456789012345678901234567890").mul(&big("123456789012345678901234567890"));
+        assert_eq!(square.sqrt(), big("123456789012345678901234567890"));
+        assert_eq!(square.sub(&BigInt::from_i64(1)).sqrt(), big("123456789012345678901234567889"));
+    }
+
+    #[test]
+    fn division_truncates() {
+        let (q, r) = big("85070591730234615847396907784232501250").div_rem(&BigInt::from_i64(i64::MAX));
+        assert_eq!((q, r), (BigInt::from_i64(i64::MAX), BigInt::from_i64(1)));
+        let (q, r) = big("-340282366920938463463374607431768211457").div_rem(&big("18446744073709551616"));
+        assert_eq!((q, r), (big("-18446744073709551616"), BigInt::from_i64(-1)));
+        let (q, r) = big("7").div_rem(&big("-100000000000000000000"));
+        assert_eq!((q, r), (BigInt::zero(), big("7")));
+    }
+
+    #[test]
+    fn converts_to_and_from_text_and_floats() {
+        let src = "-123456789012345678901234567890";
+        assert_eq!(big(src).to_string(), src);
+        assert_eq!(big("1000000000000000000000").to_string(), "1000000000000000000000");
+        assert_eq!(BigInt::parse("12a"), None);
+        assert_eq!(big("9007199254740993").to_f64(), 9007199254740992.0);
+        assert_eq!(big("9007199254740995").to_f64(), 9007199254740996.0);
+        assert_eq!(BigInt::from_f64(1e30).to_f64(), 1e30);
+        assert_eq!(BigInt::from_f64(-1e30).to_string(), "-1000000000000000019884624838656");
+        assert_eq!(BigInt::from_f64(-0.0), BigInt::zero());
+    }
+}
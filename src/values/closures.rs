@@ -1,7 +1,9 @@
 use crate::{bytecode::ByteCode, values::Tag};
-use super::Val;
+use super::{Header, Object, TypeDesc, Val};
 
+#[repr(C)]
 pub struct Closure {
+    pub header: Header,
     pub env: *const [Val],
     pub code_obj: *const ByteCode
 }
@@ -9,8 +11,31 @@ pub struct Closure {
 impl Closure {
     pub fn new(env: *const [Val], code_obj: *const ByteCode) -> Val {
         use crate::alloc::Heap;
-        let mut closure = Heap::new::<Closure>();
-        unsafe { std::ptr::write(closure, Closure { env, code_obj }) };
+        let mut closure = Heap::alloc_object::<Closure>();
+        unsafe { std::ptr::write(closure, Closure { header: Header::new::<Closure>(), env, code_obj }) };
         Val::from_ptr(Tag::Function, closure as *mut u8)
     }
+}
+
+static CLOSURE: TypeDesc = TypeDesc::of::<Closure>("fn", Tag::Function);
+
+unsafe impl Object for Closure {
+    fn desc() -> &'static TypeDesc {
+        &CLOSURE
+    }
+
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut Val)) {
+        for slot in unsafe { &mut *(self.env as *mut [Val]) } {
+            visit(slot);
+        }
+        let mut code_obj = Val::from_ptr(Tag::Object, self.code_obj as *mut u8);
+        visit(&mut code_obj);
+        self.code_obj = code_obj.ptr() as *const ByteCode;
+    }
+
+    fn buffers(&self, mark: &mut dyn FnMut(*const u8)) {
+        if !self.env.is_empty() {
+            mark(self.env as *const u8);
+        }
+    }
 }
\ No newline at end of file
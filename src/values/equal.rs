@@ -0,0 +1,178 @@
+//! Structural equality, as used by `equal?` and by map keys.
+//!
+//! Vectors are equal when their items are, pairwise, and maps when they hold equal values under
+//! equal keys. Objects with a descriptor compare through it, and everything else by identity.
+//! Numbers are equal when `=` says so, except that NaN is equal to itself, so `1` and `1.0`
+//! are the same map key and so is every NaN.
+//!
+//! Both equality and hashing terminate on cyclic values. A comparison that comes back to a pair
+//! it is already comparing assumes that pair is equal, and hashing stops at a fixed depth.
+
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use super::{Cases, Number, Tag, Val};
+
+// How deep hashing looks into nested collections. Leaving out what lies below never
+// separates equal values, and keeps the hash of a cyclic value finite.
+const HASH_DEPTH: usize = 4;
+
+impl Val {
+    /// Whether the two values are structurally equal. Agrees with `hash_equal`.
+    pub fn equal(&self, other: &Val) -> bool {
+        self.bits() == other.bits() || Comparison::default().equal(*self, *other)
+    }
+
+    /// Hashes the value so that values that are `equal` hash alike.
+    pub fn hash_equal(&self, state: &mut dyn Hasher) {
+        hash(*self, state, HASH_DEPTH)
+    }
+}
+
+#[derive(Default)]
+struct Comparison {
+    // Pairs of collections being compared further up the stack.
+    assumed: HashSet<(usize, usize)>,
+}
+
+impl Comparison {
+    fn equal(&mut self, a: Val, b: Val) -> bool {
+        if a.bits() == b.bits() {
+            return true;
+        }
+        if let (Some(x), Some(y)) = (Number::of(a), Number::of(b)) {
+            return x.compare(&y) == Some(std::cmp::Ordering::Equal);
+        }
+        if !a.is_ptr() || !b.is_ptr() {
+            return false;
+        }
+        match (a.get(), b.get()) {
+            (Cases::Vector(x), Cases::Vector(y)) => {
+                if x.len() != y.len() {
+                    return false;
+                }
+                if !self.assumed.insert((a.bits(), b.bits())) {
+                    return true;
+                }
+                x.iter().zip(y.iter()).all(|(p, q)| self.equal(p, q))
+            }
+            (Cases::Map(x), Cases::Map(y)) => {
+                if x.len() != y.len() {
+                    return false;
+                }
+                if !self.assumed.insert((a.bits(), b.bits())) {
+                    return true;
+                }
+                x.iter().all(|(k, v)| y.lookup(k).is_some_and(|w| self.equal(v, w)))
+            }
+            // Objects of the same type compare through their descriptor.
+            _ => match (a.header(), b.header()) {
+                (Some(x), Some(y)) if std::ptr::eq(x.desc, y.desc) => unsafe { (x.desc.eq)(a.ptr(), b.ptr()) },
+                _ => false,
+            },
+        }
+    }
+}
+
+fn hash(val: Val, state: &mut dyn Hasher, depth: usize) {
+    if let Some(num) = Number::of(val) {
+        return num.hash_into(state);
+    }
+    match val.get() {
+        Cases::Vector(v) => {
+            state.write_u8(Tag::Vector as u8);
+            v.len().hash_equal(state);
+            if depth > 0 {
+                for item in v.iter() {
+                    hash(item, state, depth - 1);
+                }
+            }
+        }
+        Cases::Map(m) => {
+            state.write_u8(Tag::Map as u8);
+            state.write_usize(m.len());
+            if depth > 0 {
+                // Equal maps may hold their entries in different orders, so the entries' hashes
+                // are combined in a way that does not depend on it.
+                let mut entries = 0u64;
+                for (k, v) in m.iter() {
+                    let mut entry = DefaultHasher::new();
+                    hash(k, &mut entry, depth - 1);
+                    hash(v, &mut entry, depth - 1);
+                    entries = entries.wrapping_add(entry.finish());
+                }
+                state.write_u64(entries);
+            }
+        }
+        _ => match val.header() {
+            Some(header) => unsafe { (header.desc.hash)(val.ptr(), state) },
+            _ => val.hash(&mut &mut *state),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::alloc::Heap;
+    use crate::values::{Map, Symbol, Vector};
+
+    fn vector(items: &[Val]) -> Val {
+        let ptr = Heap::alloc_object::<Vector>();
+        unsafe { std::ptr::write(ptr, Vector::new()) };
+        let val = Val::from_ptr(Tag::Vector, ptr as *mut u8);
+        for item in items {
+            unsafe { (*ptr).push(*item) };
+        }
+        val
+    }
+
+    fn map(entries: &[(Val, Val)]) -> Val {
+        let ptr = Heap::alloc_object::<Map>();
+        unsafe { std::ptr::write(ptr, Map::new()) };
+        for (k, v) in entries {
+            unsafe { (*ptr).insert(*k, *v) };
+        }
+        Val::from_ptr(Tag::Map, ptr as *mut u8)
+    }
+
+    fn hash_of(val: Val) -> u64 {
+        let mut state = DefaultHasher::new();
+        val.hash_equal(&mut state);
+        state.finish()
+    }
+
+    #[test]
+    fn collections_compare_by_contents() {
+        let int = Val::from_int;
+        let (a, b) = (vector(&[int(1), int(2)]), vector(&[int(1), int(2)]));
+        assert!(a != b && a.equal(&b));
+        assert_eq!(hash_of(a), hash_of(b));
+        assert!(!a.equal(&vector(&[int(1)])));
+        assert!(!a.equal(&vector(&[int(1), Val::from_num(2.5)])));
+        assert!(a.equal(&vector(&[int(1), Val::from_num(2.0)])));
+        assert_eq!(hash_of(a), hash_of(vector(&[Val::from_num(1.0), int(2)])));
+        assert!(Val::from_num(f64::NAN).equal(&Val::from_num(-f64::NAN)));
+
+        // Entries are compared regardless of the order they were added in.
+        let (k, nil) = (Symbol::t(), Symbol::nil());
+        let x = map(&[(k, a), (int(0), nil)]);
+        let y = map(&[(int(0), nil), (k, b)]);
+        assert!(x.equal(&y));
+        assert_eq!(hash_of(x), hash_of(y));
+        // A key mapped to nil is not the same as a missing one.
+        assert!(!map(&[(int(0), nil), (int(1), int(1))]).equal(&map(&[(int(2), nil), (int(1), int(1))])));
+    }
+
+    #[test]
+    fn cyclic_values_terminate() {
+        let (a, b) = (vector(&[]), vector(&[]));
+        if let (Cases::Vector(x), Cases::Vector(y)) = (a.get(), b.get()) {
+            x.push(a);
+            y.push(b);
+        }
+        assert!(a.equal(&b));
+        assert_eq!(hash_of(a), hash_of(b));
+    }
+}
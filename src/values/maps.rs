@@ -1,38 +1,125 @@
 use crate::alloc::Heap;
 use crate::values::*;
 
+use std::cell::Cell;
+use std::hash::{Hash, Hasher};
+
 use hashbrown::DefaultHashBuilder;
 use hashbrown::HashMap;
 
 const SMALL_MAP_MAX: usize = 31;
 
-pub enum Map {
-    SmallMap { len: usize, items: [(Val, Val); SMALL_MAP_MAX] },
-    HashMap (HashMap<Val, Val, DefaultHashBuilder, Heap>)
+/// Keys are compared structurally, so a vector can look up an entry added under an equal one.
+/// Changing a collection while it is used as a key leaves its entry unreachable. Weak maps are
+/// the exception: they key by identity, since an entry lives only as long as its own key does.
+#[repr(C)]
+pub struct Map {
+    header: Header,
+    repr: Repr,
+    // Set when a collection moves a key of the hashed representation, whose hash may depend on
+    // where its contents live. Lookups scan the table until the next update rehashes it.
+    stale: bool,
 }
 
-impl Map {
-    pub fn new() -> Map {
-        Map::SmallMap {
+enum Repr {
+    Small { len: usize, items: [(Val, Val); SMALL_MAP_MAX] },
+    Hashed(HashMap<Key, Val, DefaultHashBuilder, Heap>),
+    // Holds its keys weakly; an entry is dropped once its key is collected.
+    Weak(HashMap<Key, Val, DefaultHashBuilder, Heap>),
+}
+
+// A key of the hashed representation. It sits in a cell so that a collection can forward it
+// without moving the entry.
+struct Key {
+    val: Cell<Val>,
+    identity: bool,
+}
+
+impl Key {
+    fn new(val: Val) -> Key {
+        Key { val: Cell::new(val), identity: false }
+    }
+
+    fn weak(val: Val) -> Key {
+        Key { val: Cell::new(val), identity: true }
+    }
+
+    fn get(&self) -> Val {
+        self.val.get()
+    }
+
+    fn matches(&self, val: Val) -> bool {
+        if self.identity { self.get() == val } else { self.get().equal(&val) }
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Key) -> bool {
+        other.matches(self.get())
+    }
+}
+
+impl Eq for Key {}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if self.identity { self.get().hash(state) } else { self.get().hash_equal(state) }
+    }
+}
+
+impl Repr {
+    fn small() -> Repr {
+        Repr::Small {
             len: 0,
             items: [(Symbol::nil(), Symbol::nil()); SMALL_MAP_MAX]
         }
     }
 
+    fn weak() -> Repr {
+        Repr::Weak(HashMap::new_in(Heap))
+    }
+}
+
+impl Map {
+    pub fn new() -> Map {
+        Map { header: Header::new::<Map>(), repr: Repr::small(), stale: false }
+    }
+
+    pub fn new_weak() -> Map {
+        Map { header: Header::new::<Map>(), repr: Repr::weak(), stale: false }
+    }
+
+    pub fn is_weak(&self) -> bool {
+        matches!(self.repr, Repr::Weak(..))
+    }
+
+    // Reinserts every entry of a stale table under its current hash.
+    fn rehash(&mut self) {
+        if let Repr::Hashed(hashmap) | Repr::Weak(hashmap) = &mut self.repr && self.stale {
+            let items: Vec<_> = hashmap.drain().collect();
+            for (k, v) in items {
+                hashmap.insert(k, v);
+            }
+        }
+        self.stale = false;
+    }
+
     pub fn insert(&mut self, key: Val, value: Val) -> Val {
-        match self {
-            Map::SmallMap { len, items } if *len == SMALL_MAP_MAX => {
+        self.rehash();
+        match &mut self.repr {
+            Repr::Small { len, items } if *len == SMALL_MAP_MAX
+                && !items.iter().any(|(k, _)| key.equal(k)) => {
                 let mut hashmap = HashMap::new_in(Heap);
                 for i in 0..SMALL_MAP_MAX {
                     let (k, v) = items[i];
-                    hashmap.insert(k, v);
+                    hashmap.insert(Key::new(k), v);
                 }
-                let old_value = hashmap.insert(key, value);
-                *self = Map::HashMap(hashmap);
-                old_value.unwrap_or(Val::nil())
+                hashmap.insert(Key::new(key), value);
+                self.repr = Repr::Hashed(hashmap);
+                Val::nil()
             }
-            Map::SmallMap { len, items } => {
-                if let Some(i) = items.iter().take(*len).position(|(k, v)| key == *k) {
+            Repr::Small { len, items } => {
+                if let Some(i) = items.iter().take(*len).position(|(k, v)| key.equal(k)) {
                     let old_value = items[i];
                     items[i] = (key, value);
                     old_value.1
@@ -42,31 +129,42 @@ impl Map {
                     Val::nil()
                 }
             }
-            Map::HashMap(hashmap) => {
-                hashmap.insert(key, value).unwrap_or(Val::nil())
+            Repr::Hashed(hashmap) => {
+                hashmap.insert(Key::new(key), value).unwrap_or(Val::nil())
+            }
+            Repr::Weak(hashmap) => {
+                hashmap.insert(Key::weak(key), value).unwrap_or(Val::nil())
             }
         }
     }
 
     pub fn get(&self, key: Val) -> Val {
-        match self {
-            Map::SmallMap { len, items } => {
-                if let Some(i) = items.iter().take(*len).position(|(k, v)| key == *k) {
-                    items[i].1
-                } else {
-                    Symbol::nil()
-                }
+        self.lookup(key).unwrap_or(Symbol::nil())
+    }
+
+    /// The value under `key`, telling a missing key apart from one mapped to nil.
+    pub fn lookup(&self, key: Val) -> Option<Val> {
+        match &self.repr {
+            Repr::Small { len, items } => {
+                items.iter().take(*len).find(|(k, _)| key.equal(k)).map(|(_, v)| *v)
+            }
+            Repr::Hashed(hashmap) | Repr::Weak(hashmap) if self.stale => {
+                hashmap.iter().find(|(k, _)| k.matches(key)).map(|(_, v)| *v)
+            }
+            Repr::Hashed(hashmap) => {
+                hashmap.get(&Key::new(key)).copied()
+            }
+            Repr::Weak(hashmap) => {
+                hashmap.get(&Key::weak(key)).copied()
             }
-            Map::HashMap(hashmap) => {
-                *hashmap.get(&key).unwrap_or(&Symbol::nil())
-            }           
         }
     }
 
     pub fn remove(&mut self, key: Val) -> Val {
-        match self {
-            Map::SmallMap { len, items } => {
-                if let Some(i) = items.iter().take(*len).position(|(k, v)| key == *k) {
+        self.rehash();
+        match &mut self.repr {
+            Repr::Small { len, items } => {
+                if let Some(i) = items.iter().take(*len).position(|(k, v)| key.equal(k)) {
                     let deleted = items[i].1;
                     if i == *len - 1 {
                         items[i] = (Symbol::nil(), Symbol::nil());
@@ -80,35 +178,108 @@ impl Map {
                     Symbol::nil()
                 }
             }
-            Map::HashMap(hashmap) => {
-                hashmap.remove(&key).unwrap_or(Symbol::nil())
+            Repr::Hashed(hashmap) => {
+                let deleted = hashmap.remove(&Key::new(key)).unwrap_or(Symbol::nil());
+                // An empty table gives the collector no way to find its storage, so drop it.
+                if hashmap.is_empty() {
+                    self.repr = Repr::small();
+                }
+                deleted
+            }
+            Repr::Weak(hashmap) => {
+                let deleted = hashmap.remove(&Key::weak(key)).unwrap_or(Symbol::nil());
+                if hashmap.is_empty() {
+                    hashmap.shrink_to_fit();
+                }
+                deleted
             }           
         }
     }
 
     pub fn len(&self) -> usize {
-        match self {
-            Map::SmallMap { len, ..} => { *len }
-            Map::HashMap(hashmap) => { hashmap.len() }
+        match &self.repr {
+            Repr::Small { len, ..} => { *len }
+            Repr::Hashed(hashmap) | Repr::Weak(hashmap) => { hashmap.len() }
         }
     }
 
     pub fn clear(&mut self) {
-        *self = Map::new();
+        self.repr = if self.is_weak() { Repr::weak() } else { Repr::small() };
+        self.stale = false;
+    }
+
+    /// Drops every entry whose key `live` rejects, releasing the table once it is empty.
+    pub fn retain_keys(&mut self, live: &mut dyn FnMut(Val) -> bool) {
+        if let Repr::Weak(hashmap) = &mut self.repr {
+            hashmap.retain(|k, _| live(k.get()));
+            if hashmap.is_empty() {
+                hashmap.shrink_to_fit();
+            }
+        }
     }
 
     pub fn iter(&self) -> Box<dyn Iterator<Item=(Val, Val)> + '_> {
-        match self {
-            Map::SmallMap { len, items } => {
+        match &self.repr {
+            Repr::Small { len, items } => {
                 Box::new(items.iter().take(*len).map(|(k, v)| (*k, *v)))
             }
-            Map::HashMap(hashmap) => {
-                Box::new(hashmap.iter().take(hashmap.len()).map(|(k, v)| (*k, *v)))
+            Repr::Hashed(hashmap) | Repr::Weak(hashmap) => {
+                Box::new(hashmap.iter().take(hashmap.len()).map(|(k, v)| (k.get(), *v)))
             }             
         }
     }
 }
 
+static MAP: TypeDesc = TypeDesc::of::<Map>("map", Tag::Map);
+
+unsafe impl Object for Map {
+    fn desc() -> &'static TypeDesc {
+        &MAP
+    }
+
+    // Keys of the hashed representation may hash by identity, so if the visitor moves one of
+    // them the table goes stale. It cannot be rehashed here, since in the middle of a
+    // collection the contents of a key may not have been moved yet.
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut Val)) {
+        match &mut self.repr {
+            Repr::Small { len, items } => {
+                for (k, v) in items.iter_mut().take(*len) {
+                    visit(k);
+                    visit(v);
+                }
+            }
+            Repr::Hashed(hashmap) | Repr::Weak(hashmap) => {
+                for (k, v) in hashmap.iter_mut() {
+                    let mut key = k.get();
+                    visit(&mut key);
+                    if key.bits() != k.get().bits() {
+                        k.val.set(key);
+                        self.stale = true;
+                    }
+                    visit(v);
+                }
+            }
+        }
+    }
+
+    // The table is found through its first entry, which is why a hashed map is never left empty
+    // and an empty weak map gives its table up.
+    fn buffers(&self, mark: &mut dyn FnMut(*const u8)) {
+        if let Repr::Hashed(hashmap) | Repr::Weak(hashmap) = &self.repr
+            && let Some((k, _)) = hashmap.iter().next() {
+            mark(k as *const Key as *const u8);
+        }
+    }
+
+    fn print(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+
+    fn is_weak(&self) -> bool {
+        Map::is_weak(self)
+    }
+}
+
 impl std::fmt::Debug for Map {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{{")?;
@@ -195,11 +366,33 @@ mod test {
             map.remove(int(i));
         }
         assert!(map.len() == 0);
-        if let &Map::HashMap(..) = &map {
+        if let Repr::Hashed(..) = &map.repr {
             panic!("Should not have switched representations!");
         }
     }
 
+    #[test]
+    fn weak_map_stays_weak() {
+        let mut map = Map::new_weak();
+        map.insert(int(1), int(2));
+        map.clear();
+        assert!(map.is_weak() && map.len() == 0);
+        map.insert(int(1), int(2));
+        map.remove(int(1));
+        assert!(map.is_weak());
+    }
+
+    #[test]
+    fn weak_map_keys_by_identity() {
+        let mut map = Map::new_weak();
+        let (a, b) = (Str::alloc("key"), Str::alloc("key"));
+        map.insert(a, int(1));
+        assert_eq!(map.get(a), int(1));
+        assert_eq!(map.get(b), Symbol::nil());
+        assert_eq!(map.remove(b), Symbol::nil());
+        assert_eq!(map.len(), 1);
+    }
+
     #[test]
     fn nil_on_not_found() {
         let mut map = Map::new();
@@ -1,6 +1,6 @@
 /// Virtual machine values representing the complete set of types in defunct.
-/// Uses Webkit's NaN-boxing scheme to encode pointers and 32 bit signed integers into
-/// a double-precision float. 
+/// Uses Webkit's NaN-boxing scheme to encode pointers and 48 bit signed integers into
+/// a double-precision float. Larger integers are boxed as bignums.
 /// Pointers themselves are also tagged with type information in the low-bits.
 
 mod closures;
@@ -8,6 +8,13 @@ mod symbols;
 mod maps;
 mod vectors;
 mod native_fns;
+mod weak;
+mod object;
+mod equal;
+mod numbers;
+mod bignums;
+mod rationals;
+mod strings;
 
 use std::f32;
 
@@ -17,6 +24,12 @@ pub use symbols::SymbolTable;
 pub use maps::Map;
 pub use vectors::Vector;
 pub use native_fns::NativeFn;
+pub use weak::WeakRef;
+pub use object::{Header, Object, TypeDesc, REMEMBERED};
+pub use numbers::Number;
+pub use bignums::{BigInt, Bignum};
+pub use rationals::{Ratio, Rational};
+pub use strings::Str;
 
 use crate::bytecode::ByteCode;
 
@@ -41,6 +54,13 @@ fn byte_to_tag(byte: u8) -> Tag {
 const LOWTAG_BITS: usize = 3;
 const LOWTAG_MASK: usize = 0b111;
 const HIGHTAG_MASK: usize = 0xFFFF_0000_0000_0000;
+// Characters sit just below the integers. Only negative NaNs would rotate to this tag, and
+// every NaN is stored as the positive one.
+const CHAR_TAG: usize = 0xFFFE_0000_0000_0000;
+
+/// The range of integers stored unboxed, in the 48 bits below the high tag.
+pub const FIXNUM_MIN: i64 = -(1 << 47);
+pub const FIXNUM_MAX: i64 = (1 << 47) - 1;
 
 // We do not support 32-bit architectures.
 #[cfg(target_pointer_width = "64")]
@@ -55,7 +75,10 @@ impl Val {
         self.0.addr()
     }
 
+    /// Every NaN is stored as the same one. Others could have their payload collide
+    /// with the encoding of integers and pointers.
     pub fn from_num(num: f64) -> Val {
+        let num = if num.is_nan() { f64::NAN } else { num };
         let rotated = num.to_bits().wrapping_add(1 << 48);
         Val(rotated as *mut u8)
     }
@@ -64,7 +87,8 @@ impl Val {
     pub fn is_num(&self) -> bool {
         let Val(ptr) = *self;
         let bits = ptr.addr();
-        bits & HIGHTAG_MASK != HIGHTAG_MASK && bits & HIGHTAG_MASK != 0
+        let high = bits & HIGHTAG_MASK;
+        high != HIGHTAG_MASK && high != CHAR_TAG && high != 0
     }
 
     #[inline(always)]
@@ -81,8 +105,17 @@ impl Val {
     }
 
     pub fn from_int(int: i32) -> Val {
+        Val::from_fixnum(int as i64).unwrap()
+    }
+
+    /// The integer as an unboxed value, or `None` if it is outside the fixnum range.
+    /// `Number::to_val` boxes those instead.
+    pub fn from_fixnum(int: i64) -> Option<Val> {
+        if !(FIXNUM_MIN..=FIXNUM_MAX).contains(&int) {
+            return None;
+        }
         let extended = int as usize;
-        Val((extended | HIGHTAG_MASK) as *mut u8)
+        Some(Val((extended | HIGHTAG_MASK) as *mut u8))
     }
 
     #[inline(always)]
@@ -93,11 +126,31 @@ impl Val {
     }
 
     #[inline(always)]
-    pub fn get_int(&self) -> Option<i32> {
+    pub fn get_int(&self) -> Option<i64> {
         let Val(ptr) = *self;
         let bits = ptr.addr();
         if self.is_int() {
-            Some((bits & !HIGHTAG_MASK) as i32)
+            // Sign-extend the payload over the tag.
+            Some(((bits << 16) as i64) >> 16)
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn from_char(c: char) -> Val {
+        Val((c as usize | CHAR_TAG) as *mut u8)
+    }
+
+    #[inline(always)]
+    pub fn is_char(&self) -> bool {
+        self.bits() & HIGHTAG_MASK == CHAR_TAG
+    }
+
+    pub fn get_char(&self) -> Option<char> {
+        if self.is_char() {
+            // Only ever built from a `char`.
+            Some(unsafe { char::from_u32_unchecked(self.bits() as u32) })
         }
         else {
             None
@@ -119,6 +172,52 @@ impl Val {
         bits & HIGHTAG_MASK == 0
     }
 
+    /// The untagged address of a pointer value.
+    #[inline(always)]
+    pub fn ptr(&self) -> *mut u8 {
+        self.0.map_addr(|addr| addr & !LOWTAG_MASK)
+    }
+
+    /// The header of the heap object this points to. Immediates, native functions and the
+    /// constant symbols have none.
+    // A value is only a pointer; the object it points to is not borrowed from it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn header(&self) -> Option<&mut Header> {
+        if !self.is_ptr() || matches!(self.tag(), Tag::NativeFn) || *self == Symbol::nil() || *self == Symbol::t() {
+            return None;
+        }
+        Some(unsafe { &mut *(self.ptr() as *mut Header) })
+    }
+
+    /// The object this points to, if it is a `T`.
+    #[allow(clippy::mut_from_ref)]
+    pub fn downcast<T: Object>(&self) -> Option<&mut T> {
+        self.header()
+            .filter(|header| header.is::<T>())
+            .map(|header| unsafe { &mut *(header as *mut Header as *mut T) })
+    }
+
+    /// Applies `visit` to every value slot directly referenced by this object.
+    /// Collectors may overwrite the slots, e.g. to forward a moved object.
+    pub fn trace(&self, visit: &mut dyn FnMut(&mut Val)) {
+        if let Some(header) = self.header() {
+            unsafe { (header.desc.trace)(self.ptr(), visit) }
+        }
+    }
+
+    /// Weak references and weak-keyed maps, whose contents marking must not keep alive.
+    pub fn is_weak(&self) -> bool {
+        self.header().is_some_and(|header| unsafe { (header.desc.is_weak)(self.ptr()) })
+    }
+
+    /// Applies `mark` to every raw heap allocation owned by this object, such as the storage
+    /// behind a vector. These are freed along with their owner.
+    pub fn buffers(&self, mark: &mut dyn FnMut(*const u8)) {
+        if let Some(header) = self.header() {
+            unsafe { (header.desc.buffers)(self.ptr(), mark) }
+        }
+    }
+
     pub fn get<'a>(&'a self) -> Cases<'a> {
         if self.is_int() {
             return Cases::Int(self.get_int().unwrap())
@@ -127,6 +226,10 @@ impl Val {
         if self.is_num() {
             return Cases::Num(self.get_num().unwrap())
         }
+
+        if self.is_char() {
+            return Cases::Char(self.get_char().unwrap())
+        }
         
         let tag_bits = (self.0 as usize & LOWTAG_MASK) as u8;
         let ptr = self.0.map_addr(|addr| addr & !LOWTAG_MASK);
@@ -144,7 +247,15 @@ impl Val {
                 Cases::Vector(unsafe { &mut *(ptr as *mut Vector)})
             }
             Tag::Object => {
-                Cases::Object( unsafe { &*(ptr as *const ByteCode)})
+                if let Some(bytecode) = self.downcast::<ByteCode>() {
+                    Cases::Code(bytecode)
+                } else if let Some(weak) = self.downcast::<WeakRef>() {
+                    Cases::WeakRef(weak)
+                } else if let Some(s) = self.downcast::<Str>() {
+                    Cases::Str(s)
+                } else {
+                    Cases::Object(self.header().unwrap())
+                }
             }
             Tag::NativeFn => {
                 Cases::NativeFn(unsafe { std::mem::transmute(ptr) })
@@ -156,17 +267,26 @@ impl Val {
     pub fn nil() -> Val {
         Symbol::nil()
     }
+
+    pub fn t() -> Val {
+        Symbol::t()
+    }
 }
 
 pub enum Cases<'a> {
-    Int(i32),
+    Int(i64),
     Num(f64),
+    Char(char),
     Symbol(Symbol),
     Function(&'a Closure),
     Cons(),
     Vector(&'a mut Vector),
     Map(&'a mut Map),
-    Object(&'a ByteCode),
+    Code(&'a ByteCode),
+    WeakRef(&'a mut WeakRef),
+    Str(&'a Str),
+    /// Any other type of object, which can be reached through `Val::downcast`.
+    Object(&'a mut Header),
     Error(),
     NativeFn(NativeFn),
 }
@@ -174,32 +294,46 @@ pub enum Cases<'a> {
 impl std::fmt::Debug for Val {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use Cases::*;
+        if let Some(header) = self.header() {
+            return unsafe { (header.desc.print)(self.ptr(), f) };
+        }
         match self.get() {
             Int(i) => write!(f, "{}", i),
             Num(n) => write!(f, "{}f", n),
+            Char(c) => write_char(f, c),
             Symbol(p) => {
                 write!(f, ":{}", p.name())
             }
-            Function(p) => {
-                write!(f, "<fn {:x}>", (p as *const Closure).addr())
-            }
-            Map(p) => {
-                write!(f, "{:?}", p)
-            }
-            Vector(p) => {
-                write!(f, "{:?}", p)
-            }
             NativeFn(i) => {
                 write!(f, "<native {:x}>", i.addr())
             }
-            Object(bytecode) => {
-                write!(f, "<code {:x}>", (bytecode as *const ByteCode).addr())
-            }
             _ => unimplemented!()
         }
     }
 }
 
+/// Characters with a name in the reader's syntax, other than the graphic ones.
+pub const CHAR_NAMES: &[(&str, char)] = &[
+    ("newline", '\n'),
+    ("space", ' '),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("nul", '\0'),
+];
+
+// Writes a character the way the reader reads it back.
+fn write_char(f: &mut std::fmt::Formatter<'_>, c: char) -> std::fmt::Result {
+    if let Some((name, _)) = CHAR_NAMES.iter().find(|(_, named)| *named == c) {
+        return write!(f, "\\{}", name);
+    }
+    if c.is_whitespace() || c.is_control() {
+        return write!(f, "\\u{{{:x}}}", c as u32);
+    }
+    write!(f, "\\{}", c)
+}
+
+// Values compare and hash by identity, which is what `eq` tests. See `Val::equal` for
+// structural equality, which is where object types get to compare by their contents.
 impl std::cmp::PartialEq for Val {
     fn eq(&self, rhs: &Self) -> bool {
         self.0 == rhs.0
@@ -210,27 +344,6 @@ impl std::cmp::Eq for Val {}
 
 impl std::hash::Hash for Val {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        use Cases::*;
-        match self.get() {
-            Int(i) => {
-                state.write_i32(i)
-            }
-            Num(n) => {
-                state.write_u64(n.to_bits())
-            }
-            Symbol(s) => {
-                state.write_usize(s.addr())
-            }
-            Function(f) => {
-                state.write_usize((f as *const Closure).addr())
-            }
-            NativeFn(f) => {
-                state.write_usize(f.addr())
-            }
-            Object(bytecode) => {
-                state.write_usize((bytecode as *const ByteCode).addr())
-            }
-            _ => unimplemented!()
-        }
+        state.write_usize(self.bits())
     }
-}
\ No newline at end of file
+}
@@ -2,6 +2,9 @@ use crate::global::Global;
 
 use super::*;
 pub type ShouldHalt = bool;
+/// A function implemented in Rust, called with its arguments and the global environment.
+/// The arguments stay on the VM's stack for the duration of the call; any other value the
+/// function keeps across something that may collect must be held in a `HandleScope`.
 #[derive(Copy, Clone)]
 pub struct NativeFn(pub fn(&[Val], &mut Global) -> (Val, ShouldHalt));
 
@@ -0,0 +1,542 @@
+//! The numeric tower: integers, rationals and floats.
+//!
+//! Integers are fixnums while they fit in a value and bignums beyond that. Arithmetic on integers
+//! and rationals is exact. Integer results promote to a bignum when they overflow and come back
+//! to a fixnum when they fit again, and a rational whose denominator reduces to one becomes an
+//! integer. Once a float is involved the result is a float. Dividing integers gives a rational
+//! unless the division is exact, so `(/ 6 3)` is `2` and `(/ 1 2)` is `1/2`. Dividing an exact
+//! number by an exact zero is an error, while a float on either side gives an infinity or NaN.
+//!
+//! `=` and the comparisons look at values across kinds, so `(= 1 1.0)` and `(= 1/2 0.5)` hold.
+//! Floats compare exactly with integers and rationals, even where those have no float of their
+//! own. NaN is unordered and `=` to nothing, itself included. `eq` still compares identity, so
+//! `(eq 1 1.0)` does not.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::Hasher;
+
+use super::{BigInt, Bignum, Ratio, Rational, Val};
+
+/// The most bits a shift or an exact power may produce. Bignum digits live outside the heap, so
+/// its limit cannot stop them, and anything larger is refused before it is built.
+pub const MAX_EXACT_BITS: u64 = 1 << 24;
+
+/// A number taken out of a value to compute with. Integers that fit an `i64` are always `Int`,
+/// and a `Ratio` is never an integer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Big(BigInt),
+    Ratio(Ratio),
+    Float(f64),
+}
+
+impl Number {
+    pub fn of(val: Val) -> Option<Number> {
+        if let Some(i) = val.get_int() {
+            Some(Number::Int(i))
+        } else if let Some(f) = val.get_num() {
+            Some(Number::Float(f))
+        } else if let Some(big) = val.downcast::<Bignum>() {
+            Some(Number::integer(big.to_bigint()))
+        } else {
+            val.downcast::<Rational>().map(|rational| Number::Ratio(rational.to_ratio()))
+        }
+    }
+
+    /// The integer as an `Int` if it fits, and a `Big` otherwise.
+    pub fn integer(int: BigInt) -> Number {
+        match int.to_i64() {
+            Some(i) => Number::Int(i),
+            None => Number::Big(int),
+        }
+    }
+
+    /// The ratio as an integer if its denominator is one.
+    pub fn rational(ratio: Ratio) -> Number {
+        if ratio.is_integer() {
+            Number::integer(ratio.numerator().clone())
+        } else {
+            Number::Ratio(ratio)
+        }
+    }
+
+    /// Boxes integers outside the fixnum range as bignums, and rationals.
+    pub fn to_val(&self) -> Val {
+        match self {
+            Number::Int(i) => Val::from_fixnum(*i).unwrap_or_else(|| Bignum::alloc(&BigInt::from_i64(*i))),
+            Number::Big(big) => Bignum::alloc(big),
+            Number::Ratio(ratio) => Rational::alloc(ratio),
+            Number::Float(f) => Val::from_num(*f),
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Int(i) => *i as f64,
+            Number::Big(big) => big.to_f64(),
+            Number::Ratio(ratio) => ratio.to_f64(),
+            Number::Float(f) => *f,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Number::Int(_) | Number::Big(_))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Int(i) => *i == 0,
+            Number::Float(f) => *f == 0.0,
+            // Bignums and ratios never are.
+            _ => false,
+        }
+    }
+
+    fn to_bigint(&self) -> BigInt {
+        match self {
+            Number::Int(i) => BigInt::from_i64(*i),
+            Number::Big(big) => big.clone(),
+            _ => panic!("{} is not an integer", self),
+        }
+    }
+
+    /// The exact value of integers and rationals.
+    pub fn to_ratio(&self) -> Option<Ratio> {
+        match self {
+            Number::Int(i) => Some(Ratio::from_integer(BigInt::from_i64(*i))),
+            Number::Big(big) => Some(Ratio::from_integer(big.clone())),
+            Number::Ratio(ratio) => Some(ratio.clone()),
+            Number::Float(_) => None,
+        }
+    }
+
+    fn arith(
+        &self,
+        other: &Number,
+        int: fn(i64, i64) -> Option<i64>,
+        exact: fn(&Ratio, &Ratio) -> Option<Ratio>,
+        float: fn(f64, f64) -> f64,
+    ) -> Number {
+        if let (Number::Int(a), Number::Int(b)) = (self, other) && let Some(result) = int(*a, *b) {
+            return Number::Int(result);
+        }
+        if let (Some(a), Some(b)) = (self.to_ratio(), other.to_ratio()) && let Some(result) = exact(&a, &b) {
+            return Number::rational(result);
+        }
+        Number::Float(float(self.to_f64(), other.to_f64()))
+    }
+
+    pub fn add(&self, other: &Number) -> Number {
+        self.arith(other, i64::checked_add, |a, b| Some(a.add(b)), |a, b| a + b)
+    }
+
+    pub fn sub(&self, other: &Number) -> Number {
+        self.arith(other, i64::checked_sub, |a, b| Some(a.sub(b)), |a, b| a - b)
+    }
+
+    pub fn mul(&self, other: &Number) -> Number {
+        self.arith(other, i64::checked_mul, |a, b| Some(a.mul(b)), |a, b| a * b)
+    }
+
+    /// None if both are exact and `other` is zero.
+    pub fn div(&self, other: &Number) -> Option<Number> {
+        // An exact zero is always an `Int`.
+        if !matches!(self, Number::Float(_)) && matches!(other, Number::Int(0)) {
+            return None;
+        }
+        let exact = |a: i64, b: i64| match a.checked_rem(b) {
+            Some(0) => a.checked_div(b),
+            _ => None,
+        };
+        Some(self.arith(other, exact, |a, b| Some(a.div(b)), |a, b| a / b))
+    }
+
+    // Operations on two integers, which panic given anything else.
+    fn integer_op(&self, other: &Number, int: fn(i64, i64) -> Option<i64>, big: fn(&BigInt, &BigInt) -> BigInt) -> Number {
+        if let (Number::Int(a), Number::Int(b)) = (self, other) && let Some(result) = int(*a, *b) {
+            return Number::Int(result);
+        }
+        Number::integer(big(&self.to_bigint(), &other.to_bigint()))
+    }
+
+    /// Integer division, rounding towards zero. None if `other` is zero.
+    pub fn quot(&self, other: &Number) -> Option<Number> {
+        if other.is_zero() {
+            return None;
+        }
+        Some(self.integer_op(other, i64::checked_div, |a, b| a.div_rem(b).0))
+    }
+
+    /// The remainder of `quot`, which has the sign of `self`. None if `other` is zero.
+    pub fn rem(&self, other: &Number) -> Option<Number> {
+        if other.is_zero() {
+            return None;
+        }
+        Some(self.integer_op(other, i64::checked_rem, |a, b| a.div_rem(b).1))
+    }
+
+    /// The remainder of dividing and rounding down, which has the sign of `other`.
+    /// None if `other` is zero.
+    pub fn modulo(&self, other: &Number) -> Option<Number> {
+        if other.is_zero() {
+            return None;
+        }
+        let int = |a: i64, b: i64| a.checked_rem(b).map(|r| if r != 0 && (r < 0) != (b < 0) { r + b } else { r });
+        let big = |a: &BigInt, b: &BigInt| {
+            let r = a.div_rem(b).1;
+            if !r.is_zero() && r.is_negative() != b.is_negative() { r.add(b) } else { r }
+        };
+        Some(self.integer_op(other, int, big))
+    }
+
+    pub fn bit_and(&self, other: &Number) -> Number {
+        self.integer_op(other, |a, b| Some(a & b), BigInt::bit_and)
+    }
+
+    pub fn bit_or(&self, other: &Number) -> Number {
+        self.integer_op(other, |a, b| Some(a | b), BigInt::bit_or)
+    }
+
+    pub fn bit_xor(&self, other: &Number) -> Number {
+        self.integer_op(other, |a, b| Some(a ^ b), BigInt::bit_xor)
+    }
+
+    pub fn bit_not(&self) -> Number {
+        match self {
+            Number::Int(i) => Number::Int(!i),
+            _ => Number::integer(self.to_bigint().bit_not()),
+        }
+    }
+
+    /// Multiplies by `2^bits`. Shifting by a negative count shifts right. Panics if the result
+    /// would take more than `MAX_EXACT_BITS`.
+    pub fn shift_left(&self, bits: &Number) -> Number {
+        match bits {
+            Number::Int(bits) => self.shift(*bits),
+            _ => panic!("Cannot shift by {}", bits),
+        }
+    }
+
+    /// Divides by `2^bits`, rounding down so that the sign is kept. Shifting by a negative
+    /// count shifts left.
+    pub fn shift_right(&self, bits: &Number) -> Number {
+        match bits {
+            Number::Int(bits) if *bits != i64::MIN => self.shift(-bits),
+            _ => panic!("Cannot shift by {}", bits),
+        }
+    }
+
+    fn shift(&self, bits: i64) -> Number {
+        if let Number::Int(i) = self {
+            // Zero stays zero however far it is shifted.
+            if bits <= 0 || *i == 0 {
+                return Number::Int(i >> bits.unsigned_abs().min(63));
+            }
+            if bits < 63 && (i << bits) >> bits == *i {
+                return Number::Int(i << bits);
+            }
+        }
+        if bits > 0 && self.to_bigint().bit_len() as u64 + bits as u64 > MAX_EXACT_BITS {
+            panic!("Cannot shift by {}, the result would be too large", bits);
+        }
+        Number::integer(self.to_bigint().shift(bits))
+    }
+
+    pub fn abs(&self) -> Number {
+        match self {
+            Number::Int(i) => i.checked_abs().map_or_else(|| Number::Big(BigInt::from_i64(*i).abs()), Number::Int),
+            Number::Big(big) => Number::Big(big.abs()),
+            Number::Ratio(ratio) => Number::Ratio(ratio.abs()),
+            Number::Float(f) => Number::Float(f.abs()),
+        }
+    }
+
+    /// The largest integer not above the number. Exact numbers give integers and floats give
+    /// floats, as do the other roundings.
+    pub fn floor(&self) -> Number {
+        self.round_with(f64::floor, |quotient, remainder, _| match remainder.is_negative() {
+            true => quotient.sub(&BigInt::from_i64(1)),
+            false => quotient,
+        })
+    }
+
+    /// The smallest integer not below the number.
+    pub fn ceil(&self) -> Number {
+        self.round_with(f64::ceil, |quotient, remainder, _| match !remainder.is_zero() && !remainder.is_negative() {
+            true => quotient.add(&BigInt::from_i64(1)),
+            false => quotient,
+        })
+    }
+
+    /// The nearest integer, rounding ties to even.
+    pub fn round(&self) -> Number {
+        self.round_with(f64::round_ties_even, |quotient, remainder, denominator| {
+            let away = match remainder.abs().shl(1).cmp(denominator) {
+                Ordering::Less => false,
+                Ordering::Equal => quotient.is_odd(),
+                Ordering::Greater => true,
+            };
+            match (away, remainder.is_negative()) {
+                (false, _) => quotient,
+                (true, false) => quotient.add(&BigInt::from_i64(1)),
+                (true, true) => quotient.sub(&BigInt::from_i64(1)),
+            }
+        })
+    }
+
+    /// The integer part of the number, rounding towards zero.
+    pub fn truncate(&self) -> Number {
+        self.round_with(f64::trunc, |quotient, _, _| quotient)
+    }
+
+    // Rounds a ratio given the quotient and remainder of dividing it out, which round towards
+    // zero, and its denominator.
+    fn round_with(&self, float: fn(f64) -> f64, exact: impl Fn(BigInt, BigInt, &BigInt) -> BigInt) -> Number {
+        match self {
+            Number::Int(_) | Number::Big(_) => self.clone(),
+            Number::Ratio(ratio) => {
+                let (quotient, remainder) = ratio.numerator().div_rem(ratio.denominator());
+                Number::integer(exact(quotient, remainder, ratio.denominator()))
+            }
+            Number::Float(f) => Number::Float(float(*f)),
+        }
+    }
+
+    /// The number as an integer, rounding towards zero. Panics for infinities and NaN.
+    pub fn to_integer(&self) -> Number {
+        match self {
+            Number::Float(f) if !f.is_finite() => panic!("Cannot convert {} to an integer", self),
+            Number::Float(f) => Number::integer(BigInt::from_f64(f.trunc())),
+            _ => self.truncate(),
+        }
+    }
+
+    /// The square root, which is exact for exact numbers that are squares. Other numbers, and
+    /// negative ones, give a float.
+    pub fn sqrt(&self) -> Number {
+        let exact_root = |n: &BigInt| {
+            let root = n.sqrt();
+            (root.mul(&root) == *n).then_some(root)
+        };
+        match self.to_ratio() {
+            Some(ratio) if !ratio.numerator().is_negative() => {
+                if let (Some(numerator), Some(denominator)) = (exact_root(ratio.numerator()), exact_root(ratio.denominator())) {
+                    return Number::rational(Ratio::new(numerator, denominator));
+                }
+                Number::Float(self.to_f64().sqrt())
+            }
+            _ => Number::Float(self.to_f64().sqrt()),
+        }
+    }
+
+    /// `self` raised to `exponent`. An exact number raised to an integer that fits an `i64`
+    /// stays exact; anything else gives a float. None if an exact zero is raised to a negative
+    /// power. Panics if the exact result would take more than `MAX_EXACT_BITS`.
+    pub fn pow(&self, exponent: &Number) -> Option<Number> {
+        match (self, exponent) {
+            (Number::Float(_), _) | (_, Number::Big(_) | Number::Ratio(_) | Number::Float(_)) => {
+                Some(Number::Float(self.to_f64().powf(exponent.to_f64())))
+            }
+            (base, Number::Int(exponent)) => {
+                // The result takes at least this many bits for each step of the exponent.
+                let bits = match base {
+                    Number::Ratio(ratio) => ratio.numerator().bit_len().max(ratio.denominator().bit_len()),
+                    _ => base.to_bigint().bit_len(),
+                } as u64;
+                if bits.saturating_sub(1).saturating_mul(exponent.unsigned_abs()) > MAX_EXACT_BITS {
+                    panic!("Cannot raise {} to {}, the result would be too large", base, exponent);
+                }
+                // Squares the base for each bit of the exponent.
+                let (mut result, mut base, mut bits) = (Number::Int(1), base.clone(), exponent.unsigned_abs());
+                while bits > 0 {
+                    if bits & 1 == 1 {
+                        result = result.mul(&base);
+                    }
+                    bits >>= 1;
+                    if bits > 0 {
+                        base = base.mul(&base);
+                    }
+                }
+                if *exponent < 0 { Number::Int(1).div(&result) } else { Some(result) }
+            }
+        }
+    }
+
+    pub fn compare(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => Some(a.cmp(b)),
+            (Number::Float(a), Number::Float(b)) => a.partial_cmp(b),
+            // Integers this small convert to floats exactly.
+            (Number::Int(a), Number::Float(b)) if a.unsigned_abs() <= 1 << 53 => (*a as f64).partial_cmp(b),
+            (Number::Float(a), Number::Int(b)) if b.unsigned_abs() <= 1 << 53 => a.partial_cmp(&(*b as f64)),
+            (Number::Float(f), exact) => compare_float(*f, &exact.to_ratio().unwrap()),
+            (exact, Number::Float(f)) => compare_float(*f, &exact.to_ratio().unwrap()).map(Ordering::reverse),
+            (a, b) => Some(a.to_ratio().unwrap().cmp(&b.to_ratio().unwrap())),
+        }
+    }
+
+    /// Hashes the number so that numbers that compare equal hash alike, whatever their kind.
+    pub fn hash_into(&self, state: &mut dyn Hasher) {
+        match self {
+            Number::Int(i) => state.write_i64(*i),
+            Number::Big(big) => big.hash_into(state),
+            Number::Ratio(ratio) => ratio.hash_into(state),
+            Number::Float(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => state.write_i64(*f as i64),
+            Number::Float(f) if f.is_finite() => Number::rational(Ratio::from_f64(*f)).hash_into(state),
+            Number::Float(f) => state.write_u64(f.to_bits()),
+        }
+    }
+}
+
+// Compares a float with an exact number exactly, rather than rounding that to a float.
+fn compare_float(float: f64, exact: &Ratio) -> Option<Ordering> {
+    if float.is_nan() {
+        return None;
+    }
+    if float.is_infinite() {
+        return Some(if float > 0.0 { Ordering::Greater } else { Ordering::Less });
+    }
+    Some(Ratio::from_f64(float).cmp(exact))
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Int(i) => write!(f, "{}", i),
+            Number::Big(big) => write!(f, "{}", big),
+            Number::Ratio(ratio) => write!(f, "{}", ratio),
+            Number::Float(n) => write!(f, "{:?}", n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use Number::*;
+
+    fn big(src: &str) -> Number {
+        Big(BigInt::parse(src).unwrap())
+    }
+
+    #[test]
+    fn integers_promote_and_demote() {
+        assert_eq!(Int(2).add(&Int(3)), Int(5));
+        assert_eq!(Int(2).mul(&Float(1.5)), Float(3.0));
+        assert_eq!(Int(i64::MAX).add(&Int(1)), big("9223372036854775808"));
+        assert_eq!(Int(i64::MIN).sub(&Int(1)), big("-9223372036854775809"));
+        assert_eq!(big("9223372036854775808").sub(&Int(1)), Int(i64::MAX));
+        assert_eq!(big("9223372036854775808").mul(&Float(0.5)), Float(4611686018427387904.0));
+    }
+
+    fn ratio(numerator: i64, denominator: i64) -> Number {
+        Number::rational(super::Ratio::new(BigInt::from_i64(numerator), BigInt::from_i64(denominator)))
+    }
+
+    #[test]
+    fn division_is_exact() {
+        assert_eq!(Int(6).div(&Int(3)), Some(Int(2)));
+        assert_eq!(Int(1).div(&Int(2)), Some(ratio(1, 2)));
+        assert_eq!(Int(-7).div(&Int(2)), Some(ratio(-7, 2)));
+        assert_eq!(ratio(1, 3).mul(&Int(3)), Int(1));
+        assert_eq!(ratio(1, 2).add(&Float(0.25)), Float(0.75));
+        assert_eq!(Int(i64::MIN).div(&Int(-1)), Some(big("9223372036854775808")));
+        assert_eq!(big("18446744073709551616").div(&Int(1 << 32)), Some(Int(1 << 32)));
+        assert_eq!(Int(1).div(&Int(0)), None);
+        assert_eq!(ratio(1, 2).div(&Int(0)), None);
+        assert_eq!(Int(1).div(&Float(0.0)), Some(Float(f64::INFINITY)));
+        assert!(matches!(Float(0.0).div(&Int(0)), Some(Float(f)) if f.is_nan()));
+    }
+
+    #[test]
+    fn integer_division_rounds_as_documented() {
+        let cases = [(7, 2, 3, 1, 1), (-7, 2, -3, -1, 1), (7, -2, -3, 1, -1), (-7, -2, 3, -1, -1), (6, -3, -2, 0, 0)];
+        for (a, b, quot, rem, modulo) in cases {
+            assert_eq!(Int(a).quot(&Int(b)), Some(Int(quot)));
+            assert_eq!(Int(a).rem(&Int(b)), Some(Int(rem)));
+            assert_eq!(Int(a).modulo(&Int(b)), Some(Int(modulo)), "(mod {} {})", a, b);
+        }
+        assert_eq!(Int(i64::MIN).quot(&Int(-1)), Some(big("9223372036854775808")));
+        assert_eq!(Int(i64::MIN).rem(&Int(-1)), Some(Int(0)));
+        assert_eq!(big("-18446744073709551617").modulo(&Int(10)), Some(Int(3)));
+    }
+
+    #[test]
+    fn integer_division_by_zero_has_no_result() {
+        assert_eq!(Int(1).quot(&Int(0)), None);
+        assert_eq!(big("18446744073709551616").rem(&Int(0)), None);
+        assert_eq!(Int(-1).modulo(&Int(0)), None);
+    }
+
+    #[test]
+    fn shifts_promote_and_round_down() {
+        assert_eq!(Int(1).shift_left(&Int(62)), Int(1 << 62));
+        assert_eq!(Int(1).shift_left(&Int(63)), big("9223372036854775808"));
+        assert_eq!(Int(-1).shift_left(&Int(63)), Int(i64::MIN));
+        assert_eq!(Int(-5).shift_right(&Int(1)), Int(-3));
+        assert_eq!(Int(-5).shift_left(&Int(-100)), Int(-1));
+        assert_eq!(big("9223372036854775808").shift_right(&Int(63)), Int(1));
+        assert_eq!(Int(-6).bit_and(&Int(5)), Int(0));
+        assert_eq!(Int(5).bit_not(), Int(-6));
+    }
+
+    #[test]
+    fn shifts_and_powers_stop_at_the_bit_limit() {
+        let limit = MAX_EXACT_BITS as i64;
+        assert_eq!(Int(1).shift_left(&Int(limit - 1)).shift_right(&Int(limit - 1)), Int(1));
+        assert!(std::panic::catch_unwind(|| Int(1).shift_left(&Int(limit))).is_err());
+        assert_eq!(Int(-1).pow(&Int(limit + 1)), Some(Int(-1)));
+        assert!(std::panic::catch_unwind(|| Int(2).pow(&Int(limit + 1))).is_err());
+        assert!(std::panic::catch_unwind(|| ratio(1, 3).pow(&Int(-limit - 1))).is_err());
+        assert_eq!(Int(1).pow(&Int(i64::MAX)), Some(Int(1)));
+    }
+
+    #[test]
+    fn rounding_keeps_exactness() {
+        let cases = [(ratio(7, 2), 3, 4, 4, 3), (ratio(-7, 2), -4, -3, -4, -3), (ratio(5, 2), 2, 3, 2, 2), (ratio(-1, 3), -1, 0, 0, 0), (ratio(5, 3), 1, 2, 2, 1)];
+        for (n, floor, ceil, round, truncate) in cases {
+            assert_eq!((n.floor(), n.ceil(), n.round(), n.truncate()), (Int(floor), Int(ceil), Int(round), Int(truncate)), "{}", n);
+        }
+        assert_eq!(Float(2.5).round(), Float(2.0));
+        assert_eq!(Float(-2.5).floor(), Float(-3.0));
+        assert_eq!(big("9223372036854775808").floor(), big("9223372036854775808"));
+        assert_eq!(Float(-2.7).to_integer(), Int(-2));
+        assert_eq!(Float(1e19).to_integer(), big("10000000000000000000"));
+    }
+
+    #[test]
+    fn roots_and_powers_stay_exact() {
+        assert_eq!(Int(16).sqrt(), Int(4));
+        assert_eq!(ratio(9, 4).sqrt(), ratio(3, 2));
+        assert_eq!(Int(2).sqrt(), Float(2f64.sqrt()));
+        assert!(matches!(Int(-4).sqrt(), Float(f) if f.is_nan()));
+        assert_eq!(big("18446744073709551616").sqrt(), Int(1 << 32));
+        assert_eq!(Int(2).pow(&Int(10)), Some(Int(1024)));
+        assert_eq!(Int(2).pow(&Int(64)), Some(big("18446744073709551616")));
+        assert_eq!(Int(2).pow(&Int(-2)), Some(ratio(1, 4)));
+        assert_eq!(ratio(-2, 3).pow(&Int(3)), Some(ratio(-8, 27)));
+        assert_eq!(Int(7).pow(&Int(0)), Some(Int(1)));
+        assert_eq!(Int(4).pow(&Float(0.5)), Some(Float(2.0)));
+        assert_eq!(Float(1.5).pow(&Int(2)), Some(Float(2.25)));
+        assert_eq!(Int(0).pow(&Int(-1)), None);
+        assert_eq!(Int(-7).abs(), Int(7));
+        assert_eq!(Int(i64::MIN).abs(), big("9223372036854775808"));
+    }
+
+    #[test]
+    fn comparisons_cross_kinds() {
+        assert_eq!(Int(1).compare(&Float(1.0)), Some(Ordering::Equal));
+        assert_eq!(Int(1).compare(&Float(1.5)), Some(Ordering::Less));
+        assert_eq!(Float(f64::NAN).compare(&Float(f64::NAN)), None);
+        // 2^53 + 1 has no float, and must not compare equal to the one it rounds to.
+        assert_eq!(Int((1 << 53) + 1).compare(&Float(9007199254740992.0)), Some(Ordering::Greater));
+        assert_eq!(Float(-0.5).compare(&big("-9223372036854775809")), Some(Ordering::Greater));
+        assert_eq!(big("1267650600228229401496703205376").compare(&Float(2f64.powi(100))), Some(Ordering::Equal));
+        assert_eq!(big("-9223372036854775809").compare(&Float(f64::NEG_INFINITY)), Some(Ordering::Greater));
+        assert_eq!(ratio(1, 2).compare(&Float(0.5)), Some(Ordering::Equal));
+        assert_eq!(ratio(1, 3).compare(&Float(1.0 / 3.0)), Some(Ordering::Greater));
+        assert_eq!(ratio(-1, 3).compare(&Int(0)), Some(Ordering::Less));
+    }
+}
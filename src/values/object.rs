@@ -0,0 +1,175 @@
+//! Every heap object starts with a `Header` pointing at the `TypeDesc` of its type, so the
+//! collector and the rest of the runtime can trace, print, compare and hash an object without
+//! knowing what it is. New types of object implement `Object` and share `Tag::Object`.
+
+use std::fmt;
+use std::hash::Hasher;
+
+use super::{Tag, Val};
+
+/// Collector flag set on objects in the remembered set.
+pub const REMEMBERED: u32 = 1 << 0;
+
+#[repr(C)]
+pub struct Header {
+    pub desc: &'static TypeDesc,
+    /// Flags owned by the collector.
+    pub gc: u32,
+    /// Size of the whole object in bytes, header included.
+    pub size: u32,
+}
+
+impl Header {
+    pub fn new<T: Object>() -> Header {
+        Header { desc: T::desc(), gc: 0, size: size_of::<T>() as u32 }
+    }
+
+    pub fn is<T: Object>(&self) -> bool {
+        std::ptr::eq(self.desc, T::desc())
+    }
+}
+
+type TraceFn = unsafe fn(*mut u8, &mut dyn FnMut(&mut Val));
+
+/// The operations on one type of heap object. Built by `TypeDesc::of` from an `Object` impl,
+/// and kept in a static so that its address identifies the type.
+pub struct TypeDesc {
+    pub name: &'static str,
+    /// The tag carried by values pointing at objects of this type.
+    pub tag: Tag,
+    pub trace: TraceFn,
+    pub buffers: unsafe fn(*const u8, &mut dyn FnMut(*const u8)),
+    pub print: unsafe fn(*const u8, &mut fmt::Formatter<'_>) -> fmt::Result,
+    pub eq: unsafe fn(*const u8, *const u8) -> bool,
+    pub hash: unsafe fn(*const u8, &mut dyn Hasher),
+    pub is_weak: unsafe fn(*const u8) -> bool,
+}
+
+impl TypeDesc {
+    pub const fn of<T: Object>(name: &'static str, tag: Tag) -> TypeDesc {
+        TypeDesc {
+            name, tag,
+            trace: trace::<T>,
+            buffers: buffers::<T>,
+            print: print::<T>,
+            eq: eq::<T>,
+            hash: hash::<T>,
+            is_weak: is_weak::<T>,
+        }
+    }
+}
+
+unsafe fn trace<T: Object>(obj: *mut u8, visit: &mut dyn FnMut(&mut Val)) {
+    unsafe { (*(obj as *mut T)).trace(visit) }
+}
+
+unsafe fn buffers<T: Object>(obj: *const u8, mark: &mut dyn FnMut(*const u8)) {
+    unsafe { (*(obj as *const T)).buffers(mark) }
+}
+
+unsafe fn print<T: Object>(obj: *const u8, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    unsafe { (*(obj as *const T)).print(f) }
+}
+
+unsafe fn eq<T: Object>(obj: *const u8, other: *const u8) -> bool {
+    unsafe { (*(obj as *const T)).equals(&*(other as *const T)) }
+}
+
+unsafe fn hash<T: Object>(obj: *const u8, state: &mut dyn Hasher) {
+    unsafe { (*(obj as *const T)).hash(state) }
+}
+
+unsafe fn is_weak<T: Object>(obj: *const u8) -> bool {
+    unsafe { (*(obj as *const T)).is_weak() }
+}
+
+/// A type of heap object. Everything but `desc` defaults to treating the object as an opaque
+/// identity with no references.
+///
+/// # Safety
+/// Implementors must be `#[repr(C)]` and start with a `Header` made by `Header::new::<Self>()`.
+pub unsafe trait Object: Sized + 'static {
+    fn desc() -> &'static TypeDesc;
+
+    /// Applies `visit` to every value slot in the object.
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut Val)) {}
+
+    /// Applies `mark` to every raw heap allocation the object owns.
+    fn buffers(&self, mark: &mut dyn FnMut(*const u8)) {}
+
+    fn print(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} {:x}>", Self::desc().name, (self as *const Self).addr())
+    }
+
+    /// Only called with another object of the same type. Must agree with `hash`.
+    fn equals(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+
+    fn hash(&self, state: &mut dyn Hasher) {
+        state.write_usize((self as *const Self).addr())
+    }
+
+    /// Whether marking must leave the object's references alone.
+    fn is_weak(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::alloc::Heap;
+    use crate::values::Cases;
+    use std::hash::DefaultHasher;
+
+    // Points are equal whenever their coordinates are.
+    #[repr(C)]
+    struct Point {
+        header: Header,
+        x: i32,
+        y: i32,
+    }
+
+    static POINT: TypeDesc = TypeDesc::of::<Point>("point", Tag::Object);
+
+    unsafe impl Object for Point {
+        fn desc() -> &'static TypeDesc {
+            &POINT
+        }
+
+        fn equals(&self, other: &Point) -> bool {
+            (self.x, self.y) == (other.x, other.y)
+        }
+
+        fn hash(&self, state: &mut dyn Hasher) {
+            state.write_i32(self.x);
+            state.write_i32(self.y);
+        }
+    }
+
+    fn point(x: i32, y: i32) -> Val {
+        let ptr = Heap::alloc_object::<Point>();
+        unsafe { std::ptr::write(ptr, Point { header: Header::new::<Point>(), x, y }) };
+        Val::from_ptr(Tag::Object, ptr as *mut u8)
+    }
+
+    #[test]
+    fn objects_dispatch_through_their_descriptor() {
+        let (a, b, c) = (point(1, 2), point(1, 2), point(2, 1));
+        assert_ne!(a.bits(), b.bits());
+        assert!(a.equal(&b) && !a.equal(&c));
+        assert_ne!(a, b);
+        let hash = |val: Val| {
+            let mut state = DefaultHasher::new();
+            val.hash_equal(&mut state);
+            state.finish()
+        };
+        assert_eq!(hash(a), hash(b));
+
+        assert_eq!(a.downcast::<Point>().unwrap().y, 2);
+        assert!(a.downcast::<crate::values::WeakRef>().is_none());
+        assert!(matches!(a.get(), Cases::Object(header) if header.desc.name == "point"));
+        assert!(format!("{:?}", a).starts_with("<point "));
+    }
+}
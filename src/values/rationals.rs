@@ -0,0 +1,245 @@
+//! Exact fractions.
+//!
+//! `Ratio` does the arithmetic on a numerator and a denominator kept in lowest terms, with the
+//! sign on the numerator. A `Rational` is the heap object a `Ratio` is stored in once it becomes
+//! a value. It holds both parts as integer values, so they are fixnums or bignums like any other
+//! integer. Ratios whose denominator is one are integers, and are never stored as rationals.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::Hasher;
+
+use crate::alloc::Heap;
+use super::{BigInt, Header, Number, Object, Tag, TypeDesc, Val};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ratio {
+    numerator: BigInt,
+    denominator: BigInt,
+}
+
+impl Ratio {
+    /// `numerator / denominator` in lowest terms. Panics if the denominator is zero.
+    pub fn new(numerator: BigInt, denominator: BigInt) -> Ratio {
+        assert!(!denominator.is_zero(), "Division by zero");
+        let (numerator, denominator) = match denominator.is_negative() {
+            true => (numerator.neg(), denominator.neg()),
+            false => (numerator, denominator),
+        };
+        if denominator.is_one() {
+            return Ratio { numerator, denominator };
+        }
+        let divisor = numerator.gcd(&denominator);
+        if divisor.is_one() {
+            return Ratio { numerator, denominator };
+        }
+        Ratio { numerator: numerator.div_rem(&divisor).0, denominator: denominator.div_rem(&divisor).0 }
+    }
+
+    pub fn from_integer(int: BigInt) -> Ratio {
+        Ratio { numerator: int, denominator: BigInt::from_i64(1) }
+    }
+
+    /// The fraction a finite float is exactly equal to. Every float is one, with a power of two
+    /// for its denominator.
+    pub fn from_f64(float: f64) -> Ratio {
+        assert!(float.is_finite(), "{} is not a fraction", float);
+        if float.fract() == 0.0 {
+            return Ratio::from_integer(BigInt::from_f64(float));
+        }
+        let bits = float.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as usize;
+        let mantissa = (bits & ((1 << 52) - 1)) | if exponent > 0 { 1 << 52 } else { 0 };
+        let numerator = BigInt::from_i64(mantissa as i64);
+        let numerator = if float < 0.0 { numerator.neg() } else { numerator };
+        Ratio::new(numerator, BigInt::from_i64(1).shl(1075 - exponent.max(1)))
+    }
+
+    pub fn numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        &self.denominator
+    }
+
+    pub fn abs(&self) -> Ratio {
+        Ratio { numerator: self.numerator.abs(), denominator: self.denominator.clone() }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator.is_one()
+    }
+
+    /// The nearest float, rounding ties to even.
+    pub fn to_f64(&self) -> f64 {
+        let (numerator, denominator) = (&self.numerator, &self.denominator);
+        // Both convert exactly, and float division rounds correctly.
+        if numerator.bit_len() <= 53 && denominator.bit_len() <= 53 {
+            return numerator.to_f64() / denominator.to_f64();
+        }
+        // Otherwise divide with at least 65 bits of quotient, and one more bit recording
+        // whether anything was left over, which is enough to round the same way.
+        let mut shift = 65 + denominator.bit_len() as i64 - numerator.bit_len() as i64;
+        let (quotient, remainder) = match shift >= 0 {
+            true => numerator.abs().shl(shift as usize).div_rem(denominator),
+            false => numerator.abs().div_rem(&denominator.shl(-shift as usize)),
+        };
+        let sticky = BigInt::from_i64(!remainder.is_zero() as i64);
+        let quotient = quotient.shl(1).add(&sticky);
+        shift += 1;
+        let magnitude = scale(quotient.to_f64(), -shift);
+        if numerator.is_negative() { -magnitude } else { magnitude }
+    }
+
+    pub fn add(&self, other: &Ratio) -> Ratio {
+        if self.is_integer() && other.is_integer() {
+            return Ratio::from_integer(self.numerator.add(&other.numerator));
+        }
+        let numerator = self.numerator.mul(&other.denominator).add(&other.numerator.mul(&self.denominator));
+        Ratio::new(numerator, self.denominator.mul(&other.denominator))
+    }
+
+    pub fn sub(&self, other: &Ratio) -> Ratio {
+        self.add(&Ratio { numerator: other.numerator.neg(), denominator: other.denominator.clone() })
+    }
+
+    pub fn mul(&self, other: &Ratio) -> Ratio {
+        Ratio::new(self.numerator.mul(&other.numerator), self.denominator.mul(&other.denominator))
+    }
+
+    /// Panics if `other` is zero.
+    pub fn div(&self, other: &Ratio) -> Ratio {
+        Ratio::new(self.numerator.mul(&other.denominator), self.denominator.mul(&other.numerator))
+    }
+
+    pub fn hash_into(&self, state: &mut dyn Hasher) {
+        self.numerator.hash_into(state);
+        self.denominator.hash_into(state);
+    }
+}
+
+// Multiplies by a power of two, in steps that stay within the range of a float.
+fn scale(mut float: f64, mut exponent: i64) -> f64 {
+    while exponent > 1000 {
+        float *= 2f64.powi(1000);
+        exponent -= 1000;
+    }
+    while exponent < -1000 {
+        float *= 2f64.powi(-1000);
+        exponent += 1000;
+    }
+    float * 2f64.powi(exponent as i32)
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Ratio) -> Ordering {
+        // Denominators are positive, so cross-multiplying keeps the order.
+        self.numerator.mul(&other.denominator).cmp(&other.numerator.mul(&self.denominator))
+    }
+}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Ratio) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Ratio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_integer() {
+            return write!(f, "{}", self.numerator);
+        }
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+/// A `Ratio` on the heap, with its parts as integer values.
+#[repr(C)]
+pub struct Rational {
+    header: Header,
+    numerator: Val,
+    denominator: Val,
+}
+
+impl Rational {
+    /// Allocates a rational holding `ratio`, which must not be an integer.
+    pub fn alloc(ratio: &Ratio) -> Val {
+        debug_assert!(!ratio.is_integer());
+        let numerator = Number::integer(ratio.numerator.clone()).to_val();
+        let denominator = Number::integer(ratio.denominator.clone()).to_val();
+        let ptr = Heap::alloc_object::<Rational>();
+        unsafe { std::ptr::write(ptr, Rational { header: Header::new::<Rational>(), numerator, denominator }) };
+        let val = Val::from_ptr(Tag::Object, ptr as *mut u8);
+        // The nursery may have been full, leaving a mature object pointing at young bignums.
+        Heap::write_barrier(val, numerator);
+        Heap::write_barrier(val, denominator);
+        val
+    }
+
+    pub fn to_ratio(&self) -> Ratio {
+        let integer = |val: Val| match Number::of(val) {
+            Some(Number::Int(i)) => BigInt::from_i64(i),
+            Some(Number::Big(big)) => big,
+            _ => unreachable!("The parts of a rational are integers"),
+        };
+        Ratio { numerator: integer(self.numerator), denominator: integer(self.denominator) }
+    }
+}
+
+static RATIONAL: TypeDesc = TypeDesc::of::<Rational>("rational", Tag::Object);
+
+unsafe impl Object for Rational {
+    fn desc() -> &'static TypeDesc {
+        &RATIONAL
+    }
+
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut Val)) {
+        visit(&mut self.numerator);
+        visit(&mut self.denominator);
+    }
+
+    fn print(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}/{:?}", self.numerator, self.denominator)
+    }
+
+    fn equals(&self, other: &Rational) -> bool {
+        self.numerator == other.numerator && self.denominator == other.denominator
+    }
+
+    fn hash(&self, state: &mut dyn Hasher) {
+        self.to_ratio().hash_into(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ratio(numerator: i64, denominator: i64) -> Ratio {
+        Ratio::new(BigInt::from_i64(numerator), BigInt::from_i64(denominator))
+    }
+
+    #[test]
+    fn ratios_stay_in_lowest_terms() {
+        assert_eq!(ratio(2, -4), ratio(-1, 2));
+        assert_eq!(ratio(2, -4).to_string(), "-1/2");
+        assert_eq!(ratio(1, 3).add(&ratio(1, 6)), ratio(1, 2));
+        assert_eq!(ratio(1, 3).sub(&ratio(1, 3)), ratio(0, 1));
+        assert_eq!(ratio(2, 3).mul(&ratio(3, 2)).to_string(), "1");
+        assert_eq!(ratio(1, 3).div(&ratio(-2, 3)), ratio(-1, 2));
+        assert!(ratio(-1, 3) < ratio(-1, 4));
+    }
+
+    #[test]
+    fn floats_convert_exactly() {
+        assert_eq!(Ratio::from_f64(0.5), ratio(1, 2));
+        assert_eq!(Ratio::from_f64(-0.1).to_f64(), -0.1);
+        assert_eq!(Ratio::from_f64(f64::MIN_POSITIVE / 8.0).to_f64(), f64::MIN_POSITIVE / 8.0);
+        assert_eq!(ratio(1, 3).to_f64(), 1.0 / 3.0);
+        let third = Ratio::new(BigInt::parse("100000000000000000000").unwrap(), BigInt::parse("300000000000000000000").unwrap());
+        assert_eq!(third.to_f64(), 1.0 / 3.0);
+        let huge = Ratio::new(BigInt::from_i64(1).shl(2000), BigInt::from_i64(3).shl(1000));
+        assert_eq!(huge.to_f64(), 2f64.powi(1000) / 3.0);
+    }
+}
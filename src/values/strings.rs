@@ -0,0 +1,78 @@
+//! Immutable UTF-8 strings.
+//!
+//! A `Str` keeps its bytes in a buffer it owns, like a bignum's digits, so it is a fixed-size
+//! object that moves cheaply. Strings compare and hash by their contents, so equal strings are
+//! the same map key, and they print as they are read, quoted and escaped.
+
+use std::fmt;
+use std::hash::Hasher;
+use std::ptr::NonNull;
+
+use crate::alloc::Heap;
+use super::{Header, Object, Tag, TypeDesc, Val};
+
+#[repr(C)]
+pub struct Str {
+    header: Header,
+    bytes: *const [u8],
+}
+
+impl Str {
+    /// Allocates a string holding a copy of `s`.
+    pub fn alloc(s: &str) -> Val {
+        // The empty string owns no buffer.
+        let bytes = match s.len() {
+            0 => NonNull::<u8>::dangling().as_ptr(),
+            len => Heap::alloc(len),
+        };
+        let bytes = unsafe {
+            std::ptr::copy_nonoverlapping(s.as_ptr(), bytes, s.len());
+            std::ptr::slice_from_raw_parts(bytes as *const u8, s.len())
+        };
+        let ptr = Heap::alloc_object::<Str>();
+        unsafe { std::ptr::write(ptr, Str { header: Header::new::<Str>(), bytes }) };
+        Val::from_ptr(Tag::Object, ptr as *mut u8)
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only ever built from a `&str`.
+        unsafe { std::str::from_utf8_unchecked(&*self.bytes) }
+    }
+
+    /// The length in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+static STR: TypeDesc = TypeDesc::of::<Str>("string", Tag::Object);
+
+unsafe impl Object for Str {
+    fn desc() -> &'static TypeDesc {
+        &STR
+    }
+
+    fn buffers(&self, mark: &mut dyn FnMut(*const u8)) {
+        if !self.is_empty() {
+            mark(self.bytes as *const u8);
+        }
+    }
+
+    fn print(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Rust escapes strings the same way the reader unescapes them.
+        write!(f, "{:?}", self.as_str())
+    }
+
+    fn equals(&self, other: &Str) -> bool {
+        self.as_str() == other.as_str()
+    }
+
+    fn hash(&self, state: &mut dyn Hasher) {
+        state.write(self.as_str().as_bytes());
+        state.write_u8(0xff);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::values::{LOWTAG_BITS, Tag, Val};
+use crate::values::{LOWTAG_BITS, Header, Object, Tag, TypeDesc, Val};
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -69,11 +69,36 @@ impl std::fmt::Debug for Symbol {
     }
 }
 
+// nil and t have no cell, so these are only ever seen for interned symbols.
+#[repr(C)]
 struct Cell {
+    header: Header,
     _name: *const str,
     _value: Option<Val>
 }
 
+static CELL: TypeDesc = TypeDesc::of::<Cell>("symbol", Tag::Symbol);
+
+unsafe impl Object for Cell {
+    fn desc() -> &'static TypeDesc {
+        &CELL
+    }
+
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut Val)) {
+        if let Some(value) = &mut self._value {
+            visit(value);
+        }
+    }
+
+    fn buffers(&self, mark: &mut dyn FnMut(*const u8)) {
+        mark(self._name as *const u8)
+    }
+
+    fn print(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, ":{}", unsafe { &*self._name })
+    }
+}
+
 /// Wraps a raw str pointer and implements hash, eq by value. Used internally by SymbolTable.
 #[derive(Copy, Clone, Eq)]
 struct UnsafeStr(*const str);
@@ -127,9 +152,11 @@ impl  SymbolTable {
                 std::ptr::copy_nonoverlapping(_name.as_ptr(), name_copy_bytes, size);
                 let name_copy = std::str::from_utf8_unchecked(std::slice::from_raw_parts(name_copy_bytes as *const _, size));
 
-                let mut cell = Heap::alloc(size_of::<Cell>()) as *mut Cell;
-                std::ptr::write(cell, Cell { _name: name_copy, _value: None });
+                let mut cell = Heap::alloc_tenured::<Cell>();
+                std::ptr::write(cell, Cell { header: Header::new::<Cell>(), _name: name_copy, _value: None });
 
+                // Interned symbols are never collected.
+                Heap::pin(Symbol(cell).as_val());
                 self.table.insert(name, Symbol(cell));
             }
         }
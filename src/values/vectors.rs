@@ -1,37 +1,69 @@
 use allocator_api2::vec::Vec;
-use super::Val;
+use super::{Header, Object, Tag, TypeDesc, Val};
 use crate::alloc::Heap;
 
-pub struct Vector(Vec<Val, Heap>);
+#[repr(C)]
+pub struct Vector {
+    header: Header,
+    items: Vec<Val, Heap>,
+}
 
 impl Vector {
     pub fn new() -> Vector {
-        Vector(Vec::new_in(Heap))
+        Vector { header: Header::new::<Vector>(), items: Vec::new_in(Heap) }
     }
 
     pub fn get(&self, i: usize) -> Option<Val> {
-        (self.0).get(i).map(|item| *item)
+        self.items.get(i).map(|item| *item)
     }
 
     pub fn set(&mut self, i: usize, v: Val) -> Option<()> {
-        (self.0).get_mut(i).map(|slot| *slot = v)
+        self.items.get_mut(i).map(|slot| *slot = v)
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.items.swap(a, b)
     }
 
     pub fn push(&mut self, v: Val) {
-        (self.0).push(v);
+        self.items.push(v);
     }
 
     pub fn pop(&mut self) -> Option<Val> {
-        (self.0).pop()
+        self.items.pop()
     }
 
     pub fn len(&self) -> Val {
-        assert!((self.0).len() < i32::MAX as usize);
-        Val::from_int((self.0).len() as i32)
+        assert!(self.items.len() < i32::MAX as usize);
+        Val::from_int(self.items.len() as i32)
     }
 
     pub fn iter(&self) -> Box<dyn Iterator<Item=Val> + '_> {
-        Box::new((self.0).iter().map(|v| *v))
+        Box::new(self.items.iter().map(|v| *v))
+    }
+}
+
+static VECTOR: TypeDesc = TypeDesc::of::<Vector>("vector", Tag::Vector);
+
+unsafe impl Object for Vector {
+    fn desc() -> &'static TypeDesc {
+        &VECTOR
+    }
+
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut Val)) {
+        for slot in self.items.iter_mut() {
+            visit(slot);
+        }
+    }
+
+    fn buffers(&self, mark: &mut dyn FnMut(*const u8)) {
+        if self.items.capacity() > 0 {
+            mark(self.items.as_ptr() as *const u8);
+        }
+    }
+
+    fn print(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
     }
 }
 
@@ -0,0 +1,52 @@
+use crate::alloc::Heap;
+use super::{Header, Object, Tag, TypeDesc, Val};
+
+/// A reference that does not keep its target alive. Major collections reset it to nil
+/// once nothing else refers to the target.
+#[repr(C)]
+pub struct WeakRef {
+    header: Header,
+    target: Val,
+}
+
+impl WeakRef {
+    /// Allocates a weak reference to `target`.
+    pub fn alloc(target: Val) -> Val {
+        let weak = Heap::alloc_object::<WeakRef>();
+        unsafe { std::ptr::write(weak, WeakRef { header: Header::new::<WeakRef>(), target }) };
+        let val = Val::from_ptr(Tag::Object, weak as *mut u8);
+        Heap::register_weak(val);
+        // The nursery may have been full, leaving a mature object pointing at a young one.
+        Heap::write_barrier(val, target);
+        val
+    }
+
+    pub fn get(&self) -> Val {
+        self.target
+    }
+
+    pub fn clear(&mut self) {
+        self.target = Val::nil();
+    }
+}
+
+static WEAK_REF: TypeDesc = TypeDesc::of::<WeakRef>("weak", Tag::Object);
+
+unsafe impl Object for WeakRef {
+    fn desc() -> &'static TypeDesc {
+        &WEAK_REF
+    }
+
+    // Only minor collections see the target this way; marking skips it.
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut Val)) {
+        visit(&mut self.target);
+    }
+
+    fn print(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<weak {:?}>", self.target)
+    }
+
+    fn is_weak(&self) -> bool {
+        true
+    }
+}
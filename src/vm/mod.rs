@@ -1,12 +1,18 @@
-use crate::alloc::Heap;
+use std::cmp::Ordering;
+use std::panic::AssertUnwindSafe;
+
+use crate::alloc::{Finalizer, Heap, OutOfMemory, Site};
 use crate::global::Global;
-use crate::values::{Map, Tag, Val, Vector, Symbol};
-use crate::bytecode::{OpCode, to_op};
+use crate::values::{Map, Number, Tag, Val, Vector, Symbol};
+use crate::bytecode::{ByteCode, OpCode, to_op};
 
 #[derive(Copy, Clone)]
 struct Frame {
     ip: usize,  
     base: usize,
+    // Keeps the code being run alive. Code objects are never moved, so the
+    // pointers into its buffers below stay valid.
+    code_obj: *const ByteCode,
     constants: *const [Val],
     code: *const [u8],
     env: *const [Val],
@@ -18,85 +24,96 @@ pub struct Vm<'a> {
     fp: Frame,
     frames: Vec<Frame>,
     values: Vec<Val>,
+    finalizing: bool,
+    steps: usize,
+    verify_interval: Option<usize>,
+    profiling: bool,
 }
 
 macro_rules! primitive_math_op {
-    ($self:expr, $bin_op:tt) => {{
+    ($self:expr, $op:ident) => {{
         let right = $self.pop();
         let left = $self.pop();
-
-        if left.is_int() && right.is_int() {
-            let result = left.get_int().unwrap() $bin_op right.get_int().unwrap();
-            $self.push(Val::from_int(result));
-        } 
-                
-        else if left.is_num() && right.is_num() {
-            let result = left.get_num().unwrap() $bin_op right.get_num().unwrap();
-            $self.push(Val::from_num(result));
-        }
-                
-        else if left.is_ptr() || right.is_ptr() {
+        match (Number::of(left), Number::of(right)) {
+            (Some(left), Some(right)) => $self.push(left.$op(&right).to_val()),
             // TODO: TypeErr
-            unimplemented!();
+            _ => unimplemented!(),
         }
+    }}
+}
 
-        else {
-            let result =
-                left.get_int().map(|i| i as f64).or(left.get_num()).unwrap()
-                $bin_op right.get_int().map(|i| i as f64).or(right.get_num()).unwrap();
-            $self.push(Val::from_num(result));
+macro_rules! primitive_integer_op {
+    ($self:expr, $name:literal, $op:ident) => {{
+        let right = integer($name, $self.pop());
+        let left = integer($name, $self.pop());
+        $self.push(left.$op(&right).to_val())
+    }}
+}
+
+macro_rules! primitive_division_op {
+    ($self:expr, $operand:ident, $name:literal, $op:ident) => {{
+        let right = $operand($name, $self.pop());
+        let left = $operand($name, $self.pop());
+        match left.$op(&right) {
+            Some(result) => $self.push(result.to_val()),
+            None => panic!("Division by zero in ({} {} {})", $name, left, right),
         }
-                // TODO: TypeErr
     }}
 }
 
+// Takes an operand of `/`, the one arithmetic opcode that can fail on numbers.
+fn number(name: &str, val: Val) -> Number {
+    Number::of(val).unwrap_or_else(|| panic!("{} expects a number, got {:?}", name, val))
+}
+
+// Takes an operand of one of the integer opcodes, named after the primitive it implements.
+fn integer(name: &str, val: Val) -> Number {
+    match Number::of(val) {
+        Some(n) if n.is_integer() => n,
+        _ => panic!("{} expects an integer, got {:?}", name, val),
+    }
+}
+
 macro_rules! primitive_logic_op {
-    ($self:expr, $bin_op:tt) => {{
+    ($self:expr, $ordering:pat) => {{
         let right = $self.pop();
         let left = $self.pop();
-
-        if left.is_int() && right.is_int() {
-            let result = left.get_int().unwrap() $bin_op right.get_int().unwrap();
-            
-            $self.push(if result { Symbol::t() } else { Symbol::nil() });
-        } 
-                
-        else if left.is_num() && right.is_num() {
-            let result = left.get_num().unwrap() $bin_op right.get_num().unwrap();
-            $self.push(if result { Symbol::t() } else { Symbol::nil() });
-        }
-                
-        else if left.is_ptr() || right.is_ptr() {
+        match (Number::of(left), Number::of(right)) {
+            (Some(left), Some(right)) => {
+                let result = matches!(left.compare(&right), Some($ordering));
+                $self.push(if result { Symbol::t() } else { Symbol::nil() });
+            }
             // TODO: TypeErr
-            unimplemented!();
-        }
-
-        else {
-            let result =
-                left.get_int().map(|i| i as f64).or(left.get_num()).unwrap()
-                $bin_op right.get_int().map(|i| i as f64).or(right.get_num()).unwrap();
-            $self.push(if result { Symbol::t() } else { Symbol::nil() });
+            _ => unimplemented!(),
         }
-                // TODO: TypeErr
     }}
 }
 
 
 impl<'a> Vm<'a> {
-    pub fn new(global: &'a mut Global, entrypoint: crate::bytecode::ByteCode, initargs: &[Val], debug: bool) -> Vm <'a> {
+    /// Creates a VM that runs `entrypoint`. The VM keeps its own copy of the code, so the
+    /// entrypoint only has to be valid until then.
+    pub fn new(global: &'a mut Global, entrypoint: ByteCode, initargs: &[Val], debug: bool) -> Vm <'a> {
         let mut values = vec![];
         for val in initargs {
             values.push(*val);
         }
         let frames = vec![];
+        let code_obj = unsafe { ByteCode::alloc(&*entrypoint.consts, &*entrypoint.code) };
+        let code_obj = code_obj.ptr() as *const ByteCode;
         let initial_frame = Frame {
             ip: 0,
             base: 0,
-            constants: entrypoint.consts,
-            code: entrypoint.code,
+            code_obj,
+            constants: unsafe { (*code_obj).consts },
+            code: unsafe { (*code_obj).code },
             env: &[],
         };
-        Vm { debug, fp: initial_frame, frames, values, global }
+        Vm {
+            debug, fp: initial_frame, frames, values, global,
+            finalizing: false, steps: 0, verify_interval: Heap::verify_interval(),
+            profiling: Heap::profiling(),
+        }
     }
 
     pub fn pop(&mut self) -> Val {
@@ -113,13 +130,103 @@ impl<'a> Vm<'a> {
         return byte;
     }
 
+    // Everything the VM can reach lives on the value stack between instructions,
+    // so this is the only place a collection may happen.
+    fn safepoint(&mut self) {
+        crate::alloc::safepoint(&mut |visit| self.visit_roots(visit));
+        if !self.finalizing {
+            self.run_finalizers();
+        }
+        if let Some((path, format)) = Heap::take_dump_request() {
+            let result = Heap::dump_to_file(&mut |visit| self.visit_roots(visit), format, &path);
+            if let Err(e) = result {
+                eprintln!("Could not dump the heap to {}: {}", path.display(), e);
+            }
+        }
+        self.steps += 1;
+        if let Some(interval) = self.verify_interval && self.steps.is_multiple_of(interval) {
+            let result = Heap::verify(&mut |visit| self.visit_roots(visit));
+            if let Err(problem) = result {
+                panic!("Heap verification failed after {} steps: {}", self.steps, problem);
+            }
+        }
+    }
+
+    fn visit_roots(&mut self, visit: &mut dyn FnMut(&mut Val)) {
+        for slot in self.values.iter_mut() {
+            visit(slot);
+        }
+        for frame in self.frames.iter().chain(std::iter::once(&self.fp)) {
+            visit(&mut Val::from_ptr(Tag::Object, frame.code_obj as *mut u8));
+        }
+    }
+
+    // Runs every finalizer queued by the last sweep. Script finalizers run on this VM's stack,
+    // on top of the interrupted instruction, and are not themselves interrupted by others.
+    fn run_finalizers(&mut self) {
+        self.finalizing = true;
+        while let Some((obj, finalizer)) = Heap::next_finalizer() {
+            match finalizer {
+                Finalizer::Native(f) => f(obj),
+                Finalizer::Script(f) => {
+                    self.call(f, obj);
+                }
+            }
+        }
+        self.finalizing = false;
+    }
+
+    // Calls `f` with a single argument and runs it to completion.
+    fn call(&mut self, f: Val, arg: Val) -> Val {
+        use crate::values::Cases;
+        self.push(f);
+        self.push(arg);
+        match f.get() {
+            Cases::Function(ptr) => {
+                let depth = self.frames.len();
+                self.frames.push(self.fp);
+                self.fp.code_obj = ptr.code_obj;
+                unsafe {
+                    self.fp.code = (*ptr.code_obj).code;
+                    self.fp.constants = (*ptr.code_obj).consts;
+                }
+                self.fp.env = ptr.env;
+                self.fp.ip = 0;
+                self.fp.base = self.values.len() - 1;
+                while self.frames.len() > depth {
+                    if self.step() {
+                        panic!("VM halted inside a finalizer");
+                    }
+                }
+            }
+            Cases::NativeFn(crate::values::NativeFn(native_fn)) => {
+                let (result, _) = native_fn(&[arg], self.global);
+                self.pop();
+                self.push(result);
+            }
+            _ => {
+                // TODO: TypeError
+                unimplemented!()
+            }
+        }
+        let result = self.pop();
+        self.pop();
+        result
+    }
+
     // Returns true if machine has to suddenly halt.
     pub fn step(&mut self) -> bool {
         use crate::values::Cases;
         use OpCode::*;
+        self.safepoint();
         let op_code = unsafe { (*self.fp.code)[self.fp.ip] };
+        let op = crate::bytecode::to_op(op_code);
+        if self.profiling {
+            // Set on every step, since finalizers run at the safepoint may have moved it.
+            Heap::set_site(Some(Site { code: self.fp.code.addr(), offset: self.fp.ip, op: op.to_str() }));
+        }
         self.fp.ip += 1;
-        match crate::bytecode::to_op(op_code) {
+        match op {
             Halt => { return true; },
             Const => {
                 let i = self.take_operand();
@@ -164,9 +271,10 @@ impl<'a> Vm<'a> {
                     Cases::Function(ptr) => {
                         self.frames.push(self.fp);
                         unsafe {
-                            self.fp.code = (*(*ptr).code_obj).code;
-                            self.fp.constants = (*(*ptr).code_obj).consts;
-                            self.fp.env = (*ptr).env;
+                            self.fp.code_obj = ptr.code_obj;
+                            self.fp.code = (*ptr.code_obj).code;
+                            self.fp.constants = (*ptr.code_obj).consts;
+                            self.fp.env = ptr.env;
                         }
                         self.fp.ip = 0;
                         self.fp.base = self.values.len() - n as usize;
@@ -203,14 +311,27 @@ impl<'a> Vm<'a> {
                 let frame = self.frames.pop().unwrap();
                 self.fp = frame;
             }
-            Add => primitive_math_op!(self, +),
-            Sub => primitive_math_op!(self, -),
-            Mul => primitive_math_op!(self, *),
-            Div => primitive_math_op!(self, /),
-            Lt =>  primitive_logic_op!(self, <),
-            Gt =>  primitive_logic_op!(self, >),
-            Lte =>  primitive_logic_op!(self, <=),
-            Gte =>  primitive_logic_op!(self, >=),
+            Add => primitive_math_op!(self, add),
+            Sub => primitive_math_op!(self, sub),
+            Mul => primitive_math_op!(self, mul),
+            Div => primitive_division_op!(self, number, "/", div),
+            Quot => primitive_division_op!(self, integer, "quot", quot),
+            Rem => primitive_division_op!(self, integer, "rem", rem),
+            Mod => primitive_division_op!(self, integer, "mod", modulo),
+            BitAnd => primitive_integer_op!(self, "bit-and", bit_and),
+            BitOr => primitive_integer_op!(self, "bit-or", bit_or),
+            BitXor => primitive_integer_op!(self, "bit-xor", bit_xor),
+            Shl => primitive_integer_op!(self, "bit-shift-left", shift_left),
+            Shr => primitive_integer_op!(self, "bit-shift-right", shift_right),
+            BitNot => {
+                let n = integer("bit-not", self.pop());
+                self.push(n.bit_not().to_val())
+            }
+            Lt =>  primitive_logic_op!(self, Ordering::Less),
+            Gt =>  primitive_logic_op!(self, Ordering::Greater),
+            Lte =>  primitive_logic_op!(self, Ordering::Less | Ordering::Equal),
+            Gte =>  primitive_logic_op!(self, Ordering::Greater | Ordering::Equal),
+            NumEq => primitive_logic_op!(self, Ordering::Equal),
             Eq =>  {
                 let right = self.pop();
                 let left = self.pop();
@@ -239,6 +360,8 @@ impl<'a> Vm<'a> {
                 let map = self.pop();
                 match map.get() {
                     Cases::Map(m) => {
+                        Heap::write_barrier(map, key);
+                        Heap::write_barrier(map, val);
                         m.insert(key, val);
                     }
                     _ => {
@@ -248,13 +371,15 @@ impl<'a> Vm<'a> {
                 }
             }
             MapNew => {
-                let mut ptr = Heap::alloc(size_of::<Map>()) as *mut _;
+                let mut ptr = Heap::alloc_object::<Map>();
                 unsafe { std::ptr::write(ptr, Map::new()); }
                 self.push(Val::from_ptr(Tag::Map, ptr as *mut u8));
             }
             MapDel => {
                 let key = self.pop();
                 let map = self.pop();
+                // No barrier is needed: removing an entry stores nothing new into the map, and
+                // the value handed back lands on the stack, which is rescanned before marking ends.
                 match map.get() {
                     Cases::Map(m) => {
                         self.push(m.remove(key))
@@ -266,7 +391,7 @@ impl<'a> Vm<'a> {
                 }
             }
             VecNew => {
-                let mut ptr = Heap::alloc(size_of::<Vector>()) as *mut _;
+                let mut ptr = Heap::alloc_object::<Vector>();
                 unsafe { std::ptr::write(ptr, Vector::new()); }
                 self.push(Val::from_ptr(Tag::Vector, ptr as *mut u8));
             }
@@ -290,6 +415,7 @@ impl<'a> Vm<'a> {
                 let vec = self.pop();
                 match (vec.get(), index.get()) {
                     (Cases::Vector(v), Cases::Int(i))  if i >= 0 => {
+                        Heap::write_barrier(vec, value);
                         v.set(i as usize, value);
                     }
                     _ => {
@@ -303,6 +429,7 @@ impl<'a> Vm<'a> {
                 let vec = self.pop();
                 match vec.get() {
                     Cases::Vector(v) => {
+                        Heap::write_barrier(vec, value);
                         v.push(value);                      
                     }
                     _ => {
@@ -348,6 +475,7 @@ impl<'a> Vm<'a> {
                 let sym = self.pop();
                 match sym.get() {
                     Cases::Symbol(mut sym) => {
+                        Heap::write_barrier(sym.as_val(), val);
                         sym.set(val);
                         self.push(val);
                     }
@@ -361,7 +489,7 @@ impl<'a> Vm<'a> {
                 let i = self.take_operand();
                 let ptr = unsafe { (*self.fp.constants)[i as usize] };
                 match ptr.get() {
-                    Cases::Object(obj) => {
+                    Cases::Code(obj) => {
                         let closure = crate::Closure::new(&[], obj as *const _);
                         self.push(closure)
                     }
@@ -380,11 +508,31 @@ impl<'a> Vm<'a> {
             }
             if self.step() {
                 // TODO: collect backtrace if debugging enabled
+                if self.profiling {
+                    Heap::set_site(None);
+                }
                 return self.values.pop().expect("VM halted without a final value")
             }
         }
     }
 
+    /// Runs like `run`, but hands back an allocation that would exceed the heap limit as an error
+    /// instead of unwinding through the caller. The VM cannot be resumed afterwards.
+    pub fn try_run(&mut self) -> Result<Val, OutOfMemory> {
+        match std::panic::catch_unwind(AssertUnwindSafe(|| self.run())) {
+            Ok(val) => Ok(val),
+            Err(payload) => match payload.downcast::<OutOfMemory>() {
+                Ok(oom) => {
+                    if self.profiling {
+                        Heap::set_site(None);
+                    }
+                    Err(*oom)
+                }
+                Err(payload) => std::panic::resume_unwind(payload),
+            },
+        }
+    }
+
     pub fn print_state(&self) {
         let addr_shorthand = self.fp.code.addr();
         print!("[{:x}]", addr_shorthand);
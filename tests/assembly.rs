@@ -16,6 +16,7 @@ fn functions() {
     ", &mut global).unwrap();
 
     let mut closure = values::Closure {
+        header: values::Header::new::<values::Closure>(),
         env: &[],
         code_obj: &func_obj
     };
@@ -80,4 +81,22 @@ fn vectors() {
     ", &mut global).unwrap();
     let mut vm = defunct::Vm::new(&mut global, entrypoint, &[], false);
     println!("Result: {:?}", vm.run());
-}
\ No newline at end of file
+}
+#[test]
+fn integer_ops() {
+    let mut global = Global::new();
+    let entrypoint = assemble("
+    const -7
+    const 2
+    mod
+    const 12
+    const 10
+    bitxor
+    shl
+    bitnot
+    halt
+    ", &mut global).unwrap();
+    assert!(format!("{:?}", entrypoint).contains("bitxor"));
+    let mut vm = defunct::Vm::new(&mut global, entrypoint, &[], false);
+    assert_eq!(vm.run(), Val::from_int(-65));
+}
@@ -0,0 +1,87 @@
+mod common;
+use common::*;
+
+fn ch(c: char) -> Val {
+    Val::from_char(c)
+}
+
+#[test]
+fn characters_are_immediates() {
+    for c in ['a', '\0', 'é', '\u{1F600}', char::MAX] {
+        let val = ch(c);
+        assert_eq!(val.get_char(), Some(c));
+        assert!(!val.is_int() && !val.is_num() && !val.is_ptr());
+        assert!(matches!(val.get(), Cases::Char(got) if got == c));
+    }
+    assert_eq!(Val::from_int(97).get_char(), None);
+    assert_eq!(Val::from_num(-0.0).get_char(), None);
+}
+
+#[test]
+fn literals_read_and_print_back() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, r"\a", ch('a'));
+    eval_and_assert_eq(&mut global, r"\newline", ch('\n'));
+    eval_and_assert_eq(&mut global, r"\space", ch(' '));
+    eval_and_assert_eq(&mut global, r"\u{1F600}", ch('\u{1F600}'));
+    eval_and_assert_eq(&mut global, r"\u", ch('u'));
+    eval_and_assert_eq(&mut global, r"\(", ch('('));
+    eval_and_assert_eq(&mut global, r"\\", ch('\\'));
+    eval_and_assert_eq(&mut global, r"\é", ch('é'));
+    assert_eq!(printed(&mut global, r"[\a \( \newline \tab \u{7f} \é]"), r"[\a, \(, \newline, \tab, \u{7f}, \é]");
+    for src in [r"\ab", r"\u{110000}", r"\u{zz}", r"\"] {
+        assert!(compile(src, &mut global.st).is_err(), "{} compiled", src);
+    }
+}
+
+#[test]
+fn conversions_and_classification() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, r"(char->int \A)", int(65));
+    eval_and_assert_eq(&mut global, "(int->char 955)", ch('λ'));
+    eval_and_assert_eq(&mut global, r"(alpha? \λ)", Val::t());
+    eval_and_assert_eq(&mut global, r"(alpha? \1)", Val::nil());
+    eval_and_assert_eq(&mut global, r"(digit? \7)", Val::t());
+    eval_and_assert_eq(&mut global, r"(digit? \x)", Val::nil());
+    eval_and_assert_eq(&mut global, r"(whitespace? \tab)", Val::t());
+    eval_and_assert_eq(&mut global, r"(whitespace? \-)", Val::nil());
+}
+
+#[test]
+fn characters_compare_by_code_point() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, r"(char<? \a \b \c)", Val::t());
+    eval_and_assert_eq(&mut global, r"(char<? \a \c \b)", Val::nil());
+    eval_and_assert_eq(&mut global, r"(char>=? \z \z \a)", Val::t());
+    eval_and_assert_eq(&mut global, r"(char=? \a (int->char 97))", Val::t());
+    eval_and_assert_eq(&mut global, r"(eq \a \a)", Val::t());
+    eval_and_assert_eq(&mut global, r"(equal? [\a] [\a])", Val::t());
+    eval_and_assert_eq(&mut global, r#"(let [m {\a 1}] (map-get m (char-at "abc" 0)))"#, int(1));
+}
+
+#[test]
+fn strings_iterate_as_characters() {
+    let mut global = Global::new();
+    assert_eq!(printed(&mut global, r#"(chars "hé!")"#), r"[\h, \é, \!]");
+    eval_and_assert_eq(&mut global, r#"(char-at "naïve" 2)"#, ch('ï'));
+    assert_eq!(printed(&mut global, r#"(str \a "b" \c)"#), r#""abc""#);
+    // A tokenizer that splits on whitespace and pulls out runs of digits as numbers.
+    let src = r#"
+      (set tokenize
+        (fn [cs i token tokens]
+          (if (< i (vector-length cs))
+            (let [c (vector-get cs i)]
+              (if (whitespace? c)
+                (tokenize cs (+ i 1) "" (if (= (string-length token) 0) tokens (do (vector-push! tokens token) tokens)))
+                (tokenize cs (+ i 1) (str token c) tokens)))
+            (do (vector-push! tokens token) tokens))))"#;
+    eval(&mut global, src);
+    assert_eq!(printed(&mut global, r#"(tokenize (chars "let  x 42") 0 "" [])"#), r#"["let", "x", "42"]"#);
+}
+
+#[test]
+#[should_panic(expected = "is not a Unicode code point")]
+fn surrogates_are_not_characters() {
+    let mut global = Global::new();
+    eval(&mut global, "(int->char 55296)");
+}
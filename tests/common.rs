@@ -21,6 +21,18 @@ pub fn trace(global: &mut Global, src: &str) {
     let result = vm.run();
 }
 
+pub fn int(i: i32) -> Val {
+    Val::from_int(i)
+}
+
+pub fn num(n: f64) -> Val {
+    Val::from_num(n)
+}
+
+pub fn printed(global: &mut Global, src: &str) -> String {
+    format!("{:?}", eval(global, src))
+}
+
 pub fn eval_and_assert_eq(global: &mut Global, src: &str, test_val: Val) {
     let result = eval(global, src);
     assert_eq!(result, test_val);
@@ -0,0 +1,74 @@
+mod common;
+use common::*;
+use defunct::DumpFormat;
+
+fn dump(global: &Global, format: DumpFormat) -> String {
+    let mut out = vec![];
+    global.write_heap_dump(format, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+// The line describing the object numbered `id`.
+fn object<'a>(text: &'a str, id: &str) -> &'a str {
+    text.lines().find(|line| line.starts_with(&format!("{} ", id))).unwrap()
+}
+
+fn references(line: &str) -> Vec<&str> {
+    line.split(" -> ").nth(1).unwrap_or("").split_whitespace().collect()
+}
+
+#[test]
+fn text_dump_follows_references() {
+    let mut global = Global::new();
+    eval(&mut global, "(set keep [{:a 1} {:b 2}])");
+    let text = dump(&global, DumpFormat::Text);
+
+    let keep = text.lines().find(|line| line.contains("symbol :keep ")).unwrap();
+    let vector = object(&text, references(keep)[0]);
+    assert!(vector.contains(" vector "), "{}", vector);
+    let maps = references(vector);
+    assert_eq!(maps.len(), 2);
+    for map in maps {
+        let map = object(&text, map);
+        assert!(map.contains(" map "), "{}", map);
+        // Only the key is an object.
+        let key = object(&text, references(map)[0]);
+        assert!(key.contains(" symbol :a ") || key.contains(" symbol :b "), "{}", key);
+    }
+}
+
+#[test]
+fn text_dumps_are_stable_across_runs() {
+    // Each thread has a heap of its own, at different addresses.
+    let run = || std::thread::spawn(|| {
+        let mut global = Global::new();
+        eval(&mut global, "(set keep [{:a 1} {:b [1 2 3]}])");
+        eval(&mut global, "(gc)");
+        dump(&global, DumpFormat::Text)
+    }).join().unwrap();
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn dot_dump_marks_weak_references() {
+    let mut global = Global::new();
+    eval(&mut global, "(set target [1])");
+    eval(&mut global, "(set weak (weak-ref target))");
+    let dot = dump(&global, DumpFormat::Dot);
+    assert!(dot.starts_with("digraph heap {"));
+    assert!(dot.trim_end().ends_with('}'));
+    assert_eq!(dot.lines().filter(|line| line.contains("[style=dashed]")).count(), 1);
+}
+
+#[test]
+fn dump_heap_includes_the_stack() {
+    let mut global = Global::new();
+    let path = std::env::temp_dir().join(format!("defunct-dump-{}", std::process::id()));
+    // The vector is only ever on the stack.
+    let src = format!("(let [v [:only-on-the-stack]] (dump-heap {:?}) (vector-length v))", path.display().to_string());
+    eval_and_assert_eq(&mut global, &src, Val::from_int(1));
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let roots = references(text.lines().nth(1).unwrap());
+    assert!(roots.iter().any(|root| object(&text, root).contains(" vector ")));
+}
@@ -0,0 +1,113 @@
+mod common;
+use common::*;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+#[test]
+fn native_finalizer_runs_once_unreachable() {
+    let mut global = Global::new();
+    let handle = eval(&mut global, "(do (set handle {:fd 3}) handle)");
+    let closed = Rc::new(Cell::new(0));
+    let counter = closed.clone();
+    global.set_finalizer(handle, move |_| counter.set(counter.get() + 1));
+
+    eval(&mut global, "(gc)");
+    eval(&mut global, "(gc)");
+    assert_eq!(closed.get(), 0);
+
+    eval(&mut global, "(do (set handle 0) (gc))");
+    eval(&mut global, "(gc)");
+    assert_eq!(closed.get(), 1);
+}
+
+#[test]
+fn native_finalizer_sees_the_object_intact() {
+    let mut global = Global::new();
+    let handle = eval(&mut global, "(do (set handle {:fd 3 :path [1 2 3]}) handle)");
+    let path = global.intern("path").as_val();
+    let seen = Rc::new(Cell::new(None));
+    let last_seen = seen.clone();
+    global.set_finalizer(handle, move |obj| {
+        if let Cases::Map(map) = obj.get() && let Cases::Vector(path) = map.get(path).get() {
+            last_seen.set(Some((map.len(), path.len())));
+        }
+    });
+    eval(&mut global, "(do (set handle 0) (gc))");
+    eval(&mut global, "(gc)");
+    assert_eq!(seen.get(), Some((2, Val::from_int(3))));
+}
+
+#[test]
+fn script_finalizer_runs_after_collection() {
+    let mut global = Global::new();
+    let src = "
+    (do
+      (set closed [])
+      (set open
+        (fn [n]
+          (let [file {:fd n}]
+            (set-finalizer! file (fn [f] (vector-push! closed (map-get f :fd))))
+            n)))
+      (open 1)
+      (open 2)
+      (gc))
+    ";
+    eval(&mut global, src);
+    eval(&mut global, "(gc)");
+    eval_and_assert_eq(&mut global, "(vector-length closed)", Val::from_int(2));
+}
+
+#[test]
+fn resurrected_objects_are_not_finalized_twice() {
+    let mut global = Global::new();
+    let src = "
+    (do
+      (set count [])
+      (set obj {:id 7})
+      (set-finalizer! obj (fn [o] (vector-push! count 1) (set saved o)))
+      (set obj 0)
+      (gc))
+    ";
+    eval(&mut global, src);
+    eval(&mut global, "(gc)");
+    eval_and_assert_eq(&mut global, "(map-get saved :id)", Val::from_int(7));
+
+    eval(&mut global, "(do (set saved 0) (gc))");
+    eval(&mut global, "(gc)");
+    eval_and_assert_eq(&mut global, "(vector-length count)", Val::from_int(1));
+}
+
+// Marking finds the object dead well before an incremental sweep is done with the heap, but
+// its finalizer waits for the sweep to finish.
+#[test]
+fn finalizers_wait_for_an_incremental_sweep() {
+    let mut global = Global::new();
+    global.set_pause_budget(Some(16));
+    let handle = eval(&mut global, "(do (set handle {:fd 3}) handle)");
+    let ran_idle = Rc::new(Cell::new(None));
+    let record = ran_idle.clone();
+    global.set_finalizer(handle, move |_| record.set(Some(!defunct::testing::collecting())));
+    let src = "
+    (do
+      (set handle 0)
+      (set churn
+        (fn [n]
+          (if (< n 1)
+            (vector-length keep)
+            (do
+              (vector-push! keep {:n n :v [n n n]})
+              (churn (+ n -1))))))
+      (set keep [])
+      (churn 2000))
+    ";
+    eval(&mut global, src);
+    for _ in 0..50 {
+        if ran_idle.get().is_some() {
+            break;
+        }
+        eval(&mut global, "(do (set keep []) (churn 2000))");
+    }
+    assert_eq!(ran_idle.get(), Some(true));
+    assert!(global.heap_stats().major_collections > 0);
+}
@@ -0,0 +1,160 @@
+mod common;
+use common::*;
+
+use std::time::Duration;
+
+#[test]
+fn survivors_are_promoted() {
+    let mut global = Global::new();
+    // Each call allocates roughly 2KiB of garbage, so this runs through the nursery several times.
+    let src = "
+    (do
+      (set keep [])
+      (set churn
+        (fn [n]
+          (if (< n 1)
+            (vector-length keep)
+            (let [a {:n n :v [n n n]}
+                  b {:n n}
+                  c {:n n}
+                  m {:n n :sq (* n n)}]
+              (vector-push! keep m)
+              (set last m)
+              (churn (+ n -1))))))
+      (churn 2000))
+    ";
+    eval_and_assert_eq(&mut global, src, Val::from_int(2000));
+
+    eval_and_assert_eq(&mut global, "(map-get (vector-get keep 0) :n)", Val::from_int(2000));
+    eval_and_assert_eq(&mut global, "(map-get (vector-get keep 1999) :sq)", Val::from_int(1));
+    eval_and_assert_eq(&mut global, "(map-get last :sq)", Val::from_int(1));
+}
+
+#[test]
+fn closures_survive_promotion() {
+    let mut global = Global::new();
+    let src = "
+    (do
+      (set fns [])
+      (set make
+        (fn [n]
+          (if (< n 1)
+            (vector-length fns)
+            (let [garbage [{:a n} {:b n} {:c n}]]
+              (vector-push! fns (fn [x] (+ x 1)))
+              (make (+ n -1))))))
+      (make 2000))
+    ";
+    eval_and_assert_eq(&mut global, src, Val::from_int(2000));
+    eval_and_assert_eq(&mut global, "((vector-get fns 1234) 41)", Val::from_int(42));
+}
+
+const CHURN: &str = "
+(set churn
+  (fn [n]
+    (if (< n 1)
+      (vector-length keep)
+      (let [a {:n n :v [n n n]}
+            b {:n n}
+            c {:n n}
+            m {:n n :sq (* n n)}]
+        (vector-push! keep m)
+        (churn (+ n -1))))))
+";
+
+// Every round leaves about 4MiB of promoted garbage behind, so the spans only stay
+// small if major collections reclaim it.
+fn churn_rounds(global: &mut Global) {
+    eval(global, CHURN);
+    for _ in 0..10 {
+        eval_and_assert_eq(global, "(do (set keep []) (churn 2000))", Val::from_int(2000));
+        eval_and_assert_eq(global, "(map-get (vector-get keep 1000) :n)", Val::from_int(1000));
+    }
+    let stats = global.heap_stats();
+    assert!(stats.major_collections > 0);
+    assert!(stats.allocated < 1 << 24, "{} bytes still allocated", stats.allocated);
+}
+
+#[test]
+fn major_collection_reclaims_spans() {
+    let mut global = Global::new();
+    churn_rounds(&mut global);
+}
+
+#[test]
+fn incremental_major_collection() {
+    let mut global = Global::new();
+    global.set_pause_budget(Some(256));
+    churn_rounds(&mut global);
+}
+
+// Apart from rescanning the roots, which has to happen in one go, nothing a slice does goes
+// unbudgeted while there are no finalizers to revive.
+#[test]
+fn incremental_collection_bounds_pauses() {
+    let mut global = Global::new();
+    global.set_pause_budget(Some(256));
+    churn_rounds(&mut global);
+    let longest = global.heap_stats().longest_pause;
+    assert!(longest > 0 && longest <= 256, "longest pause did {} work", longest);
+
+    let mut global = Global::new();
+    global.set_pause_budget(None);
+    eval(&mut global, "(gc)");
+    assert!(global.heap_stats().longest_pause > 256);
+}
+
+// Slices have to fit in a frame. A shallow stack keeps the root rescan short, and a budget
+// this size keeps every slice well under a millisecond even in a debug build. Each run gets a
+// thread and a heap of its own, and the best of three is taken, so that a slice the OS happened
+// to preempt does not fail the test.
+#[test]
+fn incremental_slices_take_under_a_millisecond() {
+    let run = || std::thread::spawn(|| {
+        let mut global = Global::new();
+        global.set_pause_budget(Some(64));
+        eval(&mut global, CHURN);
+        for _ in 0..600 {
+            eval_and_assert_eq(&mut global, "(do (set keep []) (churn 50))", Val::from_int(50));
+        }
+        let stats = global.heap_stats();
+        assert!(stats.major_collections > 0);
+        stats.slowest_slice
+    }).join().unwrap();
+    let slowest = (0..3).map(|_| run()).min().unwrap();
+    assert!(slowest < Duration::from_millis(1), "slowest slice took {:?}", slowest);
+}
+
+#[test]
+fn heap_stays_consistent_through_collections() {
+    let mut global = Global::new();
+    global.set_pause_budget(Some(256));
+    global.set_verify_interval(Some(101));
+    eval(&mut global, CHURN);
+    for _ in 0..3 {
+        eval_and_assert_eq(&mut global, "(do (set keep []) (churn 500))", Val::from_int(500));
+    }
+    assert_eq!(global.verify_heap(), Ok(()));
+}
+
+fn code_objects() -> usize {
+    defunct::testing::count_objects("code")
+}
+
+#[test]
+fn redefined_functions_are_reclaimed() {
+    let mut global = Global::new();
+    eval(&mut global, "(set f (fn [x] (+ x 1)))");
+    eval(&mut global, "(gc)");
+    eval(&mut global, "(gc)");
+    let before = code_objects();
+
+    for i in 0..200 {
+        eval(&mut global, &format!("(set f (fn [x] (+ x {})))", i));
+    }
+    eval(&mut global, "(gc)");
+    eval(&mut global, "(gc)");
+    assert!(code_objects() <= before + 4, "{} code objects before, {} after", before, code_objects());
+    eval_and_assert_eq(&mut global, "(f 1)", Val::from_int(200));
+    assert_eq!(global.verify_heap(), Ok(()));
+}
@@ -0,0 +1,89 @@
+mod common;
+use common::*;
+use defunct::{HeapConfig, OutOfMemory};
+
+const MAX_HEAP: usize = 1 << 23;
+
+fn limited() -> Global {
+    Global::with_config(HeapConfig {
+        arena_size: 1 << 20,
+        max_heap: Some(MAX_HEAP),
+        ..HeapConfig::default()
+    })
+}
+
+fn try_eval(global: &mut Global, src: &str) -> Result<Val, OutOfMemory> {
+    let bytecode = compile(src, &mut global.st).expect("Could not compile bytecode.").pop().unwrap().clone();
+    let mut vm = Vm::new(global, bytecode, &[], false);
+    vm.try_run()
+}
+
+#[test]
+fn exceeding_the_limit_is_an_error() {
+    let mut global = limited();
+    // Builds a list that never stops growing.
+    let src = "
+    (do
+      (set keep 0)
+      (set hoard
+        (fn [n]
+          (if (< n 1)
+            0
+            (do
+              (set keep {:next keep :v [n n n n n n n n]})
+              (hoard (+ n -1))))))
+      (hoard 1000000))
+    ";
+    let err = try_eval(&mut global, src).expect_err("The heap grew past its limit");
+    assert_eq!(err.limit, MAX_HEAP);
+    assert!(err.in_use + err.requested > MAX_HEAP);
+    assert!(global.heap_stats().arenas <= MAX_HEAP >> 20);
+
+    // The heap is still intact, and usable once the hoard is dropped.
+    assert_eq!(global.verify_heap(), Ok(()));
+    eval(&mut global, "(set keep 0)");
+    eval(&mut global, "(gc)");
+    eval_and_assert_eq(&mut global, "(vector-length [1 2 3])", Val::from_int(3));
+}
+
+#[test]
+fn garbage_fits_under_the_limit() {
+    let mut global = limited();
+    // Allocates far more than the limit in total, but only one round's worth survives at a time.
+    let src = "
+    (do
+      (set churn
+        (fn [n]
+          (if (< n 1)
+            0
+            (let [m {:n n :v [n n n n n n n n]}]
+              (churn (+ n -1))))))
+      (set rounds
+        (fn [k]
+          (if (< k 1)
+            0
+            (do
+              (churn 500)
+              (rounds (+ k -1))))))
+      (rounds 200))
+    ";
+    assert_eq!(try_eval(&mut global, src), Ok(Val::from_int(0)));
+    assert!(global.heap_stats().major_collections > 0);
+    assert_eq!(global.verify_heap(), Ok(()));
+}
+
+#[test]
+fn initial_arenas_are_mapped_up_front() {
+    let global = Global::with_config(HeapConfig {
+        arena_size: 1 << 20,
+        initial_arenas: 3,
+        ..HeapConfig::default()
+    });
+    assert_eq!(global.heap_stats().arenas, 3);
+}
+
+#[test]
+#[should_panic(expected = "Invalid heap configuration")]
+fn arena_size_must_be_whole_pages() {
+    Global::with_config(HeapConfig { arena_size: 1000, ..HeapConfig::default() });
+}
@@ -12,7 +12,7 @@ fn basic_operations() {
       (map-get m :b))
     ";
 
-    eval_and_assert_eq(&mut global, src, Val::from_num(2.0));
+    eval_and_assert_eq(&mut global, src, Val::from_int(2));
 
     let src = "(let [m {:a 1 :b 2}] (map-length m))";
     eval_and_assert_eq(&mut global, src, Val::from_int(2));
@@ -28,5 +28,41 @@ fn basic_operations() {
     eval_and_assert_eq(&mut global, src, Val::nil());
 
     let src = "(let [m {:a 1, :b 2}] (map-remove! m :a))";
-    eval_and_assert_eq(&mut global, src, Val::from_num(1.0));
-}
\ No newline at end of file
+    eval_and_assert_eq(&mut global, src, Val::from_int(1));
+}
+#[test]
+fn equal_compares_structure() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(eq [1 2] [1 2])", Val::nil());
+    eval_and_assert_eq(&mut global, "(equal? [1 2] [1 2])", Val::t());
+    eval_and_assert_eq(&mut global, "(equal? [1 2] [2 1])", Val::nil());
+    eval_and_assert_eq(&mut global, "(equal? {:a [1] :b {:c 2}} {:b {:c 2} :a [1]})", Val::t());
+    eval_and_assert_eq(&mut global, "(equal? {:a 1} {:a 1 :b 2})", Val::nil());
+    eval_and_assert_eq(&mut global, "(let [v [1]] (vector-push! v v) (equal? v v))", Val::t());
+}
+
+#[test]
+fn collections_work_as_keys() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(let [m {}] (map-put! m [1 :a] 5) (map-get m [1 :a]))", Val::from_int(5));
+
+    // Enough entries for the hashed representation, whose keys move when they are promoted.
+    let src = "
+    (do
+      (set table {})
+      (set fill
+        (fn [n]
+          (if (< n 1)
+            (map-length table)
+            (do
+              (map-put! table [n {:n n}] n)
+              (fill (+ n -1))))))
+      (fill 100))
+    ";
+    eval_and_assert_eq(&mut global, src, Val::from_int(100));
+    eval(&mut global, "(gc)");
+    eval_and_assert_eq(&mut global, "(map-get table [40 {:n 40}])", Val::from_int(40));
+    eval_and_assert_eq(&mut global, "(map-put! table [41 {:n 41}] 0)", Val::from_int(41));
+    eval_and_assert_eq(&mut global, "(map-get table [41 {:n 41}])", Val::from_int(0));
+    eval_and_assert_eq(&mut global, "(map-length table)", Val::from_int(100));
+}
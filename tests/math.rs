@@ -0,0 +1,125 @@
+mod common;
+use common::*;
+
+#[test]
+fn exact_arguments_give_exact_results() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(sqrt 16)", int(4));
+    assert_eq!(printed(&mut global, "(sqrt 9/4)"), "3/2");
+    eval_and_assert_eq(&mut global, "(sqrt 2)", num(2f64.sqrt()));
+    eval_and_assert_eq(&mut global, "(sqrt 16.0)", num(4.0));
+    assert_eq!(printed(&mut global, "(pow 2 100)"), "1267650600228229401496703205376");
+    assert_eq!(printed(&mut global, "(pow 2/3 -2)"), "9/4");
+    eval_and_assert_eq(&mut global, "(pow -1 100000000001)", int(-1));
+    eval_and_assert_eq(&mut global, "(pow 4 1/2)", num(2.0));
+    eval_and_assert_eq(&mut global, "(pow 2.0 3)", num(8.0));
+    eval_and_assert_eq(&mut global, "(abs -5)", int(5));
+    assert_eq!(printed(&mut global, "(abs -1/2)"), "1/2");
+    eval_and_assert_eq(&mut global, "(abs -2.5)", num(2.5));
+}
+
+#[test]
+fn rounding() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(floor 7/2)", int(3));
+    eval_and_assert_eq(&mut global, "(ceil 7/2)", int(4));
+    eval_and_assert_eq(&mut global, "(round 7/2)", int(4));
+    eval_and_assert_eq(&mut global, "(round 5/2)", int(2));
+    eval_and_assert_eq(&mut global, "(truncate -7/2)", int(-3));
+    eval_and_assert_eq(&mut global, "(floor -7/2)", int(-4));
+    eval_and_assert_eq(&mut global, "(floor 3)", int(3));
+    // Floats round to floats; `int` converts.
+    eval_and_assert_eq(&mut global, "(floor -2.5)", num(-3.0));
+    eval_and_assert_eq(&mut global, "(round 2.5)", num(2.0));
+    eval_and_assert_eq(&mut global, "(int -2.7)", int(-2));
+    eval_and_assert_eq(&mut global, "(int 7/2)", int(3));
+    eval_and_assert_eq(&mut global, "(float 7/2)", num(3.5));
+    assert_eq!(printed(&mut global, "(int 100000000000000000000.5)"), "100000000000000000000");
+}
+
+#[test]
+fn min_max_and_clamp_return_their_arguments() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(min 3 1 2)", int(1));
+    eval_and_assert_eq(&mut global, "(max 3 1.5 2)", int(3));
+    eval_and_assert_eq(&mut global, "(max 1 1.5)", num(1.5));
+    assert_eq!(printed(&mut global, "(min 1 1/2 0.75)"), "1/2");
+    eval_and_assert_eq(&mut global, "(min 7)", int(7));
+    eval(&mut global, "(set nan (/ 0.0 0))");
+    eval_and_assert_eq(&mut global, "(= (max 1 nan 2) (max 1 nan 2))", Val::nil());
+    eval_and_assert_eq(&mut global, "(clamp 5 0 10)", int(5));
+    eval_and_assert_eq(&mut global, "(clamp -5 0 10)", int(0));
+    eval_and_assert_eq(&mut global, "(clamp 15 0 10.0)", num(10.0));
+}
+
+#[test]
+fn transcendental_functions_give_floats() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(exp 0)", num(1.0));
+    eval_and_assert_eq(&mut global, "(log euler)", num(1.0));
+    eval_and_assert_eq(&mut global, "(log 8 2)", num(3.0));
+    eval_and_assert_eq(&mut global, "(sin 0)", num(0.0));
+    eval_and_assert_eq(&mut global, "(cos pi)", num(-1.0));
+    eval_and_assert_eq(&mut global, "(atan 1 1)", num(std::f64::consts::FRAC_PI_4));
+    eval_and_assert_eq(&mut global, "(acos 1)", num(0.0));
+    eval_and_assert_eq(&mut global, "(acos -1)", num(std::f64::consts::PI));
+    eval_and_assert_eq(&mut global, "(sqrt 0.0)", num(0.0));
+    eval_and_assert_eq(&mut global, "(= tau (* 2 pi))", Val::t());
+    eval_and_assert_eq(&mut global, "(> infinity 18446744073709551616)", Val::t());
+}
+
+#[test]
+#[should_panic(expected = "sqrt expects a number, got :four")]
+fn non_numbers_are_an_error() {
+    let mut global = Global::new();
+    eval(&mut global, "(sqrt :four)");
+}
+
+#[test]
+#[should_panic(expected = "sqrt is undefined for -1")]
+fn negative_numbers_have_no_square_root() {
+    let mut global = Global::new();
+    eval(&mut global, "(sqrt -1)");
+}
+
+#[test]
+#[should_panic(expected = "log is undefined for -1")]
+fn negative_numbers_have_no_logarithm() {
+    let mut global = Global::new();
+    eval(&mut global, "(log -1)");
+}
+
+#[test]
+#[should_panic(expected = "log is undefined for 0")]
+fn zero_has_no_logarithm() {
+    let mut global = Global::new();
+    eval(&mut global, "(log 0)");
+}
+
+#[test]
+#[should_panic(expected = "acos is undefined for 2")]
+fn cosines_lie_between_minus_one_and_one() {
+    let mut global = Global::new();
+    eval(&mut global, "(acos 2)");
+}
+
+#[test]
+#[should_panic(expected = "Cannot raise 2 to 100000000000, the result would be too large")]
+fn exact_powers_are_bounded() {
+    let mut global = Global::new();
+    eval(&mut global, "(pow 2 100000000000)");
+}
+
+#[test]
+#[should_panic(expected = "Cannot convert inf to an integer")]
+fn infinities_have_no_integer() {
+    let mut global = Global::new();
+    eval(&mut global, "(int (/ 1.0 0))");
+}
+
+#[test]
+#[should_panic(expected = "clamp expects lo <= hi")]
+fn clamp_bounds_must_be_ordered() {
+    let mut global = Global::new();
+    eval(&mut global, "(clamp 1 10 0)");
+}
@@ -0,0 +1,248 @@
+mod common;
+use common::*;
+
+use defunct::values::FIXNUM_MAX;
+
+#[test]
+fn integer_arithmetic_stays_integer() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(+ 1 2)", int(3));
+    eval_and_assert_eq(&mut global, "(- 1 5)", int(-4));
+    eval_and_assert_eq(&mut global, "(* -3 7)", int(-21));
+    eval_and_assert_eq(&mut global, "(+ 1 2.0)", num(3.0));
+    eval_and_assert_eq(&mut global, "(* 2.5 2)", num(5.0));
+    eval_and_assert_eq(&mut global, "(* 65536 65536)", Val::from_fixnum(1 << 32).unwrap());
+    eval_and_assert_eq(&mut global, "(+ 2147483647 1)", Val::from_fixnum(1 << 31).unwrap());
+}
+
+#[test]
+fn overflow_promotes_to_bignums() {
+    let mut global = Global::new();
+    let max = eval(&mut global, "140737488355327");
+    assert_eq!(max, Val::from_fixnum(FIXNUM_MAX).unwrap());
+    assert!(!eval(&mut global, "(+ 140737488355327 1)").is_int());
+    assert_eq!(printed(&mut global, "(+ 140737488355327 1)"), "140737488355328");
+    assert_eq!(printed(&mut global, "(* -140737488355328 2)"), "-281474976710656");
+    assert_eq!(printed(&mut global, "(+ 9223372036854775807 1)"), "9223372036854775808");
+    assert_eq!(printed(&mut global, "(* (* 4294967296 4294967296) 4294967296)"), "79228162514264337593543950336");
+    // Results that fit again come back as fixnums.
+    eval_and_assert_eq(&mut global, "(- (+ 140737488355327 1) 1)", max);
+    eval_and_assert_eq(&mut global, "(/ (* 18446744073709551616 3) 18446744073709551616)", int(3));
+    eval_and_assert_eq(&mut global, "(/ 18446744073709551616 2.0)", num(9223372036854775808.0));
+}
+
+#[test]
+fn bignums_compare_and_hash_by_value() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(= 18446744073709551616 (* 4294967296 4294967296))", Val::t());
+    eval_and_assert_eq(&mut global, "(equal? 18446744073709551616 (* 4294967296 4294967296))", Val::t());
+    eval_and_assert_eq(&mut global, "(= 18446744073709551616 18446744073709551616.0)", Val::t());
+    eval_and_assert_eq(&mut global, "(< 9007199254740993 9007199254740992.0)", Val::nil());
+    eval_and_assert_eq(&mut global, "(< -18446744073709551616 -1)", Val::t());
+    eval_and_assert_eq(&mut global, "(equal? [18446744073709551616] [18446744073709551616.0])", Val::t());
+    let src = "
+      (let [m {}]
+        (map-put! m 18446744073709551616 :a)
+        (map-put! m (* 4294967296 4294967296) :b)
+        (map-put! m 18446744073709551616.0 :c)
+        (map-length m))";
+    eval_and_assert_eq(&mut global, src, int(1));
+}
+
+#[test]
+fn bignums_survive_collections() {
+    let mut global = Global::new();
+    eval(&mut global, "(set ids [(* 4294967296 4294967297) 9223372036854775807])");
+    eval(&mut global, "(gc)");
+    eval(&mut global, "(gc)");
+    assert_eq!(printed(&mut global, "(vector-get ids 0)"), "18446744078004518912");
+    assert_eq!(printed(&mut global, "(+ (vector-get ids 1) 1)"), "9223372036854775808");
+    assert_eq!(global.verify_heap(), Ok(()));
+}
+
+#[test]
+fn division_is_exact() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(/ 6 3)", int(2));
+    assert_eq!(printed(&mut global, "(/ 1 2)"), "1/2");
+    assert_eq!(printed(&mut global, "(/ 4 -6)"), "-2/3");
+    eval_and_assert_eq(&mut global, "(/ 6.0 3)", num(2.0));
+    // A float on either side divides as floats, zero or not.
+    eval_and_assert_eq(&mut global, "(/ 1.0 0)", num(f64::INFINITY));
+    eval_and_assert_eq(&mut global, "(/ -1 0.0)", num(f64::NEG_INFINITY));
+}
+
+#[test]
+#[should_panic(expected = "Division by zero in (/ 1 0)")]
+fn exact_division_by_zero_is_an_error() {
+    let mut global = Global::new();
+    eval(&mut global, "(/ 1 0)");
+}
+
+#[test]
+#[should_panic(expected = "Division by zero in (/ 1/2 0)")]
+fn exact_division_by_zero_through_the_primitive_value_is_an_error() {
+    let mut global = Global::new();
+    eval(&mut global, "(let [divide /] (divide 1/2 0))");
+}
+
+#[test]
+fn numeric_equality_crosses_kinds() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(= 1 1.0)", Val::t());
+    eval_and_assert_eq(&mut global, "(= 1 2)", Val::nil());
+    eval_and_assert_eq(&mut global, "(= 0.0 -0.0)", Val::t());
+    eval_and_assert_eq(&mut global, "(<= 1 1.0)", Val::t());
+    eval_and_assert_eq(&mut global, "(< 1 1.5)", Val::t());
+    eval_and_assert_eq(&mut global, "(> 2 1.5)", Val::t());
+    // `eq` is still identity.
+    eval_and_assert_eq(&mut global, "(eq 1 1.0)", Val::nil());
+    eval_and_assert_eq(&mut global, "(eq 1 1)", Val::t());
+    // Boxed numbers are distinct objects however equal their values.
+    eval_and_assert_eq(&mut global, "(eq 18446744073709551616 (* 4294967296 4294967296))", Val::nil());
+    eval_and_assert_eq(&mut global, "(eq 1/2 (/ 2 4))", Val::nil());
+    eval_and_assert_eq(&mut global, "(let [x 1/2] (eq x x))", Val::t());
+}
+
+#[test]
+fn nan_is_unordered_but_canonical() {
+    let mut global = Global::new();
+    eval(&mut global, "(set nan (/ 0.0 0))");
+    eval_and_assert_eq(&mut global, "(= nan nan)", Val::nil());
+    eval_and_assert_eq(&mut global, "(< nan 1)", Val::nil());
+    eval_and_assert_eq(&mut global, "(>= nan 1)", Val::nil());
+    eval_and_assert_eq(&mut global, "(eq nan (* nan 2))", Val::t());
+    assert_eq!(num(f64::NAN).bits(), num(-f64::NAN).bits());
+    assert_eq!(num(f64::from_bits(u64::MAX)).bits(), num(f64::NAN).bits());
+}
+
+#[test]
+fn numbers_are_keys_by_value() {
+    let mut global = Global::new();
+    let one = global.intern("one").as_val();
+    eval_and_assert_eq(&mut global, "(let [m {1 :one}] (map-get m 1.0))", one);
+    eval_and_assert_eq(&mut global, "(let [m {}] (map-put! m 2.0 :a) (map-put! m 2 :b) (map-length m))", int(1));
+    eval_and_assert_eq(&mut global, "(equal? [1 2] [1.0 2.0])", Val::t());
+}
+
+#[test]
+fn rationals_are_exact() {
+    let mut global = Global::new();
+    assert_eq!(printed(&mut global, "(+ 1/3 1/6)"), "1/2");
+    assert_eq!(printed(&mut global, "(* 1/10 3)"), "3/10");
+    assert_eq!(printed(&mut global, "2/4"), "1/2");
+    eval_and_assert_eq(&mut global, "4/2", int(2));
+    eval_and_assert_eq(&mut global, "(* 1/3 3)", int(1));
+    eval_and_assert_eq(&mut global, "(- 1/2 1/2)", int(0));
+    // Ten dimes make a dollar, which they do not in floats.
+    eval(&mut global, "(set sum (fn [n total] (if (< n 1) total (sum (+ n -1) (+ total 1/10)))))");
+    eval_and_assert_eq(&mut global, "(= (sum 10 0) 1)", Val::t());
+    eval_and_assert_eq(&mut global, "(= (sum 10 0.0) 1)", Val::nil());
+    eval_and_assert_eq(&mut global, "(+ 1/2 0.25)", num(0.75));
+    assert_eq!(printed(&mut global, "(/ 1 18446744073709551616)"), "1/18446744073709551616");
+}
+
+#[test]
+fn rationals_compare_across_kinds() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(= 1/2 0.5)", Val::t());
+    eval_and_assert_eq(&mut global, "(< 1/3 0.3333333333333333)", Val::nil());
+    eval_and_assert_eq(&mut global, "(< 1/3 1/2)", Val::t());
+    eval_and_assert_eq(&mut global, "(> -1/3 -1)", Val::t());
+    eval_and_assert_eq(&mut global, "(= 1/2 (/ 2 4))", Val::t());
+    eval_and_assert_eq(&mut global, "(equal? [1/2] [0.5])", Val::t());
+    let src = "
+      (let [m {}]
+        (map-put! m 1/2 :a)
+        (map-put! m 0.5 :b)
+        (map-put! m (/ 3 6) :c)
+        (map-length m))";
+    eval_and_assert_eq(&mut global, src, int(1));
+}
+
+#[test]
+fn rational_intrinsics() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(numerator 6/4)", int(3));
+    eval_and_assert_eq(&mut global, "(denominator 6/4)", int(2));
+    eval_and_assert_eq(&mut global, "(numerator -5)", int(-5));
+    eval_and_assert_eq(&mut global, "(denominator -5)", int(1));
+    eval_and_assert_eq(&mut global, "(float 1/4)", num(0.25));
+    eval_and_assert_eq(&mut global, "(float 3)", num(3.0));
+    assert_eq!(printed(&mut global, "(denominator (/ 1 18446744073709551616))"), "18446744073709551616");
+    eval(&mut global, "(set third [(/ 1 3)])");
+    eval(&mut global, "(gc)");
+    eval(&mut global, "(gc)");
+    assert_eq!(printed(&mut global, "(vector-get third 0)"), "1/3");
+    assert_eq!(global.verify_heap(), Ok(()));
+}
+
+#[test]
+fn integer_division_and_remainders() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(quot 7 2)", int(3));
+    eval_and_assert_eq(&mut global, "(quot -7 2)", int(-3));
+    eval_and_assert_eq(&mut global, "(rem -7 2)", int(-1));
+    eval_and_assert_eq(&mut global, "(rem 7 -2)", int(1));
+    eval_and_assert_eq(&mut global, "(mod -7 2)", int(1));
+    eval_and_assert_eq(&mut global, "(mod 7 -2)", int(-1));
+    eval_and_assert_eq(&mut global, "(mod 36893488147419103232 10)", int(2));
+    assert_eq!(printed(&mut global, "(quot 36893488147419103232 -3)"), "-12297829382473034410");
+}
+
+#[test]
+#[should_panic(expected = "Division by zero")]
+fn integer_division_by_zero_is_an_error() {
+    let mut global = Global::new();
+    eval(&mut global, "(rem 1 0)");
+}
+
+#[test]
+#[should_panic(expected = "Division by zero in (quot 1 0)")]
+fn quotient_by_zero_is_an_error() {
+    let mut global = Global::new();
+    eval(&mut global, "(quot 1 0)");
+}
+
+#[test]
+#[should_panic(expected = "mod expects an integer, got 5.5f")]
+fn integer_division_of_floats_is_an_error() {
+    let mut global = Global::new();
+    eval(&mut global, "(mod 5.5 3)");
+}
+
+#[test]
+#[should_panic(expected = "bit-and expects an integer, got 1/2")]
+fn bitwise_operations_on_ratios_are_an_error() {
+    let mut global = Global::new();
+    eval(&mut global, "(bit-and 1/2 3)");
+}
+
+#[test]
+#[should_panic(expected = "Cannot shift by 100000000000, the result would be too large")]
+fn shifts_are_bounded() {
+    let mut global = Global::new();
+    eval(&mut global, "(bit-shift-left 1 100000000000)");
+}
+
+#[test]
+fn bitwise_operations() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(bit-and 12 10)", int(8));
+    eval_and_assert_eq(&mut global, "(bit-or 12 10)", int(14));
+    eval_and_assert_eq(&mut global, "(bit-xor 12 10)", int(6));
+    eval_and_assert_eq(&mut global, "(bit-not 0)", int(-1));
+    // Negative numbers act as two's complement with infinitely many sign bits.
+    eval_and_assert_eq(&mut global, "(bit-and -1 255)", int(255));
+    eval_and_assert_eq(&mut global, "(bit-and -256 511)", int(256));
+    eval_and_assert_eq(&mut global, "(bit-shift-right -5 1)", int(-3));
+    eval_and_assert_eq(&mut global, "(bit-shift-left 1 -1)", int(0));
+    eval_and_assert_eq(&mut global, "(bit-shift-left 0 100000000000)", int(0));
+    eval_and_assert_eq(&mut global, "(bit-shift-right 1 100000000000)", int(0));
+    assert_eq!(printed(&mut global, "(bit-shift-left 1 64)"), "18446744073709551616");
+    eval_and_assert_eq(&mut global, "(bit-shift-right (bit-shift-left 3 100) 99)", int(6));
+    eval_and_assert_eq(&mut global, "(bit-and (bit-shift-left -1 70) 18446744073709551615)", int(0));
+    // A 32-bit FNV-1a step, as used when hashing packets.
+    let src = "(bit-and (* (bit-xor 2166136261 97) 16777619) 4294967295)";
+    eval_and_assert_eq(&mut global, src, Val::from_fixnum(((2166136261u32 ^ 97).wrapping_mul(16777619)) as i64).unwrap());
+}
@@ -0,0 +1,87 @@
+mod common;
+use common::*;
+
+const REDUCE: &str = "
+(set reduce
+  (fn [f acc v i]
+    (if (< i (vector-length v))
+      (reduce f (f acc (vector-get v i)) v (+ i 1))
+      acc)))
+";
+
+#[test]
+fn arithmetic_takes_any_number_of_operands() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(+)", int(0));
+    eval_and_assert_eq(&mut global, "(*)", int(1));
+    eval_and_assert_eq(&mut global, "(+ 5)", int(5));
+    eval_and_assert_eq(&mut global, "(+ 1 2 3 4)", int(10));
+    eval_and_assert_eq(&mut global, "(* 1 2 3 4)", int(24));
+    eval_and_assert_eq(&mut global, "(- 10 1 2 3)", int(4));
+    eval_and_assert_eq(&mut global, "(- 7)", int(-7));
+    eval_and_assert_eq(&mut global, "(/ 60 2 3)", int(10));
+    assert_eq!(printed(&mut global, "(/ 4)"), "1/4");
+    eval_and_assert_eq(&mut global, "(bit-or 1 2 4 8)", int(15));
+    eval_and_assert_eq(&mut global, "(bit-and 7)", int(7));
+    eval_and_assert_eq(&mut global, "(let [x 3] (+ x x (* x x) x))", int(18));
+}
+
+#[test]
+fn comparisons_chain() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, "(< 1 2 3)", Val::t());
+    eval_and_assert_eq(&mut global, "(< 1 3 2)", Val::nil());
+    eval_and_assert_eq(&mut global, "(< 3 1 2)", Val::nil());
+    eval_and_assert_eq(&mut global, "(<= 1 1 2 2)", Val::t());
+    eval_and_assert_eq(&mut global, "(>= 3 2 2 1)", Val::t());
+    eval_and_assert_eq(&mut global, "(> 3 2 1 1)", Val::nil());
+    eval_and_assert_eq(&mut global, "(= 1 1.0 2/2)", Val::t());
+    eval_and_assert_eq(&mut global, "(eq :a :a :b)", Val::nil());
+    eval_and_assert_eq(&mut global, "(< 5)", Val::t());
+    // Every operand is evaluated once, even past a comparison that fails.
+    eval(&mut global, "(set calls 0)");
+    eval(&mut global, "(set count! (fn [x] (do (set calls (+ calls 1)) x)))");
+    eval_and_assert_eq(&mut global, "(< (count! 2) (count! 1) (count! 3) (count! 4))", Val::nil());
+    eval_and_assert_eq(&mut global, "calls", int(4));
+    // Chains work inside functions, whose arguments take up the first slots.
+    eval(&mut global, "(set between? (fn [lo x hi] (let [y x] (<= lo y hi))))");
+    eval_and_assert_eq(&mut global, "(between? 1 5 10)", Val::t());
+    eval_and_assert_eq(&mut global, "(between? 1 50 10)", Val::nil());
+}
+
+#[test]
+fn primitives_are_values() {
+    let mut global = Global::new();
+    eval(&mut global, REDUCE);
+    eval_and_assert_eq(&mut global, "(reduce + 0 [1 2 3 4] 0)", int(10));
+    eval_and_assert_eq(&mut global, "(reduce * 1 [1 2 3 4] 0)", int(24));
+    eval_and_assert_eq(&mut global, "(reduce bit-xor 0 [12 10] 0)", int(6));
+    assert_eq!(printed(&mut global, "(reduce / 1 [2 3] 0)"), "1/6");
+    eval_and_assert_eq(&mut global, "(let [f -] (f 3))", int(-3));
+    eval_and_assert_eq(&mut global, "(let [f +] (f 1 2 3))", int(6));
+    eval_and_assert_eq(&mut global, "(let [f <] (f 1 2 3))", Val::t());
+    eval_and_assert_eq(&mut global, "(let [f mod] (f -7 2))", int(1));
+    eval_and_assert_eq(&mut global, "(let [f eq] (f :a :a))", Val::t());
+}
+
+#[test]
+#[should_panic(expected = "+ expects a number, got :a")]
+fn primitive_values_check_their_arguments() {
+    let mut global = Global::new();
+    eval(&mut global, "(let [f +] (f 1 :a))");
+}
+
+#[test]
+#[should_panic(expected = "bit-or expects an integer, got 1.5f")]
+fn integer_primitive_values_reject_other_numbers() {
+    let mut global = Global::new();
+    eval(&mut global, "(let [f bit-or] (f 1 1.5))");
+}
+
+#[test]
+fn wrong_arity_is_rejected() {
+    let mut global = Global::new();
+    for src in ["(-)", "(/)", "(<)", "(mod 1)", "(bit-not 1 2)", "(quot 1 2 3)"] {
+        assert!(compile(src, &mut global.st).is_err(), "{} compiled", src);
+    }
+}
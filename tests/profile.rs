@@ -0,0 +1,77 @@
+mod common;
+use common::*;
+use defunct::{AllocationProfile, Tally};
+
+fn profile(global: &mut Global, src: &str) -> AllocationProfile {
+    global.start_allocation_profile();
+    eval(global, src);
+    global.stop_allocation_profile().expect("The profile was started")
+}
+
+// Allocations of `type_name` charged to the instruction `op`, across all code objects.
+fn at(profile: &AllocationProfile, type_name: &str, op: &str) -> Tally {
+    let mut tally = Tally::default();
+    for (site, name, t) in profile.entries() {
+        if name == type_name && site.is_some_and(|site| site.op == op) {
+            tally.count += t.count;
+            tally.bytes += t.bytes;
+        }
+    }
+    tally
+}
+
+#[test]
+fn allocations_are_charged_to_their_instruction() {
+    let mut global = Global::new();
+    eval(&mut global, "
+    (set make
+      (fn [n]
+        (if (< n 1)
+          0
+          (do
+            {:a n}
+            [n n n n n n n n n n]
+            (make (+ n -1))))))
+    ");
+    let profile = profile(&mut global, "(make 100)");
+
+    assert_eq!(at(&profile, "map", "mapnew").count, 100);
+    assert_eq!(at(&profile, "vector", "vecnew").count, 100);
+    // Growing a vector allocates a new buffer for its items.
+    assert!(at(&profile, "buffer", "vecpush").count >= 100);
+
+    // Both maps and vectors come from one site each, inside `make`.
+    let maps: Vec<_> = profile.entries().into_iter().filter(|(_, name, _)| *name == "map").collect();
+    assert_eq!(maps.len(), 1);
+    let (site, _, tally) = maps[0];
+    let site = site.unwrap();
+    assert_eq!(profile.by_site().iter().find(|(s, _)| *s == Some(site)).unwrap().1, tally);
+
+    let by_type = profile.by_type();
+    let map = by_type.iter().find(|(name, _)| *name == "map").unwrap().1;
+    assert_eq!(map, tally);
+    assert_eq!(map.bytes % map.count, 0);
+    assert_eq!(profile.total().count, by_type.iter().map(|(_, t)| t.count).sum::<usize>());
+}
+
+#[test]
+fn intrinsics_are_charged_to_their_call() {
+    let mut global = Global::new();
+    let profile = profile(&mut global, "(do (weak-map) (fn [x] x))");
+    assert_eq!(at(&profile, "map", "call").count, 1);
+    assert_eq!(at(&profile, "fn", "closure").count, 1);
+
+    let report = profile.to_string();
+    assert!(report.contains(" map at code@"), "{}", report);
+    assert!(report.contains(" call\n"), "{}", report);
+}
+
+#[test]
+fn nothing_is_counted_unless_profiling() {
+    let mut global = Global::new();
+    assert!(global.stop_allocation_profile().is_none());
+    eval(&mut global, "[1 2 3]");
+    let profile = profile(&mut global, "0");
+    assert_eq!(at(&profile, "vector", "vecnew").count, 0);
+    assert!(global.stop_allocation_profile().is_none());
+}
@@ -0,0 +1,50 @@
+mod common;
+use common::*;
+
+const DRAW: &str = "(set draw (fn [k acc] (if (< k 1) acc (do (vector-push! acc (rand-int 1000)) (draw (- k 1) acc)))))";
+
+#[test]
+fn seeding_reproduces_the_sequence() {
+    let mut global = Global::new();
+    eval(&mut global, DRAW);
+    eval(&mut global, "(seed! 42)");
+    let first = printed(&mut global, "[(rand) (draw 10 []) (shuffle! [1 2 3 4 5 6 7 8]) (rand-nth [:a :b :c :d])]");
+    eval(&mut global, "(seed! 42)");
+    let again = printed(&mut global, "[(rand) (draw 10 []) (shuffle! [1 2 3 4 5 6 7 8]) (rand-nth [:a :b :c :d])]");
+    assert_eq!(first, again);
+    eval(&mut global, "(seed! 43)");
+    let other = printed(&mut global, "[(rand) (draw 10 []) (shuffle! [1 2 3 4 5 6 7 8]) (rand-nth [:a :b :c :d])]");
+    assert_ne!(first, other);
+
+    // The host can seed it too.
+    global.seed_random(42);
+    assert_eq!(printed(&mut global, "[(rand) (draw 10 []) (shuffle! [1 2 3 4 5 6 7 8]) (rand-nth [:a :b :c :d])]"), first);
+}
+
+#[test]
+fn draws_stay_in_range() {
+    let mut global = Global::new();
+    eval(&mut global, "(set check (fn [k] (if (< k 1) :ok (let [x (rand) i (rand-int 3)] (if (<= 0 x) (if (< x 1) (if (<= 0 i 2) (check (- k 1)) :int) :float) :float)))))");
+    let ok = global.intern("ok").as_val();
+    eval_and_assert_eq(&mut global, "(check 1000)", ok);
+    eval_and_assert_eq(&mut global, "(rand-int 1)", int(0));
+    eval_and_assert_eq(&mut global, "(rand-nth [7])", int(7));
+}
+
+#[test]
+fn shuffling_permutes_in_place() {
+    let mut global = Global::new();
+    eval(&mut global, "(set v [1 2 3 4 5 6 7 8 9 10])");
+    eval_and_assert_eq(&mut global, "(eq (shuffle! v) v)", Val::t());
+    eval(&mut global, "(set sum (fn [i acc] (if (< i 10) (sum (+ i 1) (+ acc (vector-get v i))) acc)))");
+    eval_and_assert_eq(&mut global, "(sum 0 0)", int(55));
+    eval_and_assert_eq(&mut global, "(vector-length v)", int(10));
+    eval_and_assert_eq(&mut global, "(vector-length (shuffle! []))", int(0));
+}
+
+#[test]
+#[should_panic(expected = "rand-int expects a positive integer")]
+fn rand_int_needs_a_positive_bound() {
+    let mut global = Global::new();
+    eval(&mut global, "(rand-int 0)");
+}
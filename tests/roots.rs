@@ -0,0 +1,74 @@
+mod common;
+use common::*;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+fn items(val: Val) -> Vec<Val> {
+    match val.get() {
+        Cases::Vector(v) => (0..3).map(|i| v.get(i).unwrap()).collect(),
+        _ => panic!("Expected a vector, got {:?}", val),
+    }
+}
+
+#[test]
+fn roots_follow_their_object_when_it_moves() {
+    let mut global = Global::new();
+    let young = eval(&mut global, "[1 2 3]");
+    let root = global.root(young);
+
+    eval(&mut global, "(gc)");
+    eval(&mut global, "(do [4 5 6] (gc))");
+    assert_ne!(root.get().bits(), young.bits());
+    assert_eq!(items(root.get()), vec![Val::from_int(1), Val::from_int(2), Val::from_int(3)]);
+    assert_eq!(global.verify_heap(), Ok(()));
+}
+
+#[test]
+fn dropping_a_root_lets_its_object_go() {
+    let mut global = Global::new();
+    let obj = eval(&mut global, "{:fd 3}");
+    let root = global.root(obj);
+    let closed = Rc::new(Cell::new(false));
+    let flag = closed.clone();
+    global.set_finalizer(root.get(), move |_| flag.set(true));
+
+    let copy = root.clone();
+    drop(root);
+    eval(&mut global, "(gc)");
+    eval(&mut global, "(gc)");
+    assert!(!closed.get());
+
+    drop(copy);
+    eval(&mut global, "(gc)");
+    eval(&mut global, "(gc)");
+    assert!(closed.get());
+}
+
+#[test]
+fn handles_last_as_long_as_their_scope() {
+    let mut global = Global::new();
+    let scope = global.handle_scope();
+    let vector = scope.handle(eval(&mut global, "[1 2 3]"));
+    {
+        let inner = global.handle_scope();
+        let map = inner.handle(eval(&mut global, "{:a 1}"));
+        eval(&mut global, "(gc)");
+        assert!(matches!(map.get().get(), Cases::Map(m) if m.len() == 1));
+    }
+    eval(&mut global, "(gc)");
+    assert_eq!(items(vector.get())[2], Val::from_int(3));
+    vector.set(Val::from_int(0));
+    assert_eq!(vector.get(), Val::from_int(0));
+    assert_eq!(global.verify_heap(), Ok(()));
+}
+
+#[test]
+#[should_panic(expected = "Handle scopes dropped out of order")]
+fn scopes_must_nest() {
+    let mut global = Global::new();
+    let outer = global.handle_scope();
+    let inner = global.handle_scope();
+    drop(outer);
+    drop(inner);
+}
@@ -9,26 +9,26 @@ fn _let() {
     (let [x 1
           y 2]
       (+ x y))
-    ", Val::from_num(3.0f64));
+    ", Val::from_int(3));
 
     eval_and_assert_eq(&mut global, "
     (let [x 40
           y 50]
       (let [z 100]
         (+ (* x y) z))))
-    ", Val::from_num(2100.0f64));
+    ", Val::from_int(2100));
 
     eval_and_assert_eq(&mut global, "
     (let [x 1]
       (let [x 2]
         (let [x 3]
           x)))
-    ", Val::from_num(3.0f64));
+    ", Val::from_int(3));
 
     eval_and_assert_eq(&mut global, "
     (let []
       (* 100 100))
-    ", Val::from_num(10000.0f64));
+    ", Val::from_int(10000));
 
     eval_and_assert_eq(&mut global, "
     (let []
@@ -36,7 +36,16 @@ fn _let() {
       (+ 5 2)
       (/ 200 2)
       (* 300 3))
-    ", Val::from_num(900.0f64))
+    ", Val::from_int(900))
+}
+
+// The jump out of the first branch must land on whatever follows the if, not inside it.
+#[test]
+fn if_continues_after_either_branch() {
+  let mut global = Global::new();
+  eval_and_assert_eq(&mut global, "(+ (if (< 1 2) 10 20) 5)", Val::from_int(15));
+  eval_and_assert_eq(&mut global, "(+ (if (> 1 2) 10 20) 5)", Val::from_int(25));
+  eval_and_assert_eq(&mut global, "(let [x (if (< 1 2) [1] [2])] (vector-get x 0))", Val::from_int(1));
 }
 
 #[test]
@@ -57,7 +66,7 @@ fn _if() {
        (if (<= x 25.0)
          1
          2)))
-  ", Val::from_num(2.0))
+  ", Val::from_int(2))
 }
 
 #[test]
@@ -87,13 +96,13 @@ fn _fn() {
         f (fn [x4] (* 10 x4))]
     (f x2))
   ";
-  eval_and_assert_eq(&mut global, src, Val::from_num(20.0));
+  eval_and_assert_eq(&mut global, src, Val::from_int(20));
 
   let src = " (let [f (fn [] 20)] (f)) ";
-  eval_and_assert_eq(&mut global, src, Val::from_num(20.0));
+  eval_and_assert_eq(&mut global, src, Val::from_int(20));
 
   let src = " (let [f (fn [] (fn [] 100))] ((f))) ";
-  eval_and_assert_eq(&mut global, src, Val::from_num(100.0));
+  eval_and_assert_eq(&mut global, src, Val::from_int(100));
 
 }
 
@@ -104,7 +113,7 @@ fn _do() {
   eval_and_assert_eq(&mut global, src, Val::nil());
 
   let src = "(do 1 2 3 5)";
-  eval_and_assert_eq(&mut global, src, Val::from_num(5.0));
+  eval_and_assert_eq(&mut global, src, Val::from_int(5));
 
   let src = "
   (do
@@ -116,7 +125,7 @@ fn _do() {
     (do 1))
   ";
   
-  eval_and_assert_eq(&mut global, src, Val::from_num(1.0))
+  eval_and_assert_eq(&mut global, src, Val::from_int(1))
 }
 
 #[test]
@@ -161,7 +170,7 @@ fn _return() {
   (f 20))
   ";
 
-  eval_and_assert_eq(&mut global, src, Val::from_num(120.00));
+  eval_and_assert_eq(&mut global, src, Val::from_int(120));
 
   let src = "
   (let [f (fn [] (fn [x]
@@ -170,5 +179,5 @@ fn _return() {
     ((f) 1000))
   ";
 
-  eval_and_assert_eq(&mut global, src, Val::from_num(1001.00));
+  eval_and_assert_eq(&mut global, src, Val::from_int(1001));
 }
\ No newline at end of file
@@ -0,0 +1,191 @@
+mod common;
+use common::*;
+
+fn text(val: Val) -> String {
+    match val.get() {
+        Cases::Str(s) => s.as_str().to_string(),
+        _ => panic!("Expected a string, got {:?}", val),
+    }
+}
+
+#[test]
+fn literals_read_with_escapes() {
+    let mut global = Global::new();
+    assert_eq!(text(eval(&mut global, r#""hello, world""#)), "hello, world");
+    assert_eq!(text(eval(&mut global, r#""""#)), "");
+    assert_eq!(text(eval(&mut global, r#""tab\tquote\"backslash\\""#)), "tab\tquote\"backslash\\");
+    assert_eq!(text(eval(&mut global, r#""line\nbreak""#)), "line\nbreak");
+    assert_eq!(text(eval(&mut global, r#""\u{1F600} \u{e9}""#)), "\u{1F600} \u{e9}");
+    assert_eq!(text(eval(&mut global, "\"two\nlines\"")), "two\nlines");
+    assert_eq!(text(eval(&mut global, r#"(let [s "café"] s)"#)), "café");
+}
+
+#[test]
+fn bad_literals_are_rejected() {
+    let mut global = Global::new();
+    for src in [r#""unterminated"#, r#""\q""#, r#""\u{110000}""#, r#""\u{zz}""#, r#""\u41""#] {
+        assert!(compile(src, &mut global.st).is_err(), "{} compiled", src);
+    }
+}
+
+#[test]
+fn strings_print_as_they_are_read() {
+    let mut global = Global::new();
+    assert_eq!(printed(&mut global, r#""plain""#), r#""plain""#);
+    assert_eq!(printed(&mut global, r#""a\"b\nc\\""#), r#""a\"b\nc\\""#);
+    assert_eq!(printed(&mut global, r#"["x" {"k" "v"}]"#), r#"["x", {"k" "v"}]"#);
+}
+
+#[test]
+fn strings_are_equal_by_contents() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, r#"(equal? "abc" "abc")"#, Val::t());
+    eval_and_assert_eq(&mut global, r#"(equal? "abc" "abd")"#, Val::nil());
+    eval_and_assert_eq(&mut global, r#"(equal? ["a" "b"] ["a" "b"])"#, Val::t());
+    eval_and_assert_eq(&mut global, r#"(equal? "1" 1)"#, Val::nil());
+    let src = r#"
+      (let [m {}]
+        (map-put! m "key" 1)
+        (map-put! m "key" 2)
+        (map-put! m "other" 3)
+        [(map-length m) (map-get m "key")])"#;
+    eval_and_assert_eq(&mut global, &format!("(equal? {} [2 2])", src), Val::t());
+}
+
+#[test]
+fn strings_survive_collections() {
+    let mut global = Global::new();
+    eval(&mut global, r#"(set names ["first" "second" ""])"#);
+    eval(&mut global, "(gc)");
+    eval(&mut global, "(gc)");
+    assert_eq!(printed(&mut global, "(vector-get names 1)"), r#""second""#);
+    assert_eq!(printed(&mut global, "(vector-get names 2)"), r#""""#);
+    assert_eq!(global.verify_heap(), Ok(()));
+}
+
+// Strings past the largest size class get an allocation of their own.
+#[test]
+fn long_strings_outgrow_the_size_classes() {
+    let mut global = Global::new();
+    let line = "x".repeat(40000);
+    eval(&mut global, &format!(r#"(set long (str "{}" "{}"))"#, line, line));
+    eval(&mut global, "(gc)");
+    assert_eq!(printed(&mut global, "long"), format!(r#""{}{}""#, line, line));
+    eval_and_assert_eq(&mut global, "(string-length long)", int(80000));
+    assert_eq!(global.verify_heap(), Ok(()));
+}
+
+#[test]
+fn str_writes_values_out() {
+    let mut global = Global::new();
+    assert_eq!(text(eval(&mut global, r#"(str "n = " 42 ", x = " 2.5 " " 1/3 " " :done)"#)), "n = 42, x = 2.5 1/3 :done");
+    assert_eq!(text(eval(&mut global, "(str)")), "");
+    assert_eq!(text(eval(&mut global, r#"(str ["a" 1])"#)), r#"["a", 1]"#);
+}
+
+#[test]
+fn lengths_and_substrings_count_characters() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, r#"(string-length "naïve")"#, int(5));
+    eval_and_assert_eq(&mut global, r#"(string-length "naïve" :bytes)"#, int(6));
+    eval_and_assert_eq(&mut global, r#"(string-length "")"#, int(0));
+    assert_eq!(text(eval(&mut global, r#"(substring "naïve" 1 3)"#)), "aï");
+    assert_eq!(text(eval(&mut global, r#"(substring "naïve" 2)"#)), "ïve");
+    assert_eq!(text(eval(&mut global, r#"(substring "abc" 3 3)"#)), "");
+    eval_and_assert_eq(&mut global, r#"(index-of "naïve" "ve")"#, int(3));
+    eval_and_assert_eq(&mut global, r#"(index-of "naïve" "x")"#, Val::nil());
+}
+
+#[test]
+fn splitting_and_joining() {
+    let mut global = Global::new();
+    assert_eq!(printed(&mut global, r#"(split "a,b,,c" ",")"#), r#"["a", "b", "", "c"]"#);
+    assert_eq!(printed(&mut global, r#"(split "hé" "")"#), r#"["h", "é"]"#);
+    assert_eq!(text(eval(&mut global, r#"(join ["a" 1 :b] ", ")"#)), "a, 1, :b");
+    assert_eq!(text(eval(&mut global, r#"(join ["x" "y"])"#)), "xy");
+    assert_eq!(text(eval(&mut global, r#"(join (split "1 2 3" " ") "+")"#)), "1+2+3");
+}
+
+#[test]
+fn transforming_and_searching() {
+    let mut global = Global::new();
+    assert_eq!(text(eval(&mut global, "(trim \"  padded\\n\\t\")")), "padded");
+    assert_eq!(text(eval(&mut global, r#"(upper "straße")"#)), "STRASSE");
+    assert_eq!(text(eval(&mut global, r#"(lower "ÉCOLE")"#)), "école");
+    assert_eq!(text(eval(&mut global, r#"(replace "a-b-c" "-" "::")"#)), "a::b::c");
+    eval_and_assert_eq(&mut global, r#"(starts-with? "report.txt" "report")"#, Val::t());
+    eval_and_assert_eq(&mut global, r#"(ends-with? "report.txt" ".csv")"#, Val::nil());
+}
+
+#[test]
+fn numbers_parse_as_literals_do() {
+    let mut global = Global::new();
+    eval_and_assert_eq(&mut global, r#"(parse-number "42")"#, int(42));
+    eval_and_assert_eq(&mut global, r#"(parse-number " -7 ")"#, int(-7));
+    eval_and_assert_eq(&mut global, r#"(parse-number "2.5")"#, Val::from_num(2.5));
+    assert_eq!(printed(&mut global, r#"(parse-number "6/4")"#), "3/2");
+    assert_eq!(printed(&mut global, r#"(parse-number "18446744073709551616")"#), "18446744073709551616");
+    for src in [r#""""#, r#""abc""#, r#""1/0""#, r#""inf""#, r#""12abc""#, r#""-""#] {
+        eval_and_assert_eq(&mut global, &format!("(parse-number {})", src), Val::nil());
+    }
+}
+
+#[test]
+fn format_fills_placeholders() {
+    let mut global = Global::new();
+    assert_eq!(text(eval(&mut global, r#"(format "{} of {}" 3 10)"#)), "3 of 10");
+    assert_eq!(text(eval(&mut global, r#"(format "{1} before {0}, {1} again" "a" "b")"#)), "b before a, b again");
+    assert_eq!(text(eval(&mut global, r#"(format "{{literal}} {}" "x")"#)), "{literal} x");
+    assert_eq!(text(eval(&mut global, r#"(format "| {} | {} |" "name" 1/2)"#)), "| name | 1/2 |");
+    assert_eq!(text(eval(&mut global, r#"(format "no placeholders")"#)), "no placeholders");
+}
+
+// A report built up line by line soon outgrows the largest size class.
+#[test]
+fn reports_can_outgrow_the_size_classes() {
+    let mut global = Global::new();
+    let src = r#"
+    (do
+      (set lines [])
+      (set fill
+        (fn [n]
+          (if (< n 1)
+            (vector-length lines)
+            (do
+              (vector-push! lines (format "| {} | {} |" n (replace "item-?" "?" (str n))))
+              (fill (+ n -1))))))
+      (fill 5000)
+      (set report (join lines "\n"))
+      (string-length report))
+    "#;
+    let expected: Vec<String> = (1..=5000).rev().map(|n| format!("| {} | item-{} |", n, n)).collect();
+    let expected = expected.join("\n");
+    assert!(expected.len() > 32768);
+    eval_and_assert_eq(&mut global, src, int(expected.len() as i32));
+    assert_eq!(text(eval(&mut global, "report")), expected);
+    let wrapped = text(eval(&mut global, r#"(format "begin\n{}\nend" report)"#));
+    assert_eq!(wrapped, format!("begin\n{}\nend", expected));
+    eval(&mut global, "(gc)");
+    assert_eq!(global.verify_heap(), Ok(()));
+}
+
+#[test]
+#[should_panic(expected = "has no argument 1")]
+fn format_needs_enough_arguments() {
+    let mut global = Global::new();
+    eval(&mut global, r#"(format "{} and {}" 1)"#);
+}
+
+#[test]
+#[should_panic(expected = "substring 2..5 is out of bounds for a string of length 3")]
+fn substring_checks_bounds() {
+    let mut global = Global::new();
+    eval(&mut global, r#"(substring "abc" 2 5)"#);
+}
+
+#[test]
+#[should_panic(expected = "upper expects a string, got :name")]
+fn string_functions_need_strings() {
+    let mut global = Global::new();
+    eval(&mut global, "(upper :name)");
+}
@@ -13,7 +13,7 @@ fn basic_operations() {
       (vector-push! v 4)
       (vector-get v 2))
     ";
-    eval_and_assert_eq(&mut global, src, Val::from_num(2.0));
+    eval_and_assert_eq(&mut global, src, Val::from_int(2));
 
     let src = "(let [v [1 2 3 4 5]] (vector-length v))";
     eval_and_assert_eq(&mut global, src, Val::from_int(5));
@@ -75,4 +75,31 @@ fn bounds_respected4() {
       (vector-set! v 1 100))
     ";
     eval(&mut global, src);
-}
\ No newline at end of file
+}
+
+// A vector of 4096 values keeps them in a buffer of exactly the largest size class, and the
+// next push moves them to a buffer allocated on its own.
+#[test]
+fn vectors_grow_to_the_largest_size_class_and_past_it() {
+    let mut global = Global::new();
+    let src = "
+    (do
+      (set v [])
+      (set fill
+        (fn [n]
+          (if (< n 1)
+            (vector-length v)
+            (do
+              (vector-push! v n)
+              (fill (+ n -1))))))
+      (fill 4096))
+    ";
+    eval_and_assert_eq(&mut global, src, Val::from_int(4096));
+    eval_and_assert_eq(&mut global, "(vector-get v 4095)", Val::from_int(1));
+    eval_and_assert_eq(&mut global, "(fill 10000)", Val::from_int(14096));
+    eval(&mut global, "(gc)");
+    eval_and_assert_eq(&mut global, "(vector-get v 4095)", Val::from_int(1));
+    eval_and_assert_eq(&mut global, "(vector-get v 4096)", Val::from_int(10000));
+    eval_and_assert_eq(&mut global, "(vector-get v 14095)", Val::from_int(1));
+    assert_eq!(global.verify_heap(), Ok(()));
+}
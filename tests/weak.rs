@@ -0,0 +1,109 @@
+mod common;
+use common::*;
+
+#[test]
+fn weak_ref_is_cleared_once_target_dies() {
+    let mut global = Global::new();
+    eval(&mut global, "(do (set target {:a 1}) (set w (weak-ref target)))");
+    eval_and_assert_eq(&mut global, "(map-get (weak-get w) :a)", Val::from_int(1));
+
+    eval(&mut global, "(gc)");
+    eval_and_assert_eq(&mut global, "(map-get (weak-get w) :a)", Val::from_int(1));
+
+    eval(&mut global, "(do (set target 0) (gc))");
+    eval_and_assert_eq(&mut global, "(weak-get w)", Val::nil());
+}
+
+#[test]
+fn weak_ref_to_immediate_is_never_cleared() {
+    let mut global = Global::new();
+    eval(&mut global, "(do (set w (weak-ref 7)) (gc))");
+    eval_and_assert_eq(&mut global, "(weak-get w)", Val::from_int(7));
+}
+
+#[test]
+fn weak_map_drops_dead_keys() {
+    let mut global = Global::new();
+    let src = "
+    (do
+      (set cache (weak-map))
+      (set a {:name 1})
+      (set b {:name 2})
+      (map-put! cache a [1 2 3])
+      (map-put! cache b [4 5 6])
+      (gc))
+    ";
+    eval(&mut global, src);
+    eval_and_assert_eq(&mut global, "(map-length cache)", Val::from_int(2));
+
+    eval(&mut global, "(do (set a 0) (gc))");
+    eval_and_assert_eq(&mut global, "(map-length cache)", Val::from_int(1));
+    eval_and_assert_eq(&mut global, "(vector-get (map-get cache b) 2)", Val::from_int(6));
+
+    eval(&mut global, "(do (set b 0) (gc))");
+    eval_and_assert_eq(&mut global, "(map-length cache)", Val::from_int(0));
+}
+
+// A value that refers back to its own key must not keep the entry alive.
+#[test]
+fn weak_map_values_do_not_retain_keys() {
+    let mut global = Global::new();
+    let src = "
+    (do
+      (set cache (weak-map))
+      (set key {:k 1})
+      (map-put! cache key [key])
+      (set key 0)
+      (gc))
+    ";
+    eval(&mut global, src);
+    eval_and_assert_eq(&mut global, "(map-length cache)", Val::from_int(0));
+}
+
+// Keys only reachable through another entry's value stay as long as that entry does.
+#[test]
+fn weak_map_chains_through_values() {
+    let mut global = Global::new();
+    let src = "
+    (do
+      (set cache (weak-map))
+      (set first {:k 1})
+      (set second {:k 2})
+      (map-put! cache first second)
+      (map-put! cache second 2)
+      (set second 0)
+      (gc))
+    ";
+    eval(&mut global, src);
+    eval_and_assert_eq(&mut global, "(map-length cache)", Val::from_int(2));
+
+    eval(&mut global, "(do (set first 0) (gc))");
+    eval_and_assert_eq(&mut global, "(map-length cache)", Val::from_int(0));
+}
+
+#[test]
+fn weak_references_survive_incremental_marking() {
+    let mut global = Global::new();
+    global.set_pause_budget(Some(256));
+    let src = "
+    (do
+      (set keep [])
+      (set refs [])
+      (set churn
+        (fn [n]
+          (if (< n 1)
+            (vector-length refs)
+            (let [m {:n n :v [n n n]}
+                  garbage {:n n}]
+              (vector-push! keep m)
+              (vector-push! refs (weak-ref m))
+              (vector-push! refs (weak-ref garbage))
+              (churn (+ n -1))))))
+      (churn 1000))
+    ";
+    eval_and_assert_eq(&mut global, src, Val::from_int(2000));
+    eval(&mut global, "(gc)");
+    eval_and_assert_eq(&mut global, "(map-get (weak-get (vector-get refs 0)) :n)", Val::from_int(1000));
+    eval_and_assert_eq(&mut global, "(weak-get (vector-get refs 1))", Val::nil());
+    eval_and_assert_eq(&mut global, "(map-get (weak-get (vector-get refs 1998)) :n)", Val::from_int(1));
+}